@@ -0,0 +1,96 @@
+use std::path::{Path, PathBuf};
+
+use proc_macro::{TokenStream, TokenTree};
+
+/// Embeds all files under a directory (relative to the `Cargo.toml` of the calling crate) and
+/// expands to a `typst_as_lib::file_resolver::EmbeddedFileResolver`. Each file gets the virtual
+/// path relative to the directory, e.g. `./templates/invoice/main.typ` becomes
+/// `/invoice/main.typ`. Hidden files and directories are skipped.
+///
+/// Every file is embedded with `include_bytes!`, so changing an embedded file triggers a
+/// rebuild. Adding or removing files doesn't, because the directory is only read, when the
+/// macro is expanded. Rebuild after such changes (e.g. with `cargo clean -p <crate>`), or
+/// let a build script of the calling crate watch the directory:
+///
+/// ```rust,ignore
+/// // build.rs
+/// fn main() {
+///     println!("cargo:rerun-if-changed=templates");
+/// }
+/// ```
+///
+/// Example:
+/// ```rust,ignore
+/// let template_collection = TypstTemplateCollection::new(fonts)
+///     .add_file_resolver(embed_templates!("./templates"));
+/// ```
+#[proc_macro]
+pub fn embed_templates(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(output) => output,
+        Err(message) => format!("::core::compile_error!({message:?})")
+            .parse()
+            .expect("compile_error! is valid"),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let dir = parse_string_literal(input)?;
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| "CARGO_MANIFEST_DIR is not set".to_owned())?;
+    let root = Path::new(&manifest_dir).join(dir);
+    let mut files = Vec::new();
+    collect_files(&root, &mut files)
+        .map_err(|err| format!("could not read {}: {err}", root.display()))?;
+    files.sort();
+
+    let mut entries = String::new();
+    for path in files {
+        let relative = path
+            .strip_prefix(&root)
+            .map_err(|err| err.to_string())?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let vpath = format!("/{relative}");
+        let path = path
+            .to_str()
+            .ok_or_else(|| format!("path is not valid UTF-8: {}", path.display()))?;
+        entries.push_str(&format!(
+            "({vpath:?}, ::core::include_bytes!({path:?}) as &'static [u8]),"
+        ));
+    }
+    let output = format!("::typst_as_lib::file_resolver::EmbeddedFileResolver::new(&[{entries}])");
+    output.parse().map_err(|_| "could not expand".to_owned())
+}
+
+fn parse_string_literal(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let literal = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => literal.to_string(),
+        _ => return Err("expected a string literal with the path of a directory".to_owned()),
+    };
+    literal
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+        .filter(|literal| !literal.contains('\\'))
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| "expected a plain string literal without escapes".to_owned())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,502 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use ecow::EcoVec;
+use typst::diag::{SourceDiagnostic, Warned};
+use typst::foundations::{Datetime, Dict};
+use typst::model::Document;
+use typst::syntax::{package::PackageSpec, FileId, Span, VirtualPath};
+use typst::utils::hash128;
+
+use crate::result_cache::Dependency;
+use crate::util::write_atomically;
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplateCollection};
+
+/// Caches exported artifacts (e.g. PDF or SVG bytes) in a directory, that can be shared between
+/// multiple processes (e.g. a mounted volume).
+///
+/// For each compilation (main source file and inputs) the cache stores the files, that were
+/// read, with the hashes of their contents. An artifact is only reused, if all of these files
+/// are unchanged. When the directory exceeds `max_bytes`, the least recently used entries are
+/// removed. The warnings of the compilation are cached with the artifact, but without their
+/// spans, as `FileId`s are interned per process.
+///
+/// Other files in the directory are left alone, but don't count towards `max_bytes`.
+///
+/// See `TypstTemplateCollection::with_artifact_cache()`.
+#[derive(Debug, Clone)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ArtifactCache {
+    pub fn new<P>(dir: P, max_bytes: u64) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            dir: dir.into(),
+            max_bytes,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Remove all cached artifacts. Other files in the directory are kept.
+    pub fn clear(&self) -> io::Result<()> {
+        for (path, _) in self.cache_files()? {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Files, that the cache created, with their metadata.
+    fn cache_files(&self) -> io::Result<Vec<(PathBuf, fs::Metadata)>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() && is_cache_file_name(&entry.file_name().to_string_lossy()) {
+                files.push((entry.path(), metadata));
+            }
+        }
+        Ok(files)
+    }
+
+    fn dependencies_path(&self, compile_key: u128) -> PathBuf {
+        self.dir.join(format!("{compile_key:032x}.deps"))
+    }
+
+    fn artifact_path(&self, artifact_key: u128, format: &str) -> PathBuf {
+        self.dir.join(format!("{artifact_key:032x}.{format}"))
+    }
+
+    fn read_dependencies(&self, compile_key: u128) -> Option<Vec<Dependency>> {
+        let index = fs::read_to_string(self.dependencies_path(compile_key)).ok()?;
+        index.lines().map(parse_dependency).collect()
+    }
+
+    fn warnings_path(&self, artifact_key: u128) -> PathBuf {
+        self.dir.join(format!("{artifact_key:032x}.warnings"))
+    }
+
+    fn read_warnings(&self, artifact_key: u128) -> Option<EcoVec<SourceDiagnostic>> {
+        let warnings = fs::read_to_string(self.warnings_path(artifact_key)).ok()?;
+        Some(parse_warnings(&warnings))
+    }
+
+    fn read_artifact(&self, artifact_key: u128, format: &str) -> Option<Vec<u8>> {
+        let path = self.artifact_path(artifact_key, format);
+        let artifact = fs::read(&path).ok()?;
+        // The modification time is used to find the least recently used entries.
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(artifact)
+    }
+
+    fn write(
+        &self,
+        compile_key: u128,
+        dependencies: &[Dependency],
+        artifact_key: u128,
+        format: &str,
+        artifact: &[u8],
+        warnings: &[SourceDiagnostic],
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let index = dependencies.iter().fold(String::new(), |mut index, d| {
+            let _ = writeln!(index, "{}", format_dependency(d));
+            index
+        });
+        write_atomically(&self.dependencies_path(compile_key), index.as_bytes())?;
+        write_atomically(
+            &self.warnings_path(artifact_key),
+            format_warnings(warnings).as_bytes(),
+        )?;
+        write_atomically(&self.artifact_path(artifact_key, format), artifact)?;
+        self.evict()
+    }
+
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for (path, metadata) in self.cache_files()? {
+            total += metadata.len();
+            entries.push((metadata.modified()?, metadata.len(), path));
+        }
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Store exported artifacts in an `ArtifactCache` on disk.
+    /// See `TypstTemplateCollection::compile_and_export()`.
+    pub fn with_artifact_cache(mut self, artifact_cache: ArtifactCache) -> Self {
+        self.with_artifact_cache_mut(artifact_cache);
+        self
+    }
+
+    /// Store exported artifacts in an `ArtifactCache` on disk.
+    pub fn with_artifact_cache_mut(&mut self, artifact_cache: ArtifactCache) {
+        self.artifact_cache = Some(artifact_cache);
+    }
+
+    /// Compile with a `Dict` as input and export the document with `export`. If an
+    /// `ArtifactCache` is set, the artifact is taken from the cache, when neither the inputs nor
+    /// any of the files changed. Compilations with `HostInfo` are not cached. `format` is used
+    /// as file extension and must be different for different exporters or export options.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst::foundations::Dict;
+    /// # use typst_as_lib::artifact_cache::ArtifactCache;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # static TEMPLATE_ID: &str = "/template.typ";
+    /// # let inputs = Dict::new();
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let cache_dir = std::env::temp_dir().join("typst-as-lib-doctest-artifact-cache");
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([(TEMPLATE_ID, "Hello")])
+    /// #     .with_artifact_cache(ArtifactCache::new(cache_dir, 1 << 20));
+    /// let pdf = template_collection
+    ///     .compile_and_export(TEMPLATE_ID, inputs, "pdf", |doc| {
+    ///         typst_pdf::pdf(doc, &Default::default())
+    ///     })
+    ///     .output
+    ///     .expect("Typst error!");
+    /// ```
+    pub fn compile_and_export<F, D, E, Err>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        format: &str,
+        export: E,
+    ) -> Warned<Result<Vec<u8>, TypstAsLibError>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+        E: FnOnce(&Document) -> Result<Vec<u8>, Err>,
+        Err: Into<TypstAsLibError>,
+    {
+        let FileIdNewType(main_source_id) = main_source_id.into();
+        let inputs = inputs.into();
+        // Like the result cache, artifacts with the current time of `HostInfo` are not cached.
+        let artifact_cache = self
+            .artifact_cache
+            .as_ref()
+            .filter(|_| self.host_info.is_none());
+        let Some(artifact_cache) = artifact_cache else {
+            let Warned { output, warnings } = self
+                .compile_helper(main_source_id, Some(inputs), None)
+                .output;
+            let output = output.and_then(|document| export(&document).map_err(Into::into));
+            return Warned { output, warnings };
+        };
+
+        // `FileId`s are interned per process, so the key contains the path instead.
+        let compile_key = hash128(&(
+            main_source_id.package().map(ToString::to_string),
+            main_source_id.vpath().as_rooted_path(),
+            &inputs,
+            &self.default_inputs,
+            self.input_merge,
+            &self.inject_location,
+            &self.injections,
+            &self.book,
+            &self.preamble,
+            self.source_normalization,
+        ));
+        let now = self.now();
+        if let Some(dependencies) = artifact_cache.read_dependencies(compile_key) {
+            let unchanged = dependencies
+                .iter()
+                .all(|dependency| self.is_unchanged(dependency, now));
+            let artifact_key = artifact_key(compile_key, &dependencies, format);
+            let cached = unchanged
+                .then(|| {
+                    let warnings = artifact_cache.read_warnings(artifact_key)?;
+                    let artifact = artifact_cache.read_artifact(artifact_key, format)?;
+                    Some((artifact, warnings))
+                })
+                .flatten();
+            if let Some((artifact, warnings)) = cached {
+                return Warned {
+                    output: Ok(artifact),
+                    warnings,
+                };
+            }
+        }
+
+        let (reported, dependencies) =
+            self.compile_tracked(main_source_id, Some(inputs), None, None, true, now);
+        let Warned { output, warnings } = reported.output;
+        let output = output.and_then(|document| export(&document).map_err(Into::into));
+        if let (Ok(artifact), Some(dependencies)) = (&output, dependencies) {
+            let artifact_key = artifact_key(compile_key, &dependencies, format);
+            // The cache is an optimization, so failing to write it is not an error.
+            let _ = artifact_cache.write(
+                compile_key,
+                &dependencies,
+                artifact_key,
+                format,
+                artifact,
+                &warnings,
+            );
+        }
+        Warned { output, warnings }
+    }
+}
+
+fn artifact_key(compile_key: u128, dependencies: &[Dependency], format: &str) -> u128 {
+    let dependencies: Vec<String> = dependencies.iter().map(format_dependency).collect();
+    hash128(&(compile_key, dependencies, format))
+}
+
+/// Names of the files, that the cache creates: `<32 hex digits>.<extension>`, including
+/// temporary files.
+fn is_cache_file_name(name: &str) -> bool {
+    name.len() > 33
+        && name.as_bytes()[32] == b'.'
+        && name.as_bytes()[..32]
+            .iter()
+            .all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// One line per warning and hint: `warning <message>` or `hint <hint>`. Backslashes and line
+/// breaks are escaped.
+fn format_warnings(warnings: &[SourceDiagnostic]) -> String {
+    let mut text = String::new();
+    for warning in warnings {
+        let _ = writeln!(text, "warning {}", escape_line(&warning.message));
+        for hint in &warning.hints {
+            let _ = writeln!(text, "hint {}", escape_line(hint));
+        }
+    }
+    text
+}
+
+fn parse_warnings(text: &str) -> EcoVec<SourceDiagnostic> {
+    let mut warnings = EcoVec::new();
+    for line in text.lines() {
+        if let Some(message) = line.strip_prefix("warning ") {
+            warnings.push(SourceDiagnostic::warning(
+                Span::detached(),
+                unescape_line(message),
+            ));
+        } else if let Some(hint) = line.strip_prefix("hint ") {
+            if let Some(warning) = warnings.make_mut().last_mut() {
+                warning.hint(unescape_line(hint));
+            }
+        }
+    }
+    warnings
+}
+
+fn escape_line(line: &str) -> String {
+    line.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_line(line: &str) -> String {
+    let mut unescaped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => (),
+        }
+    }
+    unescaped
+}
+
+fn format_dependency(dependency: &Dependency) -> String {
+    match dependency {
+        Dependency::Source(id, hash) => format!("source {hash:032x} {}", format_file_id(*id)),
+        Dependency::File(id, hash) => format!("file {hash:032x} {}", format_file_id(*id)),
+        Dependency::Today(offset, today) => {
+            let offset = offset.map_or_else(|| "-".to_owned(), |offset| offset.to_string());
+            let today = today
+                .and_then(|today| Some((today.year()?, today.month()?, today.day()?)))
+                .map_or_else(
+                    || "-".to_owned(),
+                    |(year, month, day)| format!("{year}-{month}-{day}"),
+                );
+            format!("today {offset} {today}")
+        }
+    }
+}
+
+fn format_file_id(id: FileId) -> String {
+    let package = id
+        .package()
+        .map_or_else(|| "-".to_owned(), ToString::to_string);
+    format!("{package} {}", id.vpath().as_rooted_path().display())
+}
+
+fn parse_dependency(line: &str) -> Option<Dependency> {
+    let mut parts = line.splitn(4, ' ');
+    let kind = parts.next()?;
+    if kind == "today" {
+        let offset = match parts.next()? {
+            "-" => None,
+            offset => Some(offset.parse().ok()?),
+        };
+        let today = match parts.next()? {
+            "-" => None,
+            today => {
+                let mut ymd = today.splitn(3, '-');
+                Some(Datetime::from_ymd(
+                    ymd.next()?.parse().ok()?,
+                    ymd.next()?.parse().ok()?,
+                    ymd.next()?.parse().ok()?,
+                )?)
+            }
+        };
+        return Some(Dependency::Today(offset, today));
+    }
+    let hash = u128::from_str_radix(parts.next()?, 16).ok()?;
+    let package = match parts.next()? {
+        "-" => None,
+        package => Some(PackageSpec::from_str(package).ok()?),
+    };
+    let id = FileId::new(package, VirtualPath::new(parts.next()?));
+    match kind {
+        "source" => Some(Dependency::Source(id, hash)),
+        "file" => Some(Dependency::File(id, hash)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    use typst::foundations::IntoValue;
+
+    use super::*;
+    use crate::host::HostInfo;
+    use crate::normalize::SourceNormalization;
+    use crate::test_util::{font, temp_dir, MemoryResolver};
+
+    fn collection(resolver: &Arc<MemoryResolver>, dir: &Path) -> TypstTemplateCollection {
+        TypstTemplateCollection::new(vec![font()])
+            .add_file_resolver(Arc::clone(resolver))
+            .with_artifact_cache(ArtifactCache::new(dir, u64::MAX))
+    }
+
+    /// Exports the page count and counts the exports, i.e. the cache misses.
+    fn export(
+        collection: &TypstTemplateCollection,
+        inputs: Dict,
+        exports: &Cell<usize>,
+    ) -> Vec<u8> {
+        collection
+            .compile_and_export("/main.typ", inputs, "txt", |document| {
+                exports.set(exports.get() + 1);
+                Ok::<_, TypstAsLibError>(document.pages.len().to_string().into_bytes())
+            })
+            .output
+            .unwrap()
+    }
+
+    fn resolver() -> Arc<MemoryResolver> {
+        let resolver = MemoryResolver::default()
+            .with_source("/main.typ", "#include \"body.typ\"")
+            .with_source("/body.typ", "Hello");
+        Arc::new(resolver)
+    }
+
+    #[test]
+    fn unchanged_compilation_hits() {
+        let dir = temp_dir("artifact-cache-hit");
+        let exports = Cell::new(0);
+        let resolver = resolver();
+        let artifact = export(&collection(&resolver, &dir), Dict::new(), &exports);
+        // The cache is shared with other collections, e.g. in other processes.
+        let cached = export(&collection(&resolver, &dir), Dict::new(), &exports);
+        assert_eq!(artifact, cached);
+        assert_eq!(exports.get(), 1);
+    }
+
+    #[test]
+    fn changed_dependency_misses() {
+        let dir = temp_dir("artifact-cache-dependency");
+        let exports = Cell::new(0);
+        let resolver = resolver();
+        let collection = collection(&resolver, &dir);
+        assert_eq!(export(&collection, Dict::new(), &exports), b"1");
+        resolver.set_source("/body.typ", "Hello #pagebreak() World");
+        assert_eq!(export(&collection, Dict::new(), &exports), b"2");
+        assert_eq!(exports.get(), 2);
+    }
+
+    #[test]
+    fn changed_inputs_miss() {
+        let dir = temp_dir("artifact-cache-inputs");
+        let exports = Cell::new(0);
+        let collection = collection(&resolver(), &dir);
+        let mut inputs = Dict::new();
+        export(&collection, inputs.clone(), &exports);
+        inputs.insert("name".into(), "World".into_value());
+        export(&collection, inputs, &exports);
+        assert_eq!(exports.get(), 2);
+    }
+
+    #[test]
+    fn changed_preamble_or_normalization_misses() {
+        let dir = temp_dir("artifact-cache-preamble");
+        let exports = Cell::new(0);
+        let resolver = resolver();
+        export(&collection(&resolver, &dir), Dict::new(), &exports);
+        let with_preamble = collection(&resolver, &dir).with_preamble("#set page(width: 10cm)");
+        export(&with_preamble, Dict::new(), &exports);
+        assert_eq!(exports.get(), 2);
+        let other_preamble = collection(&resolver, &dir).with_preamble("#set page(width: 12cm)");
+        export(&other_preamble, Dict::new(), &exports);
+        assert_eq!(exports.get(), 3);
+        let normalized = collection(&resolver, &dir)
+            .with_source_normalization(SourceNormalization::new().with_tab_width(4));
+        export(&normalized, Dict::new(), &exports);
+        assert_eq!(exports.get(), 4);
+    }
+
+    #[test]
+    fn host_info_is_not_cached() {
+        let dir = temp_dir("artifact-cache-host");
+        let exports = Cell::new(0);
+        let collection = collection(&resolver(), &dir)
+            .with_host_info(HostInfo::new())
+            .unwrap();
+        export(&collection, Dict::new(), &exports);
+        export(&collection, Dict::new(), &exports);
+        assert_eq!(exports.get(), 2);
+    }
+}
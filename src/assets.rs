@@ -0,0 +1,147 @@
+use typst::foundations::Bytes;
+use typst::syntax::{FileId, VirtualPath};
+
+use crate::file_resolver::StaticFileResolver;
+use crate::{TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Module, that maps the names of the assets to their virtual paths (`#import assets: logo`).
+const ASSETS_MODULE: &str = "assets";
+/// Virtual directory of the assets.
+const ASSETS_DIR: &str = "/assets";
+
+/// Images and other files, that are registered under logical names, so Rust and typst code
+/// don't need to share path strings. Each asset is served under `/assets/<name>.<extension>`
+/// and its path is available in typst with `#import assets: <name>`.
+/// See `TypstTemplateCollection::with_assets()`.
+///
+/// Names should be valid typst identifiers.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::assets::AssetRegistry;
+/// # use typst_as_lib::TypstTemplate;
+/// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+/// # static LOGO_PNG: &[u8] = include_bytes!("../examples/templates/images/typst.png");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// # let signature_svg = b"<svg xmlns='http://www.w3.org/2000/svg'/>".to_vec();
+/// let assets = AssetRegistry::new()
+///     .register("logo", LOGO_PNG)
+///     .register_with_extension("signature", "svg", signature_svg);
+/// let template = TypstTemplate::new(fonts, TEMPLATE).with_assets(assets)?;
+/// // In typst:
+/// // #import assets: logo
+/// // #image(logo, width: 3cm)
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AssetRegistry {
+    /// `(name, virtual path, content)`
+    assets: Vec<(String, String, Bytes)>,
+}
+
+impl AssetRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Register an asset. The extension of the virtual path is detected from the content
+    /// (PNG, JPEG, GIF, WebP, SVG, PDF). Use `AssetRegistry::register_with_extension()` for
+    /// other files.
+    pub fn register<S, B>(self, name: S, content: B) -> Self
+    where
+        S: Into<String>,
+        B: Into<Bytes>,
+    {
+        let content = content.into();
+        let extension = detect_extension(&content);
+        self.register_helper(name.into(), extension, content)
+    }
+
+    /// Register an asset with the extension (without dot) of its virtual path, which typst
+    /// uses to detect the format of e.g. images.
+    pub fn register_with_extension<S, E, B>(self, name: S, extension: E, content: B) -> Self
+    where
+        S: Into<String>,
+        E: AsRef<str>,
+        B: Into<Bytes>,
+    {
+        self.register_helper(name.into(), Some(extension.as_ref()), content.into())
+    }
+
+    fn register_helper(mut self, name: String, extension: Option<&str>, content: Bytes) -> Self {
+        let path = match extension {
+            Some(extension) => format!("{ASSETS_DIR}/{name}.{extension}"),
+            None => format!("{ASSETS_DIR}/{name}"),
+        };
+        self.assets.retain(|(other, ..)| *other != name);
+        self.assets.push((name, path, content));
+        self
+    }
+
+    /// Virtual path of the asset.
+    pub fn path(&self, name: &str) -> Option<&str> {
+        self.assets
+            .iter()
+            .find(|(other, ..)| other == name)
+            .map(|(_, path, _)| path.as_str())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.assets.iter().map(|(name, ..)| name.as_str())
+    }
+}
+
+fn detect_extension(content: &[u8]) -> Option<&'static str> {
+    let extension = if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "png"
+    } else if content.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "jpg"
+    } else if content.starts_with(b"GIF87a") || content.starts_with(b"GIF89a") {
+        "gif"
+    } else if content.starts_with(b"RIFF") && content.get(8..12) == Some(b"WEBP") {
+        "webp"
+    } else if content.starts_with(b"%PDF-") {
+        "pdf"
+    } else {
+        let head = &content[..content.len().min(1024)];
+        let head = String::from_utf8_lossy(head);
+        if !head.contains("<svg") {
+            return None;
+        }
+        "svg"
+    };
+    Some(extension)
+}
+
+impl TypstTemplateCollection {
+    /// Serve the assets and make their paths available in typst with
+    /// `#import assets: <name>`. See `AssetRegistry`.
+    pub fn with_assets(mut self, assets: AssetRegistry) -> Result<Self, TypstAsLibError> {
+        self.with_assets_mut(assets)?;
+        Ok(self)
+    }
+
+    /// Serve the assets and make their paths available in typst. See `AssetRegistry`.
+    pub fn with_assets_mut(&mut self, assets: AssetRegistry) -> Result<(), TypstAsLibError> {
+        let AssetRegistry { assets } = assets;
+        for (name, path, _) in &assets {
+            self.add_injection_mut(ASSETS_MODULE, name.as_str(), path.as_str())?;
+        }
+        self.add_file_resolver_mut(StaticFileResolver::new(
+            assets
+                .into_iter()
+                .map(|(_, path, content)| (FileId::new(None, VirtualPath::new(&path)), content)),
+        ));
+        Ok(())
+    }
+}
+
+impl TypstTemplate {
+    /// Serve the assets and make their paths available in typst with
+    /// `#import assets: <name>`. See `AssetRegistry`.
+    pub fn with_assets(mut self, assets: AssetRegistry) -> Result<Self, TypstAsLibError> {
+        self.collection.with_assets_mut(assets)?;
+        Ok(self)
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::{Duration, Instant};
+
+use typst::diag::Warned;
+use typst::foundations::Dict;
+use typst::model::Document;
+
+use crate::report::{CompileTimings, Reported};
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Timings of repeated compilations. See `TypstTemplateCollection::benchmark()`.
+///
+/// typst evaluates and lays out the document in one call, so both are part of `compile`.
+#[derive(Debug, Clone, Default)]
+pub struct BenchmarkReport {
+    pub iterations: usize,
+    pub library_injection: TimingDistribution,
+    /// Time spent in file resolvers. Is part of `compile`.
+    pub resolve: TimingDistribution,
+    /// Time of `typst::compile()`.
+    pub compile: TimingDistribution,
+    pub eviction: TimingDistribution,
+    /// Time of the export, if the benchmark exported the document.
+    pub export: Option<TimingDistribution>,
+    /// Wall-clock time of each iteration.
+    pub total: TimingDistribution,
+}
+
+/// Distribution of the timings of a phase over all iterations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingDistribution {
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub median: Duration,
+    /// 95th percentile.
+    pub p95: Duration,
+}
+
+impl TimingDistribution {
+    pub fn from_samples(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return Default::default();
+        }
+        samples.sort();
+        let percentile = |percentile: usize| samples[(samples.len() - 1) * percentile / 100];
+        Self {
+            min: samples[0],
+            max: samples[samples.len() - 1],
+            mean: samples.iter().sum::<Duration>() / samples.len() as u32,
+            median: percentile(50),
+            p95: percentile(95),
+        }
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Compile `iterations` times and report the timings of the compilation phases, e.g. to
+    /// compare caching and eviction settings with the actual templates. With a `ResultCache`,
+    /// all iterations after the first one are taken from the cache.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst::foundations::{dict, Array};
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+    /// # static TEMPLATE_ID: &str = "/template.typ";
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([(TEMPLATE_ID, TEMPLATE)]);
+    /// # let inputs = dict! { "v" => Array::new() };
+    /// let report = template_collection.benchmark(TEMPLATE_ID, inputs, 50)?;
+    /// println!("median: {:?}", report.total.median);
+    /// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+    /// ```
+    pub fn benchmark<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        iterations: usize,
+    ) -> Result<BenchmarkReport, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let FileIdNewType(main_source_id) = main_source_id.into();
+        let inputs = inputs.into();
+        run(iterations, None::<fn(&Document)>, || {
+            self.compile_with_input_and_report(main_source_id, inputs.clone())
+        })
+    }
+
+    /// Like `TypstTemplateCollection::benchmark()`, but also exports each document with
+    /// `export`, e.g. `|doc| { typst_pdf::pdf(doc, &Default::default()); }`.
+    pub fn benchmark_with_export<F, D, E>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        iterations: usize,
+        export: E,
+    ) -> Result<BenchmarkReport, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+        E: Fn(&Document),
+    {
+        let FileIdNewType(main_source_id) = main_source_id.into();
+        let inputs = inputs.into();
+        run(iterations, Some(export), || {
+            self.compile_with_input_and_report(main_source_id, inputs.clone())
+        })
+    }
+}
+
+impl TypstTemplate {
+    /// Compile `iterations` times and report the timings of the compilation phases.
+    /// See `TypstTemplateCollection::benchmark()`.
+    pub fn benchmark<D>(
+        &self,
+        inputs: D,
+        iterations: usize,
+    ) -> Result<BenchmarkReport, TypstAsLibError>
+    where
+        D: Into<Dict>,
+    {
+        let inputs = self.validated_input(inputs)?;
+        self.collection
+            .benchmark(self.source_id, inputs, iterations)
+    }
+
+    /// Like `TypstTemplate::benchmark()`, but also exports each document with `export`.
+    pub fn benchmark_with_export<D, E>(
+        &self,
+        inputs: D,
+        iterations: usize,
+        export: E,
+    ) -> Result<BenchmarkReport, TypstAsLibError>
+    where
+        D: Into<Dict>,
+        E: Fn(&Document),
+    {
+        let inputs = self.validated_input(inputs)?;
+        self.collection
+            .benchmark_with_export(self.source_id, inputs, iterations, export)
+    }
+}
+
+fn run<C, E>(
+    iterations: usize,
+    export: Option<E>,
+    compile: C,
+) -> Result<BenchmarkReport, TypstAsLibError>
+where
+    C: Fn() -> Reported<Warned<Result<Document, TypstAsLibError>>>,
+    E: Fn(&Document),
+{
+    let mut timings = Vec::with_capacity(iterations);
+    let mut exports = Vec::with_capacity(iterations);
+    let mut totals = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let Reported { output, report } = compile();
+        let document = output.output?;
+        if let Some(export) = &export {
+            let export_start = Instant::now();
+            export(&document);
+            exports.push(export_start.elapsed());
+        }
+        totals.push(start.elapsed());
+        timings.push(report.timings);
+    }
+    let phase = |phase: fn(&CompileTimings) -> Duration| {
+        TimingDistribution::from_samples(timings.iter().map(phase).collect())
+    };
+    Ok(BenchmarkReport {
+        iterations,
+        library_injection: phase(|timings| timings.library_injection),
+        resolve: phase(|timings| timings.resolve),
+        compile: phase(|timings| timings.compile),
+        eviction: phase(|timings| timings.eviction),
+        export: export.map(|_| TimingDistribution::from_samples(exports)),
+        total: TimingDistribution::from_samples(totals),
+    })
+}
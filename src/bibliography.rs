@@ -0,0 +1,172 @@
+use typst::foundations::Bytes;
+
+use crate::file_resolver::StaticFileResolver;
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Entry of a BibTeX bibliography, e.g. built from database records. See `bibtex_bytes()`.
+/// Field values are written as they are, so they can contain BibTeX markup, but their braces
+/// must be balanced.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::bibliography::{bibtex_bytes, BibEntry};
+/// # use typst_as_lib::TypstTemplate;
+/// # struct Paper {
+/// #     key: String,
+/// #     title: String,
+/// #     authors: Vec<String>,
+/// #     year: u32,
+/// # }
+/// # let papers: Vec<Paper> = Vec::new();
+/// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+/// # static IEEE_CSL: &[u8] = b"<style xmlns='http://purl.org/net/xbiblio/csl'/>";
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// let entries = papers.iter().map(|paper| {
+///     BibEntry::new("article", &paper.key)
+///         .with_field("title", &paper.title)
+///         .with_field("author", paper.authors.join(" and "))
+///         .with_field("year", paper.year.to_string())
+/// });
+/// let template = TypstTemplate::new(fonts, TEMPLATE)
+///     .with_bibliography("/refs.bib", bibtex_bytes(entries))
+///     .with_csl_style("/ieee.csl", IEEE_CSL);
+/// template.verify_files(["/refs.bib", "/ieee.csl"])?;
+/// // In typst: #bibliography("/refs.bib", style: "/ieee.csl")
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BibEntry {
+    /// E.g. `article`, `book` or `misc`.
+    pub entry_type: String,
+    /// Citation key, that is used with `@key` or `#cite(<key>)`.
+    pub key: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl BibEntry {
+    pub fn new<T, K>(entry_type: T, key: K) -> Self
+    where
+        T: Into<String>,
+        K: Into<String>,
+    {
+        Self {
+            entry_type: entry_type.into(),
+            key: key.into(),
+            fields: Default::default(),
+        }
+    }
+
+    pub fn with_field<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.fields.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn to_bibtex(&self) -> String {
+        let Self {
+            entry_type,
+            key,
+            fields,
+        } = self;
+        let mut bibtex = format!("@{entry_type}{{{key},\n");
+        for (name, value) in fields {
+            bibtex.push_str(&format!("  {name} = {{{value}}},\n"));
+        }
+        bibtex.push_str("}\n");
+        bibtex
+    }
+}
+
+/// Serialize the entries as a BibTeX file for typst's `bibliography()`.
+pub fn bibtex_bytes<I>(entries: I) -> Bytes
+where
+    I: IntoIterator<Item = BibEntry>,
+{
+    let bibtex = entries
+        .into_iter()
+        .map(|entry| entry.to_bibtex())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Bytes::from(bibtex.into_bytes())
+}
+
+impl TypstTemplateCollection {
+    /// Adds a bibliography file (`.bib` or hayagriva `.yml`) under the virtual path. The
+    /// extension of the path decides, how typst parses it.
+    pub fn with_bibliography<F, B>(mut self, path: F, content: B) -> Self
+    where
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        self.with_bibliography_mut(path, content);
+        self
+    }
+
+    pub fn with_bibliography_mut<F, B>(&mut self, path: F, content: B)
+    where
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        self.add_file_resolver_mut(StaticFileResolver::new([(path, content)]));
+    }
+
+    /// Adds a CSL style (`.csl`) under the virtual path, that can be used with
+    /// `#bibliography(.., style: "/path.csl")`.
+    pub fn with_csl_style<F, B>(mut self, path: F, content: B) -> Self
+    where
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        self.with_bibliography_mut(path, content);
+        self
+    }
+
+    /// Check, that the files can be resolved, e.g. bibliographies and CSL styles before the
+    /// first compilation. Returns the error of the first file, that could not be resolved.
+    pub fn verify_files<I, F>(&self, paths: I) -> Result<(), TypstAsLibError>
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<FileIdNewType>,
+    {
+        for path in paths {
+            let FileIdNewType(id) = path.into();
+            self.resolve_file(id, |_| ())?;
+        }
+        Ok(())
+    }
+}
+
+impl TypstTemplate {
+    /// Adds a bibliography file. See `TypstTemplateCollection::with_bibliography()`.
+    pub fn with_bibliography<F, B>(mut self, path: F, content: B) -> Self
+    where
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        self.collection.with_bibliography_mut(path, content);
+        self
+    }
+
+    /// Adds a CSL style. See `TypstTemplateCollection::with_csl_style()`.
+    pub fn with_csl_style<F, B>(mut self, path: F, content: B) -> Self
+    where
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        self.collection.with_bibliography_mut(path, content);
+        self
+    }
+
+    /// Check, that the files can be resolved. See `TypstTemplateCollection::verify_files()`.
+    pub fn verify_files<I, F>(&self, paths: I) -> Result<(), TypstAsLibError>
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<FileIdNewType>,
+    {
+        self.collection.verify_files(paths)
+    }
+}
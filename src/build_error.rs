@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use typst::syntax::FileId;
+
+use crate::{TypstTemplate, TypstTemplateCollection};
+
+/// Configuration errors, that `TypstTemplateCollection::try_build()` found. Without the
+/// check, they only show up (in confusing ways) when compiling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildError {
+    pub issues: Vec<BuildIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildIssue {
+    /// No fonts were added, so typst can't render any text.
+    NoFonts,
+    /// Multiple static file resolvers serve the file. Only the first one is used.
+    DuplicateFileId(FileId),
+    /// The main source file of the template (or a registered template) can't be resolved.
+    MissingMainSource(FileId),
+    /// Network access is denied, but the file resolver with the name may access the network.
+    /// See `TypstTemplateCollection::deny_network()`.
+    NetworkAccess(String),
+}
+
+impl fmt::Display for BuildIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildIssue::NoFonts => write!(f, "no fonts were added"),
+            BuildIssue::DuplicateFileId(id) => {
+                write!(f, "{id:?} is served by multiple file resolvers")
+            }
+            BuildIssue::MissingMainSource(id) => write!(f, "main source {id:?} does not exist"),
+            BuildIssue::NetworkAccess(name) => {
+                write!(f, "file resolver {name} may access the network")
+            }
+        }
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let issues: Vec<_> = self.issues.iter().map(ToString::to_string).collect();
+        write!(f, "{}", issues.join("; "))
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl TypstTemplateCollection {
+    /// Check the configuration: fonts, duplicate files of static file resolvers (see
+    /// `FileResolver::file_ids()`), the main source files of the registered templates and,
+    /// if network access is denied, file resolvers, that may access the network.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let fonts = vec![font];
+    /// # let sources = [("/template.typ", TEMPLATE)];
+    /// let collection = TypstTemplateCollection::new(fonts)
+    ///     .with_static_source_file_resolver(sources)
+    ///     .try_build()?;
+    /// # Ok::<(), typst_as_lib::build_error::BuildError>(())
+    /// ```
+    pub fn try_build(self) -> Result<Self, BuildError> {
+        let issues = self.build_issues(&[]);
+        if issues.is_empty() {
+            Ok(self)
+        } else {
+            Err(BuildError { issues })
+        }
+    }
+
+    fn build_issues(&self, main_source_ids: &[FileId]) -> Vec<BuildIssue> {
+        let mut issues = Vec::new();
+        if self.fonts.is_empty() {
+            issues.push(BuildIssue::NoFonts);
+        }
+
+        let mut seen = HashSet::new();
+        let mut duplicates = HashSet::new();
+        for file_resolver in &self.file_resolvers {
+            for id in file_resolver.file_ids() {
+                if !seen.insert(id) && duplicates.insert(id) {
+                    issues.push(BuildIssue::DuplicateFileId(id));
+                }
+            }
+        }
+
+        if self.network_denied() {
+            let network_resolvers = self
+                .file_resolvers
+                .iter()
+                .chain(&self.fallback_file_resolver)
+                .filter(|file_resolver| file_resolver.uses_network());
+            for file_resolver in network_resolvers {
+                issues.push(BuildIssue::NetworkAccess(file_resolver.name().into_owned()));
+            }
+        }
+
+        let registered = self
+            .registry
+            .names()
+            .filter_map(|name| self.registry.get(name))
+            .map(|template| template.main_source_id());
+        for id in main_source_ids.iter().copied().chain(registered) {
+            if self.resolve_source(id, |_| ()).is_err() {
+                issues.push(BuildIssue::MissingMainSource(id));
+            }
+        }
+        issues
+    }
+
+    /// Make `TypstTemplateCollection::try_build()` fail, if a file resolver may access the
+    /// network (see `FileResolver::uses_network()`). With feature `no-network`, network
+    /// access is always denied and package downloads fail.
+    pub fn deny_network(mut self) -> Self {
+        self.deny_network_mut();
+        self
+    }
+
+    /// See `TypstTemplateCollection::deny_network()`.
+    pub fn deny_network_mut(&mut self) {
+        self.deny_network = true;
+    }
+
+    pub(crate) fn network_denied(&self) -> bool {
+        self.deny_network || cfg!(feature = "no-network")
+    }
+}
+
+impl TypstTemplate {
+    /// Make `TypstTemplate::try_build()` fail, if a file resolver may access the network.
+    /// See `TypstTemplateCollection::deny_network()`.
+    pub fn deny_network(mut self) -> Self {
+        self.collection.deny_network_mut();
+        self
+    }
+
+    /// Check the configuration, including the main source file.
+    /// See `TypstTemplateCollection::try_build()`.
+    pub fn try_build(self) -> Result<Self, BuildError> {
+        let issues = self.collection.build_issues(&[self.source_id]);
+        if issues.is_empty() {
+            Ok(self)
+        } else {
+            Err(BuildError { issues })
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read;
+use std::str::FromStr;
+
+use binstall_tar::{Archive, Builder, Header};
+use chrono::{DateTime, Utc};
+use typst::diag::{FileResult, Warned};
+use typst::foundations::{Bytes, Dict, Value};
+use typst::model::Document;
+use typst::syntax::{package::PackageSpec, FileId, Source, VirtualPath};
+use typst::text::Font;
+
+use crate::file_resolver::FileResolver;
+use crate::result_cache::Dependency;
+use crate::util::{bytes_to_source, not_found};
+use crate::{FileIdNewType, InjectLocation, TypstAsLibError, TypstTemplateCollection};
+
+const MANIFEST: &str = "manifest.tsv";
+const INPUTS: &str = "inputs.json";
+const FILES_DIR: &str = "files";
+/// Directory of files, that are not part of a package.
+const LOCAL_DIR: &str = "_";
+
+/// Everything a compilation read: main source, all resolved files (including package files),
+/// inputs and the time of the compilation. Can be written to a single tar archive and replayed
+/// with the `BundleResolver`, e.g. to reproduce rendering bugs reported by customers.
+///
+/// Fonts are not included, only their metadata. Values injected with
+/// `TypstTemplateCollection::add_injection()` are not captured.
+///
+/// Example:
+/// ```rust,no_run
+/// # use typst::foundations::Dict;
+/// # use typst_as_lib::bundle::CaptureBundle;
+/// # use typst_as_lib::TypstTemplateCollection;
+/// # let inputs = Dict::new();
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// # let template_collection = TypstTemplateCollection::new(fonts.clone())
+/// #     .with_file_system_resolver("./templates");
+/// let bundle = template_collection.capture_bundle("/template.typ", inputs);
+/// std::fs::write("bug-1234.tar", bundle.to_archive()?)?;
+/// // Later, somewhere else:
+/// let bundle = CaptureBundle::from_archive(&std::fs::read("bug-1234.tar")?)?;
+/// let doc = bundle.compile(fonts).output?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct CaptureBundle {
+    pub main_source_id: FileId,
+    /// Inputs after merging the default inputs.
+    pub inputs: Dict,
+    /// `(module_name, value_name)`, if a custom inject location was set.
+    pub inject_location: Option<(String, String)>,
+    pub now: DateTime<Utc>,
+    pub files: HashMap<FileId, Bytes>,
+    pub fonts: Vec<CapturedFont>,
+}
+
+/// Metadata of a font of the captured `TypstTemplateCollection`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFont {
+    pub family: String,
+    /// Style, weight and stretch.
+    pub variant: String,
+}
+
+impl TypstTemplateCollection {
+    /// Compile and capture everything, that was read during the compilation, in a
+    /// `CaptureBundle`. The bundle is also created, when the compilation fails.
+    pub fn capture_bundle<F, D>(&self, main_source_id: F, inputs: D) -> CaptureBundle
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let FileIdNewType(main_source_id) = main_source_id.into();
+        let inputs = self
+            .merge_default_inputs(Some(inputs.into()))
+            .unwrap_or_default();
+        let now = self.now();
+        let (_, dependencies) =
+            self.compile_tracked(main_source_id, Some(inputs.clone()), None, None, true, now);
+        let files = dependencies
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|dependency| match dependency {
+                Dependency::Source(id, _) => {
+                    let source = self.resolve_source(id, |_| ()).ok()?;
+                    Some((id, Bytes::from(source.text().as_bytes().to_vec())))
+                }
+                Dependency::File(id, _) => {
+                    let bytes = self.resolve_file(id, |_| ()).ok().or_else(|| {
+                        let fallback_file_resolver = self.fallback_file_resolver.as_ref()?;
+                        fallback_file_resolver.resolve_binary(id).ok()
+                    })?;
+                    Some((id, bytes.into_owned()))
+                }
+                Dependency::Today(..) => None,
+            })
+            .collect();
+        let fonts = self
+            .fonts
+            .iter()
+            .map(|font| CapturedFont {
+                family: font.info().family.clone(),
+                variant: format!("{:?}", font.info().variant),
+            })
+            .collect();
+        let inject_location = self.inject_location.as_ref().map(
+            |InjectLocation {
+                 module_name,
+                 value_name,
+             }| (module_name.clone(), value_name.clone()),
+        );
+        CaptureBundle {
+            main_source_id,
+            inputs,
+            inject_location,
+            now,
+            files,
+            fonts,
+        }
+    }
+}
+
+impl CaptureBundle {
+    /// File resolver, that serves the captured files.
+    pub fn resolver(&self) -> BundleResolver {
+        BundleResolver {
+            files: self.files.clone(),
+        }
+    }
+
+    /// Compile the captured template again with the same files, inputs and time.
+    pub fn compile<V>(&self, fonts: V) -> Warned<Result<Document, TypstAsLibError>>
+    where
+        V: Into<Vec<Font>>,
+    {
+        let mut collection = TypstTemplateCollection::new(fonts).add_file_resolver(self.resolver());
+        if let Some((module_name, value_name)) = &self.inject_location {
+            collection.custom_inject_location_mut(module_name.as_str(), value_name.as_str());
+        }
+        collection
+            .compile_tracked(
+                self.main_source_id,
+                Some(self.inputs.clone()),
+                None,
+                None,
+                false,
+                self.now,
+            )
+            .0
+            .output
+    }
+
+    /// Write the bundle into a tar archive. The inputs are stored as JSON, so the export fails
+    /// with `TypstAsLibError::InvalidBundle`, if they contain values, that JSON can't
+    /// represent exactly, e.g. datetimes, bytes, lengths or content.
+    pub fn to_archive(&self) -> Result<Vec<u8>, TypstAsLibError> {
+        let Self {
+            main_source_id,
+            inputs,
+            inject_location,
+            now,
+            files,
+            fonts,
+        } = self;
+        let mut manifest = format!(
+            "main\t{}\nnow\t{}\n",
+            file_path(*main_source_id),
+            now.to_rfc3339()
+        );
+        if let Some((module_name, value_name)) = inject_location {
+            manifest.push_str(&format!("inject_location\t{module_name}\t{value_name}\n"));
+        }
+        for CapturedFont { family, variant } in fonts {
+            manifest.push_str(&format!("font\t{variant}\t{family}\n"));
+        }
+        let inputs = Value::Dict(inputs.clone());
+        check_json_value(&inputs, "inputs")?;
+        let inputs = serde_json::to_vec_pretty(&inputs)
+            .map_err(|err| TypstAsLibError::InvalidBundle(err.to_string()))?;
+
+        let mut builder = Builder::new(Vec::new());
+        append(&mut builder, MANIFEST, manifest.as_bytes())?;
+        append(&mut builder, INPUTS, &inputs)?;
+        for (id, bytes) in files {
+            append(&mut builder, &file_path(*id), bytes.as_slice())?;
+        }
+        builder.into_inner().map_err(io_error)
+    }
+
+    /// Read a bundle, that was written with `CaptureBundle::to_archive()`.
+    pub fn from_archive(archive: &[u8]) -> Result<Self, TypstAsLibError> {
+        let mut manifest = None;
+        let mut inputs = None;
+        let mut files = HashMap::new();
+        let mut archive = Archive::new(archive);
+        for entry in archive.entries().map_err(io_error)? {
+            let mut entry = entry.map_err(io_error)?;
+            let path = entry
+                .path()
+                .map_err(io_error)?
+                .to_string_lossy()
+                .into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(io_error)?;
+            match path.as_str() {
+                MANIFEST => manifest = Some(String::from_utf8_lossy(&data).into_owned()),
+                INPUTS => inputs = Some(data),
+                path => {
+                    let id = parse_file_path(path).ok_or_else(|| invalid(path))?;
+                    files.insert(id, Bytes::from(data));
+                }
+            }
+        }
+
+        let manifest = manifest.ok_or_else(|| invalid(MANIFEST))?;
+        let inputs = inputs.ok_or_else(|| invalid(INPUTS))?;
+        let inputs = match serde_json::from_slice::<Value>(&inputs) {
+            Ok(Value::Dict(inputs)) => inputs,
+            _ => return Err(invalid(INPUTS)),
+        };
+        let mut main_source_id = None;
+        let mut now = None;
+        let mut inject_location = None;
+        let mut fonts = Vec::new();
+        for line in manifest.lines() {
+            let mut parts = line.splitn(3, '\t');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("main"), Some(id), None) => main_source_id = parse_file_path(id),
+                (Some("now"), Some(timestamp), None) => {
+                    now = DateTime::parse_from_rfc3339(timestamp)
+                        .ok()
+                        .map(|now| now.with_timezone(&Utc))
+                }
+                (Some("inject_location"), Some(module_name), Some(value_name)) => {
+                    inject_location = Some((module_name.to_owned(), value_name.to_owned()))
+                }
+                (Some("font"), Some(variant), Some(family)) => fonts.push(CapturedFont {
+                    family: family.to_owned(),
+                    variant: variant.to_owned(),
+                }),
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(Self {
+            main_source_id: main_source_id.ok_or_else(|| invalid("main"))?,
+            inputs,
+            inject_location,
+            now: now.ok_or_else(|| invalid("now"))?,
+            files,
+            fonts,
+        })
+    }
+}
+
+/// Serves the files of a `CaptureBundle`.
+#[derive(Debug, Clone)]
+pub struct BundleResolver {
+    files: HashMap<FileId, Bytes>,
+}
+
+impl BundleResolver {
+    pub fn from_bundle(bundle: &CaptureBundle) -> Self {
+        bundle.resolver()
+    }
+}
+
+impl FileResolver for BundleResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        self.files
+            .get(&id)
+            .map(Cow::Borrowed)
+            .ok_or_else(|| not_found(id))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let bytes = self.files.get(&id).ok_or_else(|| not_found(id))?;
+        Ok(Cow::Owned(bytes_to_source(id, bytes)?))
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.files.values().map(|bytes| bytes.len()).sum()
+    }
+}
+
+fn append(builder: &mut Builder<Vec<u8>>, path: &str, data: &[u8]) -> Result<(), TypstAsLibError> {
+    let mut header = Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    builder
+        .append_data(&mut header, path, data)
+        .map_err(io_error)
+}
+
+/// `files/_/<path>` or `files/@<namespace>/<name>:<version>/<path>`
+fn file_path(id: FileId) -> String {
+    let package = id
+        .package()
+        .map_or_else(|| LOCAL_DIR.to_owned(), ToString::to_string);
+    let path = id.vpath().as_rootless_path().to_string_lossy();
+    format!("{FILES_DIR}/{package}/{path}")
+}
+
+fn parse_file_path(path: &str) -> Option<FileId> {
+    let path = path.strip_prefix(FILES_DIR)?.strip_prefix('/')?;
+    if let Some(path) = path
+        .strip_prefix(LOCAL_DIR)
+        .and_then(|p| p.strip_prefix('/'))
+    {
+        return Some(FileId::new(None, VirtualPath::new(path)));
+    }
+    let mut parts = path.splitn(3, '/');
+    let package = format!("{}/{}", parts.next()?, parts.next()?);
+    let package = PackageSpec::from_str(&package).ok()?;
+    Some(FileId::new(Some(package), VirtualPath::new(parts.next()?)))
+}
+
+/// Fails for values, that are not read back the same from JSON.
+fn check_json_value(value: &Value, path: &str) -> Result<(), TypstAsLibError> {
+    match value {
+        Value::None | Value::Bool(_) | Value::Int(_) | Value::Str(_) => Ok(()),
+        Value::Float(float) if float.is_finite() => Ok(()),
+        Value::Array(array) => array
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, value)| check_json_value(value, &format!("{path}.{index}"))),
+        Value::Dict(dict) => dict
+            .iter()
+            .try_for_each(|(key, value)| check_json_value(value, &format!("{path}.{key}"))),
+        value => Err(TypstAsLibError::InvalidBundle(format!(
+            "{path} has type {}, which can't be stored as JSON",
+            value.ty()
+        ))),
+    }
+}
+
+fn io_error(err: std::io::Error) -> TypstAsLibError {
+    TypstAsLibError::InvalidBundle(err.to_string())
+}
+
+fn invalid(entry: &str) -> TypstAsLibError {
+    TypstAsLibError::InvalidBundle(format!("invalid or missing entry: {entry}"))
+}
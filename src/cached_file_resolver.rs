@@ -1,7 +1,12 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
 };
 
 use typst::{
@@ -10,12 +15,124 @@ use typst::{
     syntax::{FileId, Source},
 };
 
-use crate::file_resolver::FileResolver;
+use crate::file_resolver::{FileResolver, FileResolverStats};
+use crate::instrument;
 
+/// Number of cache locks, that were poisoned by a panicking thread and recovered.
+static POISONED_LOCKS_RECOVERED: AtomicU64 = AtomicU64::new(0);
+
+/// Number of cache locks (of `CachedFileResolver`, `InMemoryCache` and `ResultCache`), that
+/// were poisoned by a panicking thread since the start of the process. Caches keep working
+/// after a panic, so a value above `0` only hints at panics, e.g. for health checks.
+pub fn poisoned_locks_recovered() -> u64 {
+    POISONED_LOCKS_RECOVERED.load(Ordering::Relaxed)
+}
+
+/// Lock a cache. A poisoned lock is recovered (and counted), as the caches can't be left in
+/// an inconsistent state.
+pub(crate) fn lock_cache<T>(cache: &Mutex<T>) -> MutexGuard<'_, T> {
+    cache.lock().unwrap_or_else(|poisoned| {
+        cache.clear_poison();
+        recover(poisoned)
+    })
+}
+
+fn recover<G>(poisoned: PoisonError<G>) -> G {
+    POISONED_LOCKS_RECOVERED.fetch_add(1, Ordering::Relaxed);
+    instrument::cache_lock_poisoned();
+    poisoned.into_inner()
+}
+
+/// Number of shards of a `ShardedCache`.
+const SHARDS: usize = 32;
+
+/// A map, that is split into shards with their own lock, so threads, that access different
+/// files, don't wait for each other. Reads of the same shard don't block each other either.
+#[derive(Debug)]
+pub(crate) struct ShardedCache<K, V> {
+    shards: Box<[RwLock<HashMap<K, V>>]>,
+    hasher: RandomState,
+}
+
+impl<K, V> Default for ShardedCache<K, V> {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Default::default()).collect(),
+            hasher: Default::default(),
+        }
+    }
+}
+
+impl<K, V> ShardedCache<K, V>
+where
+    K: Hash + Eq,
+    V: Clone,
+{
+    pub(crate) fn get(&self, key: &K) -> Option<V> {
+        let shard = read_shard(self.shard(key));
+        shard.get(key).cloned()
+    }
+
+    pub(crate) fn insert(&self, key: K, value: V) {
+        let mut shard = write_shard(self.shard(&key));
+        shard.insert(key, value);
+    }
+
+    pub(crate) fn remove(&self, key: &K) {
+        let mut shard = write_shard(self.shard(key));
+        shard.remove(key);
+    }
+
+    pub(crate) fn clear(&self) {
+        for shard in self.shards.iter() {
+            write_shard(shard).clear();
+        }
+    }
+
+    /// Sum of `f` over all values.
+    pub(crate) fn sum_by<F>(&self, f: F) -> usize
+    where
+        F: Fn(&V) -> usize,
+    {
+        self.shards
+            .iter()
+            .map(|shard| read_shard(shard).values().map(&f).sum::<usize>())
+            .sum()
+    }
+
+    fn shard(&self, key: &K) -> &RwLock<HashMap<K, V>> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        &self.shards[index]
+    }
+}
+
+fn read_shard<T>(shard: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    shard.read().unwrap_or_else(|poisoned| {
+        shard.clear_poison();
+        recover(poisoned)
+    })
+}
+
+fn write_shard<T>(shard: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    shard.write().unwrap_or_else(|poisoned| {
+        shard.clear_poison();
+        recover(poisoned)
+    })
+}
+
+/// Caches the sources and binaries of a file resolver in memory. The caches are kept in
+/// shards with their own lock, so concurrent compilations don't contend on one lock.
 pub struct CachedFileResolver<T> {
     pub file_resolver: T,
-    pub in_memory_source_cache: Option<Arc<Mutex<HashMap<FileId, Source>>>>,
-    pub in_memory_binary_cache: Option<Arc<Mutex<HashMap<FileId, Bytes>>>>,
+    in_memory_source_cache: Option<Arc<ShardedCache<FileId, Source>>>,
+    in_memory_binary_cache: Option<Arc<ShardedCache<FileId, Bytes>>>,
+    counters: Arc<CacheCounters>,
+}
+
+#[derive(Debug, Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
 impl<T> CachedFileResolver<T> {
@@ -24,6 +141,7 @@ impl<T> CachedFileResolver<T> {
             file_resolver,
             in_memory_source_cache: None,
             in_memory_binary_cache: None,
+            counters: Default::default(),
         }
     }
 
@@ -40,55 +158,139 @@ impl<T> CachedFileResolver<T> {
             ..self
         }
     }
+
+    pub fn has_in_memory_source_cache(&self) -> bool {
+        self.in_memory_source_cache.is_some()
+    }
+
+    pub fn has_in_memory_binary_cache(&self) -> bool {
+        self.in_memory_binary_cache.is_some()
+    }
+
+    /// Remove all cached sources and binaries, e.g. after the files changed on disk. Use
+    /// `FileResolver::invalidate()` to remove single files.
+    pub fn clear(&self) {
+        let Self {
+            in_memory_source_cache,
+            in_memory_binary_cache,
+            ..
+        } = self;
+        if let Some(cache) = in_memory_source_cache {
+            cache.clear();
+        }
+        if let Some(cache) = in_memory_binary_cache {
+            cache.clear();
+        }
+    }
 }
 
 impl<T> FileResolver for CachedFileResolver<T>
 where
     T: FileResolver,
 {
-    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<Bytes>> {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
         let Self {
             in_memory_binary_cache,
             ..
         } = self;
 
         if let Some(in_memory_binary_cache) = in_memory_binary_cache {
-            if let Ok(in_memory_binary_cache) = in_memory_binary_cache.lock() {
-                if let Some(cached) = in_memory_binary_cache.get(&id) {
-                    return Ok(Cow::Owned(cached.clone()));
-                }
+            if let Some(cached) = in_memory_binary_cache.get(&id) {
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Cow::Owned(cached));
             }
+            self.counters.misses.fetch_add(1, Ordering::Relaxed);
         }
         let resolved = self.file_resolver.resolve_binary(id)?;
         if let Some(in_memory_binary_cache) = in_memory_binary_cache {
-            if let Ok(mut in_memory_binary_cache) = in_memory_binary_cache.lock() {
-                in_memory_binary_cache.insert(id, resolved.as_ref().clone());
-            }
+            in_memory_binary_cache.insert(id, resolved.as_ref().clone());
         }
         Ok(resolved)
     }
 
-    fn resolve_source(&self, id: FileId) -> FileResult<Cow<Source>> {
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
         let Self {
             in_memory_source_cache,
             ..
         } = self;
 
         if let Some(in_memory_source_cache) = in_memory_source_cache {
-            if let Ok(in_memory_source_cache) = in_memory_source_cache.lock() {
-                if let Some(cached) = in_memory_source_cache.get(&id) {
-                    return Ok(Cow::Owned(cached.clone()));
-                }
+            if let Some(cached) = in_memory_source_cache.get(&id) {
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Cow::Owned(cached));
             }
+            self.counters.misses.fetch_add(1, Ordering::Relaxed);
         }
         let resolved = self.file_resolver.resolve_source(id)?;
         if let Some(in_memory_source_cache) = in_memory_source_cache {
-            if let Ok(mut in_memory_source_cache) = in_memory_source_cache.lock() {
-                in_memory_source_cache.insert(id, resolved.as_ref().clone());
-            }
+            in_memory_source_cache.insert(id, resolved.as_ref().clone());
         }
         Ok(resolved)
     }
+
+    fn stats(&self) -> FileResolverStats {
+        let Self {
+            file_resolver,
+            counters,
+            ..
+        } = self;
+        let stats = FileResolverStats {
+            cache_hits: counters.hits.load(Ordering::Relaxed),
+            cache_misses: counters.misses.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        stats + file_resolver.stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let Self {
+            file_resolver,
+            in_memory_source_cache,
+            in_memory_binary_cache,
+            ..
+        } = self;
+        let sources: usize = in_memory_source_cache
+            .iter()
+            .map(|cache| cache.sum_by(|source| source.text().len()))
+            .sum();
+        let binaries: usize = in_memory_binary_cache
+            .iter()
+            .map(|cache| cache.sum_by(|bytes| bytes.len()))
+            .sum();
+        sources + binaries + file_resolver.memory_footprint()
+    }
+
+    fn invalidate(&self, id: FileId) {
+        let Self {
+            file_resolver,
+            in_memory_source_cache,
+            in_memory_binary_cache,
+            ..
+        } = self;
+        if let Some(cache) = in_memory_source_cache {
+            cache.remove(&id);
+        }
+        if let Some(cache) = in_memory_binary_cache {
+            cache.remove(&id);
+        }
+        file_resolver.invalidate(id);
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.file_resolver.file_ids()
+    }
+
+    fn uses_network(&self) -> bool {
+        self.file_resolver.uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.file_resolver.roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        self.file_resolver.source_encoding(id)
+    }
 }
 
 pub trait IntoCachedFileResolver {
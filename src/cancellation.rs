@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+/// Aborts network requests of file resolvers (e.g. package downloads), that run on the
+/// current thread within `CancellationToken::run()`. `TimeoutResolver` cancels the
+/// resolution, that timed out, with a token, so its thread doesn't stay blocked on the
+/// network.
+///
+/// Downloads check the token between reads. Set read timeouts on the `ureq::Agent`, so a
+/// stalled connection can't block a read for long.
+///
+/// Example:
+/// ```rust
+/// # use typst::foundations::{dict, Array};
+/// # use typst_as_lib::cancellation::CancellationToken;
+/// # use typst_as_lib::TypstTemplate;
+/// # fn on_disconnect<F: FnOnce()>(_: F) {}
+/// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], TEMPLATE);
+/// # let inputs = dict! { "v" => Array::new() };
+/// let token = CancellationToken::new();
+/// let cancel = token.clone();
+/// // E.g. when the client disconnects.
+/// on_disconnect(move || cancel.cancel());
+/// let doc = token.run(|| template.compile_with_input(inputs));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Run `f` with this token as the token of the current thread.
+    pub fn run<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        // Restores the previous token, also if `f` panics.
+        let _restore = RestoreToken(previous);
+        f()
+    }
+
+    /// Whether the token of the current thread, if any, is cancelled.
+    #[cfg(feature = "packages")]
+    pub(crate) fn is_current_cancelled() -> bool {
+        CURRENT.with(|current| {
+            current
+                .borrow()
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled)
+        })
+    }
+}
+
+struct RestoreToken(Option<CancellationToken>);
+
+impl Drop for RestoreToken {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Fails reads, once the token of the thread, that created it, is cancelled.
+#[cfg(feature = "packages")]
+pub(crate) struct CancellableReader<R> {
+    reader: R,
+    token: Option<CancellationToken>,
+}
+
+#[cfg(feature = "packages")]
+impl<R> CancellableReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            token: CURRENT.with(|current| current.borrow().clone()),
+        }
+    }
+}
+
+#[cfg(feature = "packages")]
+impl<R> std::io::Read for CancellableReader<R>
+where
+    R: std::io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self
+            .token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(std::io::Error::other("download cancelled"));
+        }
+        self.reader.read(buf)
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+
+use crate::{TypstTemplate, TypstTemplateCollection};
+
+/// Current time of compilations. It is used for `datetime.today()` in typst and for the
+/// timestamps of exports (e.g. `PdfTimestamp::Now`).
+///
+/// Example:
+/// ```rust
+/// # use chrono::{DateTime, Utc};
+/// # use typst_as_lib::TypstTemplate;
+/// # struct BillingPeriod {
+/// #     end: DateTime<Utc>,
+/// # }
+/// # let billing_period = BillingPeriod { end: Utc::now() };
+/// # static INVOICE: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// // Invoices are dated to the end of the billing period.
+/// let template = TypstTemplate::new(fonts, INVOICE)
+///     .with_clock(move || billing_period.end);
+/// ```
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> DateTime<Utc>,
+{
+    fn now(&self) -> DateTime<Utc> {
+        self()
+    }
+}
+
+/// The time of the system. The default clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Always the same time, e.g. for tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Use `clock` instead of the time of the system. See `Clock`.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.with_clock_mut(clock);
+        self
+    }
+
+    /// Use `clock` instead of the time of the system. See `Clock`.
+    pub fn with_clock_mut<C>(&mut self, clock: C)
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.clock = Box::new(clock);
+    }
+
+    /// Current time of the clock.
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+}
+
+impl TypstTemplate {
+    /// Use `clock` instead of the time of the system. See `Clock`.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.collection.with_clock_mut(clock);
+        self
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use typst::diag::FileError;
+use typst::foundations::Bytes;
+use typst::syntax::{FileId, VirtualPath};
+
+use crate::eviction::EvictionStrategy;
+use crate::fonts::fonts_from_bytes;
+use crate::{SourceNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Declarative description of a `TypstTemplateCollection` or `TypstTemplate`, that can be
+/// deserialized with serde, e.g. from TOML or JSON deployment config.
+///
+/// Example (TOML):
+/// ```toml
+/// main_file = "/invoice.typ"
+/// roots = ["./templates", "./shared"]
+/// fonts = ["./fonts/texgyrecursor-regular.otf"]
+///
+/// [[static_files]]
+/// vpath = "/images/logo.png"
+/// path = "./assets/logo.png"
+///
+/// [packages.cache]
+/// kind = "file_system"
+///
+/// [inject_location]
+/// module_name = "host"
+/// value_name = "data"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TypstTemplateConfig {
+    /// Absolute virtual path of the main source file. Required for `TypstTemplate`.
+    pub main_file: Option<String>,
+    /// Roots of `FileSystemResolver`s. They are consulted in the given order.
+    pub roots: Vec<PathBuf>,
+    /// Files, that are read on construction and served from memory.
+    pub static_files: Vec<StaticFileConfig>,
+    /// Paths of font files.
+    pub fonts: Vec<PathBuf>,
+    /// Enables the `PackageResolver`. Needs feature `packages`.
+    pub packages: Option<PackageConfig>,
+    /// Other typst location for injected inputs.
+    pub inject_location: Option<InjectLocationConfig>,
+    /// `comemo::evict(max_age)` is called after every compilation. `None` disables eviction.
+    /// Ignored, if `eviction_strategy` is set.
+    pub comemo_evict_max_age: Option<usize>,
+    /// When `comemo::evict()` is called. See `TypstTemplateCollection::eviction_strategy()`.
+    pub eviction_strategy: Option<EvictionStrategy>,
+}
+
+impl Default for TypstTemplateConfig {
+    fn default() -> Self {
+        Self {
+            main_file: None,
+            roots: Vec::new(),
+            static_files: Vec::new(),
+            fonts: Vec::new(),
+            packages: None,
+            inject_location: None,
+            comemo_evict_max_age: Some(0),
+            eviction_strategy: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StaticFileConfig {
+    /// Absolute virtual path, under which the file is served.
+    pub vpath: String,
+    /// Path of the file on disk.
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PackageConfig {
+    pub cache: PackageCacheConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PackageCacheConfig {
+    /// Uses `<OS_CACHE_DIR>/typst/packages`, when `path` is not set.
+    FileSystem {
+        path: Option<PathBuf>,
+    },
+    InMemory,
+}
+
+impl Default for PackageCacheConfig {
+    fn default() -> Self {
+        PackageCacheConfig::FileSystem { path: None }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InjectLocationConfig {
+    pub module_name: String,
+    pub value_name: String,
+}
+
+impl TypstTemplateCollection {
+    /// Initialize from a `TypstTemplateConfig`. Fonts and static files are read from disk.
+    /// `main_file` is ignored.
+    pub fn from_config(config: TypstTemplateConfig) -> Result<Self, TypstAsLibError> {
+        let TypstTemplateConfig {
+            roots,
+            static_files,
+            fonts,
+            packages,
+            inject_location,
+            comemo_evict_max_age,
+            eviction_strategy,
+            ..
+        } = config;
+
+        let mut loaded_fonts = Vec::new();
+        for path in fonts {
+            let data = std::fs::read(&path).map_err(|error| FileError::from_io(error, &path))?;
+            loaded_fonts.extend(fonts_from_bytes(data)?);
+        }
+        let mut collection = TypstTemplateCollection::new(loaded_fonts);
+
+        let mut sources = Vec::new();
+        let mut binaries = Vec::new();
+        for StaticFileConfig { vpath, path } in static_files {
+            let data = std::fs::read(&path).map_err(|error| FileError::from_io(error, &path))?;
+            if path.extension().is_some_and(|extension| extension == "typ") {
+                let id = FileId::new(None, VirtualPath::new(&vpath));
+                let source = String::from_utf8(data).map_err(|_| FileError::InvalidUtf8)?;
+                sources.push(SourceNewType::from((id, source)));
+            } else {
+                let id = FileId::new(None, VirtualPath::new(&vpath));
+                binaries.push((id, Bytes::from(data)));
+            }
+        }
+        if !sources.is_empty() {
+            collection.with_static_source_file_resolver_mut(sources);
+        }
+        if !binaries.is_empty() {
+            collection.with_static_file_resolver_mut(binaries);
+        }
+
+        if !roots.is_empty() {
+            collection.with_file_system_roots_mut(roots);
+        }
+
+        if let Some(packages) = packages {
+            collection.add_package_resolver_from_config(packages)?;
+        }
+
+        if let Some(InjectLocationConfig {
+            module_name,
+            value_name,
+        }) = inject_location
+        {
+            collection.custom_inject_location_mut(module_name, value_name);
+        }
+
+        collection.eviction_strategy(eviction_strategy.unwrap_or(comemo_evict_max_age.into()));
+        Ok(collection)
+    }
+
+    #[cfg(feature = "packages")]
+    fn add_package_resolver_from_config(
+        &mut self,
+        config: PackageConfig,
+    ) -> Result<(), TypstAsLibError> {
+        use crate::cached_file_resolver::IntoCachedFileResolver;
+        use crate::package_resolver::{FileSystemCache, PackageResolverBuilder};
+
+        let PackageConfig { cache } = config;
+        let builder = PackageResolverBuilder::new();
+        match cache {
+            PackageCacheConfig::FileSystem { path: Some(path) } => {
+                let resolver = builder.set_cache(FileSystemCache(path)).build();
+                self.add_file_resolver_mut(resolver.into_cached());
+            }
+            PackageCacheConfig::FileSystem { path: None } => {
+                let resolver = builder.with_file_system_cache().build();
+                self.add_file_resolver_mut(resolver.into_cached());
+            }
+            PackageCacheConfig::InMemory => {
+                let resolver = builder.with_in_memory_cache().build();
+                self.add_file_resolver_mut(resolver.into_cached());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "packages"))]
+    fn add_package_resolver_from_config(
+        &mut self,
+        _config: PackageConfig,
+    ) -> Result<(), TypstAsLibError> {
+        Err(TypstAsLibError::InvalidConfig(
+            "`packages` is set, but feature `packages` is not enabled".to_owned(),
+        ))
+    }
+}
+
+/// Main file of projects, whose `typst.toml` doesn't set an entrypoint.
+const DEFAULT_ENTRYPOINT: &str = "main.typ";
+
+/// The parts of a `typst.toml`, that are used by `TypstTemplateConfig::from_project()`. Unlike
+/// `PackageManifest`, the `[package]` section is optional.
+#[derive(Debug, Default, Deserialize)]
+struct ProjectManifest {
+    package: Option<ProjectPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProjectPackage {
+    entrypoint: Option<String>,
+}
+
+impl TypstTemplateConfig {
+    /// Reads the `typst.toml` of a Typst project in `dir`. The entrypoint becomes the main file
+    /// and `dir` the file system root. Packages are enabled, if feature `packages` is enabled.
+    ///
+    /// The manifest is optional, as are its `[package]` section and entrypoint. The main file
+    /// defaults to `main.typ`.
+    pub fn from_project<P>(dir: P) -> Result<Self, TypstAsLibError>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let manifest_path = dir.join("typst.toml");
+        let manifest = match std::fs::read_to_string(&manifest_path) {
+            Ok(manifest) => toml::from_str(&manifest)
+                .map_err(|error| TypstAsLibError::InvalidConfig(format!("{error}")))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                ProjectManifest::default()
+            }
+            Err(error) => return Err(FileError::from_io(error, &manifest_path).into()),
+        };
+        let entrypoint = manifest
+            .package
+            .and_then(|package| package.entrypoint)
+            .unwrap_or_else(|| DEFAULT_ENTRYPOINT.to_owned());
+        Ok(Self {
+            main_file: Some(format!("/{}", entrypoint.trim_start_matches('/'))),
+            roots: vec![dir.to_path_buf()],
+            packages: cfg!(feature = "packages").then(Default::default),
+            ..Default::default()
+        })
+    }
+}
+
+impl TypstTemplate {
+    /// Initialize from the `typst.toml` of a Typst project in `dir`, like the typst CLI does.
+    /// See `TypstTemplateConfig::from_project()`.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use typst_as_lib::TypstTemplate;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// let template = TypstTemplate::from_project("./examples/project")
+    ///     .expect("Could not read project!")
+    ///     .add_fonts([font]);
+    /// ```
+    pub fn from_project<P>(dir: P) -> Result<Self, TypstAsLibError>
+    where
+        P: AsRef<Path>,
+    {
+        TypstTemplate::from_config(TypstTemplateConfig::from_project(dir)?)
+    }
+
+    /// Initialize from a `TypstTemplateConfig`. `main_file` has to be set and needs to be
+    /// resolvable by one of the configured file resolvers.
+    pub fn from_config(config: TypstTemplateConfig) -> Result<Self, TypstAsLibError> {
+        let Some(main_file) = config.main_file.clone() else {
+            return Err(TypstAsLibError::InvalidConfig(
+                "`main_file` is not set".to_owned(),
+            ));
+        };
+        let collection = TypstTemplateCollection::from_config(config)?;
+        Ok(Self {
+            source_id: FileId::new(None, VirtualPath::new(main_file)),
+            collection,
+            main_source: None,
+            input_validator: None,
+        })
+    }
+}
@@ -0,0 +1,157 @@
+use typst::foundations::{Bytes, Label, Selector, Value};
+use typst::introspection::MetadataElem;
+use typst::model::Document;
+use typst::utils::PicoStr;
+
+use crate::file_resolver::StaticFileResolver;
+use crate::{FileIdNewType, TypstTemplate, TypstTemplateCollection};
+
+#[cfg(feature = "data")]
+use crate::{schema::InputValidationError, TypstAsLibError};
+
+/// Serialize rows as CSV (RFC 4180), e.g. for typst's `csv()`. Fields, that contain a comma,
+/// a quote or a line break, are quoted.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::{data, TypstTemplate};
+/// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// let items = [["Item", "Price"], ["Coffee", "2.50"], ["Cake, chocolate", "3.90"]];
+/// let template = TypstTemplate::new(fonts, TEMPLATE)
+///     .with_data_files([("/data/items.csv", data::csv_bytes(items))]);
+/// // In typst: #let items = csv("/data/items.csv")
+/// ```
+pub fn csv_bytes<I, R, S>(rows: I) -> Bytes
+where
+    I: IntoIterator<Item = R>,
+    R: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut csv = String::new();
+    for row in rows {
+        for (index, field) in row.into_iter().enumerate() {
+            if index > 0 {
+                csv.push(',');
+            }
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                csv.push('"');
+                csv.push_str(&field.replace('"', "\"\""));
+                csv.push('"');
+            } else {
+                csv.push_str(field);
+            }
+        }
+        csv.push_str("\r\n");
+    }
+    Bytes::from(csv.into_bytes())
+}
+
+/// Serialize the value as JSON, e.g. for typst's `json()` (feature `data`).
+#[cfg(feature = "data")]
+pub fn json_bytes<T>(value: &T) -> Result<Bytes, TypstAsLibError>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let json = serde_json::to_vec(value).map_err(|err| {
+        TypstAsLibError::InvalidInput(InputValidationError::other(err.to_string()))
+    })?;
+    Ok(Bytes::from(json))
+}
+
+/// Values of all `#metadata(..) <label>` elements in the document, in document order.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::data;
+/// # use typst_as_lib::TypstTemplate;
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], "#metadata(12.5) <invoice-total>");
+/// # let doc = template.compile().output?;
+/// // In typst: #metadata(total) <invoice-total>
+/// let totals = data::query_metadata(&doc, "invoice-total");
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub fn query_metadata(document: &Document, label: &str) -> Vec<Value> {
+    document
+        .introspector
+        .query(&Selector::Label(Label::new(PicoStr::new(label))))
+        .iter()
+        .filter_map(|content| content.to_packed::<MetadataElem>())
+        .map(|metadata| metadata.value.clone())
+        .collect()
+}
+
+/// Deserialize the values of all `#metadata(..) <label>` elements in the document, e.g. page
+/// anchors or totals, that the template computed (feature `data`).
+///
+/// Example:
+/// ```rust
+/// # use serde::Deserialize;
+/// # use typst_as_lib::data;
+/// # use typst_as_lib::TypstTemplate;
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], r#"#metadata((total: 12.5, currency: "EUR")) <invoice-total>"#);
+/// # let doc = template.compile().output?;
+/// // In typst: #metadata((total: total, currency: "EUR")) <invoice-total>
+/// #[derive(Deserialize)]
+/// struct Total {
+///     total: f64,
+///     currency: String,
+/// }
+/// let totals: Vec<Total> = data::extract_metadata(&doc, "invoice-total")?;
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+#[cfg(feature = "data")]
+pub fn extract_metadata<T>(document: &Document, label: &str) -> Result<Vec<T>, TypstAsLibError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    query_metadata(document, label)
+        .iter()
+        .map(|value| {
+            serde_json::to_value(value)
+                .and_then(serde_json::from_value)
+                .map_err(|err| TypstAsLibError::InvalidMetadata(format!("<{label}>: {err}")))
+        })
+        .collect()
+}
+
+impl TypstTemplateCollection {
+    /// Adds data files (e.g. CSV or JSON), that templates can read with typst's data loaders
+    /// (`csv()`, `json()`, `xml()`, ...). For large tabular data, these are more ergonomic
+    /// than `sys.inputs`. See `data::csv_bytes()` and `data::json_bytes()`.
+    pub fn with_data_files<IB, F, B>(mut self, files: IB) -> Self
+    where
+        IB: IntoIterator<Item = (F, B)>,
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        self.with_data_files_mut(files);
+        self
+    }
+
+    pub fn with_data_files_mut<IB, F, B>(&mut self, files: IB)
+    where
+        IB: IntoIterator<Item = (F, B)>,
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        self.add_file_resolver_mut(StaticFileResolver::new(files));
+    }
+}
+
+impl TypstTemplate {
+    /// Adds data files (e.g. CSV or JSON). See `TypstTemplateCollection::with_data_files()`.
+    pub fn with_data_files<IB, F, B>(mut self, files: IB) -> Self
+    where
+        IB: IntoIterator<Item = (F, B)>,
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        self.collection.with_data_files_mut(files);
+        self
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+
+use crate::file_resolver::FileResolver;
+use crate::{InjectLocation, TypstTemplate, TypstTemplateCollection};
+
+/// Features of this crate, that are enabled.
+const FEATURES: &[(&str, bool)] = &[
+    ("bundle", cfg!(feature = "bundle")),
+    ("config", cfg!(feature = "config")),
+    ("data", cfg!(feature = "data")),
+    ("embed", cfg!(feature = "embed")),
+    ("format", cfg!(feature = "format")),
+    ("log", cfg!(feature = "log")),
+    ("lsp-types", cfg!(feature = "lsp-types")),
+    ("mmap", cfg!(feature = "mmap")),
+    ("no-network", cfg!(feature = "no-network")),
+    ("packages", cfg!(feature = "packages")),
+    ("pdf", cfg!(feature = "pdf")),
+    ("pdf-validation", cfg!(feature = "pdf-validation")),
+    ("rayon", cfg!(feature = "rayon")),
+    ("render", cfg!(feature = "render")),
+    ("svg", cfg!(feature = "svg")),
+    ("testing", cfg!(feature = "testing")),
+    ("tracing", cfg!(feature = "tracing")),
+    ("watch", cfg!(feature = "watch")),
+    ("woff", cfg!(feature = "woff")),
+];
+
+/// Summary of the configuration of a `TypstTemplateCollection`, e.g. for support bundles or
+/// health endpoints. Serializable with feature `data`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "data", derive(serde::Serialize))]
+pub struct CollectionDescription {
+    /// Version of this crate.
+    pub version: &'static str,
+    /// Enabled features of this crate.
+    pub features: Vec<&'static str>,
+    /// File resolvers in the order, in which they are asked.
+    pub file_resolvers: Vec<FileResolverDescription>,
+    pub fallback_file_resolver: Option<FileResolverDescription>,
+    /// Distinct font families, sorted.
+    pub font_families: Vec<String>,
+    pub font_count: usize,
+    /// Where inputs are injected, e.g. `sys.inputs`.
+    pub inject_location: String,
+    /// Names of templates, that are registered with `register_template()`, sorted.
+    pub templates: Vec<String>,
+    pub eviction_strategy: String,
+    /// Capacity of the result cache, if enabled.
+    pub result_cache_capacity: Option<usize>,
+    /// Directory and size limit of the artifact cache, if enabled.
+    pub artifact_cache: Option<(PathBuf, u64)>,
+    pub has_preamble: bool,
+    pub deny_network: bool,
+    /// Bytes held by fonts and file resolvers. See `MemoryFootprint::total()`.
+    pub memory_footprint: usize,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "data", derive(serde::Serialize))]
+pub struct FileResolverDescription {
+    /// See `FileResolver::name()`.
+    pub name: String,
+    /// See `FileResolver::roots()`.
+    pub roots: Vec<PathBuf>,
+    pub uses_network: bool,
+}
+
+impl TypstTemplateCollection {
+    /// Summary of the configuration, e.g. for support bundles or health endpoints.
+    ///
+    /// Example:
+    /// ```rust
+    /// # #[cfg(feature = "data")]
+    /// # {
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([("/invoice.typ", "= Invoice")]);
+    /// let description = template_collection.describe();
+    /// let json = serde_json::to_string_pretty(&description)?;
+    /// # }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn describe(&self) -> CollectionDescription {
+        let Self {
+            fonts,
+            inject_location,
+            file_resolvers,
+            fallback_file_resolver,
+            eviction_strategy,
+            registry,
+            result_cache,
+            artifact_cache,
+            preamble,
+            ..
+        } = self;
+        let mut font_families: Vec<String> = fonts
+            .iter()
+            .map(|font| font.info().family.clone())
+            .collect();
+        font_families.sort();
+        font_families.dedup();
+        let mut templates: Vec<String> = registry.names().map(ToOwned::to_owned).collect();
+        templates.sort();
+        let inject_location = match inject_location {
+            Some(InjectLocation {
+                module_name,
+                value_name,
+            }) => format!("{module_name}.{value_name}"),
+            None => "sys.inputs".to_owned(),
+        };
+        CollectionDescription {
+            version: env!("CARGO_PKG_VERSION"),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| *feature)
+                .collect(),
+            file_resolvers: file_resolvers
+                .iter()
+                .map(|file_resolver| describe_file_resolver(file_resolver.as_ref()))
+                .collect(),
+            fallback_file_resolver: fallback_file_resolver
+                .as_ref()
+                .map(|file_resolver| describe_file_resolver(file_resolver.as_ref())),
+            font_families,
+            font_count: fonts.len(),
+            inject_location,
+            templates,
+            eviction_strategy: format!("{eviction_strategy:?}"),
+            result_cache_capacity: result_cache.as_ref().map(|cache| cache.capacity()),
+            artifact_cache: artifact_cache
+                .as_ref()
+                .map(|cache| (cache.dir().to_path_buf(), cache.max_bytes())),
+            has_preamble: preamble.is_some(),
+            deny_network: self.network_denied(),
+            memory_footprint: self.memory_footprint().total(),
+        }
+    }
+}
+
+impl TypstTemplate {
+    /// Summary of the configuration. See `TypstTemplateCollection::describe()`.
+    pub fn describe(&self) -> CollectionDescription {
+        self.collection.describe()
+    }
+}
+
+fn describe_file_resolver<T>(file_resolver: &T) -> FileResolverDescription
+where
+    T: FileResolver + ?Sized,
+{
+    FileResolverDescription {
+        name: file_resolver.name().into_owned(),
+        roots: file_resolver.roots(),
+        uses_network: file_resolver.uses_network(),
+    }
+}
@@ -0,0 +1,149 @@
+use typst::foundations::{Array, Dict, IntoValue, Value};
+
+use crate::TypstAsLibError;
+
+/// Characters, that have a meaning in typst markup.
+const MARKUP_CHARACTERS: &[char] = &[
+    '\\', '/', '[', ']', '{', '}', '#', '*', '_', '+', '=', '-', '~', '`', '$', '"', '\'', '<',
+    '>', '@',
+];
+
+/// Escape `text`, so it is shown as is, when it is inserted into typst markup, e.g. a user
+/// name. Prefer passing user data as inputs (`sys.inputs`); escaping is for sources, that
+/// have to be assembled in Rust. See `typst_literal!()`.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::escape::escape_typst;
+/// assert_eq!(escape_typst("#sys.exit() *bold*"), r"\#sys.exit() \*bold\*");
+/// ```
+pub fn escape_typst(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if MARKUP_CHARACTERS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape `text` for a typst string literal, without the quotes: `"{}"`.
+pub fn escape_typst_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            '\t' => escaped.push_str(r"\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Remove characters from user data, that are invisible or change how text around them is
+/// shown: control characters (except line breaks and tabs) and bidirectional overrides.
+/// The result is still plain text, which is safe as an input (`sys.inputs`), e.g.
+/// `text(user_name)`.
+pub fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\t'))
+        .filter(|c| !matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'))
+        .collect()
+}
+
+/// `sanitize()` and `escape_typst()` `text`, for templates, that pass it to
+/// `eval(.., mode: "markup")`, so it is shown as is.
+///
+/// Example:
+/// ```rust
+/// # use typst::foundations::dict;
+/// # use typst_as_lib::escape;
+/// # let comment = "Looks good! #sys.exit()".to_owned();
+/// // In typst: #eval(sys.inputs.comment, mode: "markup")
+/// let inputs = dict! { "comment" => escape::sanitize_for_eval(&comment) };
+/// ```
+pub fn sanitize_for_eval(text: &str) -> Value {
+    escape_typst(&sanitize(text)).into_value()
+}
+
+/// Apply `sanitize()` to all strings in `value`, e.g. to all inputs of a compilation. Arrays
+/// and dictionaries are sanitized recursively, keys of dictionaries included.
+pub fn sanitize_value(value: Value) -> Value {
+    match value {
+        Value::Str(text) => sanitize(&text).into_value(),
+        Value::Array(array) => array
+            .into_iter()
+            .map(sanitize_value)
+            .collect::<Array>()
+            .into_value(),
+        Value::Dict(dict) => dict
+            .into_iter()
+            .map(|(key, value)| (sanitize(&key).into(), sanitize_value(value)))
+            .collect::<Dict>()
+            .into_value(),
+        value => value,
+    }
+}
+
+/// Like `format!()`, but the arguments are escaped with `escape_typst()`, so user data can't
+/// inject typst markup. Only positional arguments are escaped; don't capture variables in the
+/// format string (`{name}`).
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::{typst_literal, TypstTemplate};
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// # let customer_name = "ACME *Corp*";
+/// let source = typst_literal!("= Invoice for {}\n#lorem(10)", customer_name);
+/// let template = TypstTemplate::new(fonts, source);
+/// ```
+#[macro_export]
+macro_rules! typst_literal {
+    ($format:literal $(, $arg:expr)* $(,)?) => {
+        ::std::format!(
+            $format
+            $(, $crate::escape::escape_typst(&::std::string::ToString::to_string(&$arg)))*
+        )
+    };
+}
+
+/// Heuristic check of a main source, that was assembled in Rust: fails with
+/// `TypstAsLibError::UnescapedUserData`, if a value of `user_data`, that contains typst
+/// markup characters, appears unescaped in `source`. Values without markup characters are
+/// harmless and not reported.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::{escape, TypstTemplate};
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// # let customer_name = "ACME Corp".to_owned();
+/// let source = format!("= Invoice for {customer_name}");
+/// escape::check_user_data(&source, [customer_name.as_str()])?;
+/// let template = TypstTemplate::new(fonts, source);
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub fn check_user_data<I, S>(source: &str, user_data: I) -> Result<(), TypstAsLibError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    for value in user_data {
+        let value = value.as_ref();
+        if !value.contains(MARKUP_CHARACTERS) {
+            continue;
+        }
+        let escaped = escape_typst(value);
+        // The escaped form doesn't always contain the value (e.g. `a\*b` for `a*b`), so
+        // the escaped occurrences are removed first.
+        if source.replace(escaped.as_str(), "").contains(value) {
+            return Err(TypstAsLibError::UnescapedUserData(value.to_owned()));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// When to call `comemo::evict()` after a compilation. Evicting often keeps the memory low,
+/// evicting rarely makes recompilations of similar documents faster.
+///
+/// Example (TOML, feature `config`):
+/// ```toml
+/// [eviction_strategy]
+/// kind = "memory_threshold"
+/// bytes = 1_000_000_000
+/// max_age = 10
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum EvictionStrategy {
+    /// Call `comemo::evict(max_age)` after every compilation.
+    AfterEveryCompile { max_age: usize },
+    /// Call `comemo::evict(max_age)` after every `n`-th compilation.
+    EveryNCompiles { n: usize, max_age: usize },
+    /// Call `comemo::evict(max_age)` after a compilation, when the resident memory of the
+    /// process exceeds `bytes`. The resident memory can only be determined on Linux, other
+    /// platforms never evict.
+    MemoryThreshold { bytes: usize, max_age: usize },
+    /// Never evict.
+    Never,
+}
+
+impl Default for EvictionStrategy {
+    fn default() -> Self {
+        EvictionStrategy::AfterEveryCompile { max_age: 0 }
+    }
+}
+
+impl From<Option<usize>> for EvictionStrategy {
+    /// The former `comemo_evict_max_age` knob.
+    fn from(max_age: Option<usize>) -> Self {
+        match max_age {
+            Some(max_age) => EvictionStrategy::AfterEveryCompile { max_age },
+            None => EvictionStrategy::Never,
+        }
+    }
+}
+
+impl EvictionStrategy {
+    /// `compile_count` is the number of compilations, including the one, that just finished.
+    pub(crate) fn evict_after_compile(&self, compile_count: &AtomicUsize) {
+        let compile_count = compile_count.fetch_add(1, Ordering::Relaxed) + 1;
+        let max_age = match *self {
+            EvictionStrategy::AfterEveryCompile { max_age } => Some(max_age),
+            EvictionStrategy::EveryNCompiles { n, max_age } => {
+                compile_count.is_multiple_of(n.max(1)).then_some(max_age)
+            }
+            EvictionStrategy::MemoryThreshold { bytes, max_age } => resident_memory()
+                .is_some_and(|resident| resident > bytes)
+                .then_some(max_age),
+            EvictionStrategy::Never => None,
+        };
+        if let Some(max_age) = max_age {
+            comemo::evict(max_age);
+        }
+    }
+}
+
+/// Resident memory of the process in bytes.
+#[cfg(target_os = "linux")]
+pub(crate) fn resident_memory() -> Option<usize> {
+    // `VmRSS:    12345 kB`, independent of the page size.
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let resident = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?;
+    let kib: usize = resident.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn resident_memory() -> Option<usize> {
+    None
+}
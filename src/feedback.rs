@@ -0,0 +1,110 @@
+use typst::diag::Warned;
+use typst::foundations::{Array, Dict, IntoValue};
+use typst::model::Document;
+
+use crate::data::query_metadata;
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Label of the metadata, that a pass feeds back into the next pass: `#metadata(..) <feedback>`.
+pub const FEEDBACK_LABEL: &str = "feedback";
+
+/// Key of the input, that contains the feedback of the previous pass:
+/// `sys.inputs.at("feedback", default: ())`. It is an array of the values of all
+/// `<feedback>` metadata elements, in document order.
+pub const FEEDBACK_INPUT: &str = "feedback";
+
+/// Default for the maximal number of passes of `compile_with_feedback()`.
+pub const DEFAULT_MAX_PASSES: usize = 5;
+
+impl TypstTemplateCollection {
+    /// Compile in passes, until the feedback of the template is stable, e.g. for layouts, that
+    /// depend on their own measurements. Each pass gets the feedback of the previous pass as
+    /// input `feedback` (see `FEEDBACK_INPUT`) and reports its feedback with
+    /// `#metadata(..) <feedback>` (see `FEEDBACK_LABEL`). The first pass gets no feedback.
+    ///
+    /// The document of the first pass, that reproduces its feedback (or reports none), is
+    /// returned. Fails with `TypstAsLibError::FeedbackDidNotConverge`, if the feedback
+    /// repeats an earlier feedback (a loop) or after `max_passes` passes.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst::foundations::Dict;
+    /// # use typst_as_lib::feedback::DEFAULT_MAX_PASSES;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([("/report.typ", "= Report")]);
+    /// # let inputs = Dict::new();
+    /// // In typst:
+    /// // #let feedback = sys.inputs.at("feedback", default: ())
+    /// // #let width = feedback.at(0, default: 10cm)
+    /// // #context metadata(measure(table).width) <feedback>
+    /// let doc = template_collection
+    ///     .compile_with_feedback("/report.typ", inputs, DEFAULT_MAX_PASSES)
+    ///     .output?;
+    /// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+    /// ```
+    pub fn compile_with_feedback<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        max_passes: usize,
+    ) -> Warned<Result<Document, TypstAsLibError>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let FileIdNewType(main_source_id) = main_source_id.into();
+        let inputs: Dict = inputs.into();
+        let mut seen: Vec<Array> = Vec::new();
+        let mut pass_inputs = inputs.clone();
+        loop {
+            let Warned { output, warnings } = self
+                .compile_helper(main_source_id, Some(pass_inputs), None)
+                .output;
+            let document = match output {
+                Ok(document) => document,
+                Err(err) => {
+                    return Warned {
+                        output: Err(err),
+                        warnings,
+                    }
+                }
+            };
+            let feedback: Array = query_metadata(&document, FEEDBACK_LABEL)
+                .into_iter()
+                .collect();
+            if feedback.is_empty() || seen.last() == Some(&feedback) {
+                return Warned {
+                    output: Ok(document),
+                    warnings,
+                };
+            }
+            if seen.contains(&feedback) || seen.len() + 1 >= max_passes {
+                return Warned {
+                    output: Err(TypstAsLibError::FeedbackDidNotConverge(seen.len() + 1)),
+                    warnings,
+                };
+            }
+            pass_inputs = inputs.clone();
+            pass_inputs.insert(FEEDBACK_INPUT.into(), feedback.clone().into_value());
+            seen.push(feedback);
+        }
+    }
+}
+
+impl TypstTemplate {
+    /// Compile in passes, until the feedback of the template is stable.
+    /// See `TypstTemplateCollection::compile_with_feedback()`.
+    pub fn compile_with_feedback<D>(
+        &self,
+        inputs: D,
+        max_passes: usize,
+    ) -> Warned<Result<Document, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        self.collection
+            .compile_with_feedback(self.source_id, inputs, max_passes)
+    }
+}
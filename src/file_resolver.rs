@@ -3,11 +3,17 @@ use std::{
     borrow::Cow,
     collections::HashMap,
     path::{Path, PathBuf},
+    sync::{Arc, OnceLock, RwLock, RwLockReadGuard},
+};
+#[cfg(feature = "mmap")]
+use std::{
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
 };
 use typst::{
     diag::{FileError, FileResult},
     foundations::Bytes,
-    syntax::{FileId, Source},
+    syntax::{package::PackageSpec, FileId, Source, VirtualPath},
 };
 
 use crate::{
@@ -21,38 +27,223 @@ use crate::{
 pub const DEFAULT_PACKAGES_SUBDIR: &str = "typst/packages";
 
 pub trait FileResolver {
-    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<Bytes>>;
-    fn resolve_source(&self, id: FileId) -> FileResult<Cow<Source>>;
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>>;
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>>;
+
+    /// Name of the file resolver, e.g. for tracing. Defaults to the type name.
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed(std::any::type_name::<Self>())
+    }
+
+    /// Counters since creation of the file resolver. Used for the `CompilationReport`.
+    fn stats(&self) -> FileResolverStats {
+        Default::default()
+    }
+
+    /// Approximate bytes of sources and binaries, that the file resolver holds in memory.
+    fn memory_footprint(&self) -> usize {
+        0
+    }
+
+    /// Remove the file from caches, e.g. because it changed on disk.
+    fn invalidate(&self, _id: FileId) {}
+
+    /// Files, that the file resolver serves, if it knows them upfront (e.g. static file
+    /// resolvers). Used by `TypstTemplateCollection::try_build()`.
+    fn file_ids(&self) -> Vec<FileId> {
+        Vec::new()
+    }
+
+    /// Whether the file resolver may access the network, e.g. to download packages. Checked
+    /// by `TypstTemplateCollection::try_build()`, when network access is denied.
+    fn uses_network(&self) -> bool {
+        false
+    }
+
+    /// Directories, from which the file resolver reads, e.g. for
+    /// `TypstTemplateCollection::describe()`.
+    fn roots(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Encoding, from which the file resolver decoded the source, if it was not valid UTF-8
+    /// (see `DecodingResolver`). Compilations warn about these sources.
+    fn source_encoding(&self, _id: FileId) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Share one file resolver (and its caches) between several `TypstTemplateCollection`s, e.g.
+/// `Arc<dyn FileResolver + Send + Sync>`. Its memory footprint is reported by each of them.
+impl<T> FileResolver for Arc<T>
+where
+    T: FileResolver + ?Sized,
+{
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        self.as_ref().resolve_binary(id)
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        self.as_ref().resolve_source(id)
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        self.as_ref().name()
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.as_ref().stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.as_ref().memory_footprint()
+    }
+
+    fn invalidate(&self, id: FileId) {
+        self.as_ref().invalidate(id)
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.as_ref().file_ids()
+    }
+
+    fn uses_network(&self) -> bool {
+        self.as_ref().uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.as_ref().roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        self.as_ref().source_encoding(id)
+    }
+}
+
+impl<T> FileResolver for Box<T>
+where
+    T: FileResolver + ?Sized,
+{
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        self.as_ref().resolve_binary(id)
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        self.as_ref().resolve_source(id)
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        self.as_ref().name()
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.as_ref().stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.as_ref().memory_footprint()
+    }
+
+    fn invalidate(&self, id: FileId) {
+        self.as_ref().invalidate(id)
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.as_ref().file_ids()
+    }
+
+    fn uses_network(&self) -> bool {
+        self.as_ref().uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.as_ref().roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        self.as_ref().source_encoding(id)
+    }
+}
+
+/// Counters of a file resolver. File resolvers, that wrap other file resolvers, include
+/// the counters of the wrapped one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileResolverStats {
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub packages_downloaded: u64,
+}
+
+impl FileResolverStats {
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self {
+            cache_hits: self.cache_hits.saturating_sub(other.cache_hits),
+            cache_misses: self.cache_misses.saturating_sub(other.cache_misses),
+            packages_downloaded: self
+                .packages_downloaded
+                .saturating_sub(other.packages_downloaded),
+        }
+    }
+}
+
+impl std::ops::Add for FileResolverStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            cache_hits: self.cache_hits + other.cache_hits,
+            cache_misses: self.cache_misses + other.cache_misses,
+            packages_downloaded: self.packages_downloaded + other.packages_downloaded,
+        }
+    }
 }
 
+/// Resolves the main source of a `TypstTemplate`. The source is shared with the template,
+/// so it can be replaced with `TypstTemplate::set_main_file()`.
 #[derive(Debug, Clone)]
 pub(crate) struct MainSourceFileResolver {
-    main_source: Source,
+    main_source: Arc<RwLock<Source>>,
 }
 
 impl MainSourceFileResolver {
-    pub(crate) fn new(main_source: Source) -> Self {
+    pub(crate) fn new(main_source: Arc<RwLock<Source>>) -> Self {
         Self { main_source }
     }
 }
 
 impl FileResolver for MainSourceFileResolver {
-    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<Bytes>> {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
         Err(not_found(id))
     }
 
-    fn resolve_source(&self, id: FileId) -> FileResult<Cow<Source>> {
-        let Self { main_source } = self;
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let main_source = read_main_source(&self.main_source);
         if id == main_source.id() {
-            return Ok(Cow::Borrowed(main_source));
+            return Ok(Cow::Owned(main_source.clone()));
         }
         Err(not_found(id))
     }
+
+    fn memory_footprint(&self) -> usize {
+        read_main_source(&self.main_source).text().len()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        vec![read_main_source(&self.main_source).id()]
+    }
+}
+
+pub(crate) fn read_main_source(main_source: &RwLock<Source>) -> RwLockReadGuard<'_, Source> {
+    main_source
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
 }
 
 #[derive(Debug, Clone)]
 pub struct StaticSourceFileResolver {
     sources: HashMap<FileId, Source>,
+    /// Parsed on first access.
+    static_sources: HashMap<FileId, (&'static str, OnceLock<Source>)>,
 }
 
 impl StaticSourceFileResolver {
@@ -68,26 +259,89 @@ impl StaticSourceFileResolver {
                 (s.id(), s)
             })
             .collect();
-        Self { sources }
+        Self {
+            sources,
+            static_sources: HashMap::new(),
+        }
+    }
+
+    /// Serves `&'static` sources, e.g. from `include_str!()`. A source is only parsed (and
+    /// copied), when it is accessed for the first time, so unused sources cost nothing.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::file_resolver::StaticSourceFileResolver;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let fonts = vec![font];
+    /// let resolver = StaticSourceFileResolver::from_static([
+    ///     ("/template.typ", include_str!("../examples/templates/template.typ")),
+    ///     ("/function.typ", include_str!("../examples/templates/function.typ")),
+    /// ]);
+    /// let template_collection = TypstTemplateCollection::new(fonts).add_file_resolver(resolver);
+    /// ```
+    pub fn from_static<IS, F>(sources: IS) -> Self
+    where
+        IS: IntoIterator<Item = (F, &'static str)>,
+        F: Into<FileIdNewType>,
+    {
+        let static_sources = sources
+            .into_iter()
+            .map(|(id, text)| {
+                let FileIdNewType(id) = id.into();
+                (id, (text, OnceLock::new()))
+            })
+            .collect();
+        Self {
+            sources: HashMap::new(),
+            static_sources,
+        }
     }
 }
 
 impl FileResolver for StaticSourceFileResolver {
-    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<Bytes>> {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
         Err(not_found(id))
     }
 
-    fn resolve_source(&self, id: FileId) -> FileResult<Cow<Source>> {
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        if let Some((text, source)) = self.static_sources.get(&id) {
+            let source = source.get_or_init(|| Source::new(id, text.to_string()));
+            return Ok(Cow::Borrowed(source));
+        }
         self.sources
             .get(&id)
-            .map(|s| Cow::Borrowed(s))
+            .map(Cow::Borrowed)
             .ok_or_else(|| not_found(id))
     }
+
+    fn memory_footprint(&self) -> usize {
+        // The static texts don't count, only the parsed sources.
+        let parsed = self
+            .static_sources
+            .values()
+            .filter_map(|(_, source)| source.get());
+        self.sources
+            .values()
+            .chain(parsed)
+            .map(|source| source.text().len())
+            .sum()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.sources
+            .keys()
+            .chain(self.static_sources.keys())
+            .copied()
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct StaticFileResolver {
     binaries: HashMap<FileId, Bytes>,
+    /// Length of the `&'static` binaries, which don't take up memory.
+    static_len: usize,
 }
 
 impl StaticFileResolver {
@@ -104,41 +358,271 @@ impl StaticFileResolver {
                 (id, b.into())
             })
             .collect();
-        Self { binaries }
+        Self {
+            binaries,
+            static_len: 0,
+        }
+    }
+
+    /// Serves `&'static` binaries, e.g. from `include_bytes!()`, without copying them.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::file_resolver::StaticFileResolver;
+    /// # use typst_as_lib::TypstTemplate;
+    /// # static source: &str = include_str!("../examples/templates/template.typ");
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let fonts = vec![font];
+    /// let resolver = StaticFileResolver::from_static([
+    ///     ("/images/typst.png", include_bytes!("../examples/templates/images/typst.png").as_slice()),
+    ///     ("/fonts/texgyrecursor.otf", include_bytes!("../examples/fonts/texgyrecursor-regular.otf").as_slice()),
+    /// ]);
+    /// let template = TypstTemplate::new(fonts, source).add_file_resolver(resolver);
+    /// ```
+    pub fn from_static<IB, F>(binaries: IB) -> Self
+    where
+        IB: IntoIterator<Item = (F, &'static [u8])>,
+        F: Into<FileIdNewType>,
+    {
+        let binaries: HashMap<_, _> = binaries
+            .into_iter()
+            .map(|(id, bytes)| {
+                let FileIdNewType(id) = id.into();
+                (id, Bytes::from_static(bytes))
+            })
+            .collect();
+        let static_len = binaries.values().map(|bytes| bytes.len()).sum();
+        Self {
+            binaries,
+            static_len,
+        }
     }
 }
 
 impl FileResolver for StaticFileResolver {
-    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<Bytes>> {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
         self.binaries
             .get(&id)
-            .map(|b| Cow::Borrowed(b))
+            .map(Cow::Borrowed)
             .ok_or_else(|| not_found(id))
     }
 
-    fn resolve_source(&self, id: FileId) -> FileResult<Cow<Source>> {
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
         Err(not_found(id))
     }
+
+    fn memory_footprint(&self) -> usize {
+        let len: usize = self.binaries.values().map(|bytes| bytes.len()).sum();
+        len - self.static_len
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.binaries.keys().copied().collect()
+    }
+}
+
+/// Serves files, that are embedded into the binary, e.g. with `embed_templates!()`
+/// (feature `embed`). Files ending with `.typ` are parsed once on creation.
+#[derive(Debug, Clone)]
+pub struct EmbeddedFileResolver {
+    binaries: HashMap<FileId, Bytes>,
+    sources: HashMap<FileId, Source>,
+}
+
+impl EmbeddedFileResolver {
+    /// `files` are pairs of the absolute virtual path and the content.
+    pub fn new(files: &[(&str, &'static [u8])]) -> Self {
+        let mut binaries = HashMap::new();
+        let mut sources = HashMap::new();
+        for (vpath, bytes) in files {
+            let id = FileId::new(None, VirtualPath::new(vpath));
+            if vpath.ends_with(".typ") {
+                if let Ok(source) = bytes_to_source(id, bytes) {
+                    sources.insert(id, source);
+                }
+            }
+            binaries.insert(id, Bytes::from_static(bytes));
+        }
+        Self { binaries, sources }
+    }
+}
+
+impl FileResolver for EmbeddedFileResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        self.binaries
+            .get(&id)
+            .map(Cow::Borrowed)
+            .ok_or_else(|| not_found(id))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        if let Some(source) = self.sources.get(&id) {
+            return Ok(Cow::Borrowed(source));
+        }
+        let bytes = self.binaries.get(&id).ok_or_else(|| not_found(id))?;
+        Ok(Cow::Owned(bytes_to_source(id, bytes)?))
+    }
+
+    fn memory_footprint(&self) -> usize {
+        // The binaries are static, so only the parsed sources count.
+        self.sources
+            .values()
+            .map(|source| source.text().len())
+            .sum()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.binaries.keys().copied().collect()
+    }
+}
+
+/// Extensions of the images, that typst supports.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "svg", "svgz"];
+
+/// Whether the file is an image, judging by its extension.
+pub(crate) fn is_image(id: FileId) -> bool {
+    id.vpath()
+        .as_rootless_path()
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|image| image.eq_ignore_ascii_case(extension))
+        })
+}
+
+/// Serves placeholder binaries (e.g. a "missing image" PNG) for every `FileId`.
+/// Meant to be used with `TypstTemplateCollection::with_fallback_file_resolver()`.
+#[derive(Debug, Clone, Default)]
+pub struct PlaceholderResolver {
+    default_placeholder: Option<Bytes>,
+    placeholders_by_extension: HashMap<String, Bytes>,
+}
+
+impl PlaceholderResolver {
+    /// Serves `placeholder` for all binaries, that have no placeholder for their extension.
+    pub fn new<B>(placeholder: B) -> Self
+    where
+        B: Into<Bytes>,
+    {
+        Self {
+            default_placeholder: Some(placeholder.into()),
+            placeholders_by_extension: Default::default(),
+        }
+    }
+
+    /// Serves `placeholder` for binaries with the extension (without dot). Typst detects the
+    /// image format by the extension, so e.g. `.svg` files need an SVG placeholder.
+    pub fn with_extension_placeholder<S, B>(mut self, extension: S, placeholder: B) -> Self
+    where
+        S: Into<String>,
+        B: Into<Bytes>,
+    {
+        let extension: String = extension.into();
+        self.placeholders_by_extension
+            .insert(extension.to_lowercase(), placeholder.into());
+        self
+    }
+}
+
+impl FileResolver for PlaceholderResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let Self {
+            default_placeholder,
+            placeholders_by_extension,
+        } = self;
+        let extension = id
+            .vpath()
+            .as_rootless_path()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        extension
+            .and_then(|extension| placeholders_by_extension.get(&extension))
+            .or(default_placeholder.as_ref())
+            .map(Cow::Borrowed)
+            .ok_or_else(|| not_found(id))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        Err(not_found(id))
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let Self {
+            default_placeholder,
+            placeholders_by_extension,
+        } = self;
+        default_placeholder
+            .iter()
+            .chain(placeholders_by_extension.values())
+            .map(|bytes| bytes.len())
+            .sum()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct FileSystemResolver {
-    root: PathBuf,
+    roots: Vec<PathBuf>,
     local_package_root: Option<PathBuf>,
+    symlink_policy: SymlinkPolicy,
+    allowed_extensions: Option<Vec<String>>,
+    case_insensitive_paths: bool,
+    #[cfg(feature = "mmap")]
+    memory_map_min_size: Option<u64>,
+}
+
+/// How `FileSystemResolver` treats symlinks. Virtual paths can never escape the root
+/// lexically (e.g. with `../../etc/passwd`), but symlinks inside of the root can point
+/// anywhere.
+///
+/// The default `SymlinkPolicy::Follow` does NOT confine reads to the root. With the other
+/// policies, the path is canonicalized, checked and the canonical path is opened, so a file
+/// outside of the root is never read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Follow all symlinks, also to files outside of the root.
+    #[default]
+    Follow,
+    /// Canonicalize the path and only allow it, if it still is under the root.
+    WithinRoot,
+    /// Deny all paths, that contain a symlink below the root.
+    Deny,
 }
 
 impl FileSystemResolver {
     pub fn new(root: PathBuf) -> Self {
-        let mut root = root.clone();
-        // trailing slash is necessary for resolve function, which is, what this 'hack' does
-        // https://users.rust-lang.org/t/trailing-in-paths/43166/9
-        root.push("");
+        Self::new_with_roots([root])
+    }
+
+    /// Use multiple roots. When resolving a local file, the roots are consulted in
+    /// the given order, until the file is found in one of them.
+    pub fn new_with_roots<I, P>(roots: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
         Self {
-            root,
+            roots: roots
+                .into_iter()
+                .map(|root| with_trailing_slash(root.into()))
+                .collect(),
             local_package_root: None,
+            symlink_policy: Default::default(),
+            allowed_extensions: None,
+            case_insensitive_paths: false,
+            #[cfg(feature = "mmap")]
+            memory_map_min_size: None,
         }
     }
 
+    /// Add a root, that is consulted after the already existing ones.
+    pub fn with_root(mut self, root: PathBuf) -> Self {
+        self.roots.push(with_trailing_slash(root));
+        self
+    }
+
     /// Use other path to look for local packages
     pub fn with_local_package_root(self, path: PathBuf) -> Self {
         Self {
@@ -147,13 +631,77 @@ impl FileSystemResolver {
         }
     }
 
+    /// Set how symlinks are treated. The default follows symlinks out of the root, so use
+    /// `SymlinkPolicy::WithinRoot` or `SymlinkPolicy::Deny`, when compiling untrusted
+    /// templates.
+    pub fn with_symlink_policy(self, symlink_policy: SymlinkPolicy) -> Self {
+        Self {
+            symlink_policy,
+            ..self
+        }
+    }
+
+    /// Only resolve files with one of these extensions (case insensitive, without dot).
+    /// All other files are denied.
+    pub fn with_allowed_extensions<I, S>(self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allowed_extensions: Some(extensions.into_iter().map(Into::into).collect()),
+            ..self
+        }
+    }
+
+    /// When a file doesn't exist, look for a file, whose path only differs in case, e.g. for
+    /// templates authored on Windows. Backslashes are treated as path separators. Slower for
+    /// missing files, as the directories are listed. See also `PathNormalization`.
+    pub fn with_case_insensitive_paths(self) -> Self {
+        Self {
+            case_insensitive_paths: true,
+            ..self
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    /// Memory-map binary files with at least `min_size` bytes instead of reading them into
+    /// memory, e.g. for large images or data files. Sources are always read.
+    ///
+    /// Each file is mapped at most once per process and stays mapped until the process exits,
+    /// as typst 0.12 can only borrow `'static` data without copying, and documents or the
+    /// caches of typst may still reference it. A file, that changed after it was mapped, is
+    /// read into memory instead of being mapped again.
+    ///
+    /// The files must not be modified in place (e.g. truncated) while they are mapped, which
+    /// is undefined behavior and may crash the process with `SIGBUS`. Replace them atomically
+    /// (write a new file and rename it) instead. Don't combine it with
+    /// `watch::TemplateWatcher`, for which the files are usually edited in place.
+    pub fn with_memory_map(self, min_size: u64) -> Self {
+        Self {
+            memory_map_min_size: Some(min_size),
+            ..self
+        }
+    }
+
     fn resolve_bytes(&self, id: FileId) -> FileResult<Vec<u8>> {
+        self.resolve_with(id, |path| {
+            std::fs::read(path).map_err(|error| FileError::from_io(error, path))
+        })
+    }
+
+    /// Find the file of `id` and read it with `read`.
+    fn resolve_with<T, F>(&self, id: FileId, read: F) -> FileResult<T>
+    where
+        F: Fn(&Path) -> FileResult<T>,
+    {
         let Self {
-            root,
+            roots,
             local_package_root,
+            ..
         } = self;
         // https://github.com/typst/typst/blob/16736feb13eec87eb9ca114deaeb4f7eeb7409d2/crates/typst-kit/src/package.rs#L102C16-L102C38
-        let dir: Cow<Path> = if let Some(package) = id.package() {
+        if let Some(package) = id.package() {
             let data_dir = if let Some(data_dir) = local_package_root {
                 Cow::Borrowed(data_dir)
             } else if let Some(data_dir) = dirs::data_dir() {
@@ -164,18 +712,164 @@ impl FileSystemResolver {
             let subdir = Path::new(package.namespace.as_str())
                 .join(package.name.as_str())
                 .join(package.version.to_string());
-            Cow::Owned(data_dir.join(subdir))
-        } else {
-            Cow::Borrowed(root)
-        };
+            return self.read_from_dir(&data_dir.join(subdir), id, &read);
+        }
+
+        let mut last_error = not_found(id);
+        for root in roots {
+            match self.read_from_dir(root, id, &read) {
+                Ok(content) => return Ok(content),
+                Err(error) => last_error = error,
+            }
+        }
+        Err(last_error)
+    }
 
-        let path = id
+    fn read_from_dir<T, F>(&self, dir: &Path, id: FileId, read: F) -> FileResult<T>
+    where
+        F: Fn(&Path) -> FileResult<T>,
+    {
+        let mut path = id
             .vpath()
-            .resolve(&dir)
+            .resolve(dir)
             .ok_or_else(|| FileError::NotFound(dir.to_path_buf()))?;
-        let content = std::fs::read(&path).map_err(|error| FileError::from_io(error, &path))?;
-        Ok(content.into())
+        if self.case_insensitive_paths && !path.exists() {
+            if let Some(matched) = find_case_insensitive(dir, id.vpath()) {
+                path = matched;
+            }
+        }
+        self.check_extension(&path)?;
+        let path = self.confine(dir, path)?;
+        read(&path)
     }
+
+    fn check_extension(&self, path: &Path) -> FileResult<()> {
+        let Some(allowed_extensions) = &self.allowed_extensions else {
+            return Ok(());
+        };
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let allowed = extension.is_some_and(|extension| {
+            allowed_extensions
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(extension))
+        });
+        if !allowed {
+            return Err(FileError::AccessDenied);
+        }
+        Ok(())
+    }
+
+    /// The path, that is read, according to the `SymlinkPolicy`. Unless symlinks are
+    /// followed, it is the canonical path, that was checked, so the read doesn't resolve the
+    /// symlinks a second time.
+    fn confine(&self, dir: &Path, path: PathBuf) -> FileResult<PathBuf> {
+        if self.symlink_policy == SymlinkPolicy::Follow {
+            return Ok(path);
+        }
+        let canonical_dir = dir
+            .canonicalize()
+            .map_err(|error| FileError::from_io(error, dir))?;
+        let canonical_path = path
+            .canonicalize()
+            .map_err(|error| FileError::from_io(error, &path))?;
+        let escapes_root = !canonical_path.starts_with(&canonical_dir);
+        let contains_symlink = match path.strip_prefix(dir) {
+            Ok(relative) => canonical_path != canonical_dir.join(relative),
+            Err(_) => true,
+        };
+        let denied = match self.symlink_policy {
+            SymlinkPolicy::Follow => false,
+            SymlinkPolicy::WithinRoot => escapes_root,
+            SymlinkPolicy::Deny => escapes_root || contains_symlink,
+        };
+        if denied {
+            return Err(FileError::AccessDenied);
+        }
+        Ok(canonical_path)
+    }
+}
+
+// trailing slash is necessary for resolve function, which is, what this 'hack' does
+// https://users.rust-lang.org/t/trailing-in-paths/43166/9
+fn with_trailing_slash(mut root: PathBuf) -> PathBuf {
+    root.push("");
+    root
+}
+
+/// Files, that were memory-mapped, by path. See `FileSystemResolver::with_memory_map()`.
+#[cfg(feature = "mmap")]
+static MEMORY_MAPS: OnceLock<Mutex<HashMap<PathBuf, MemoryMap>>> = OnceLock::new();
+
+#[cfg(feature = "mmap")]
+struct MemoryMap {
+    mmap: &'static memmap2::Mmap,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+#[cfg(feature = "mmap")]
+fn read_memory_mapped(path: &Path, min_size: u64) -> FileResult<Bytes> {
+    let file = std::fs::File::open(path).map_err(|error| FileError::from_io(error, path))?;
+    let metadata = file
+        .metadata()
+        .map_err(|error| FileError::from_io(error, path))?;
+    let len = metadata.len();
+    if len < min_size {
+        let bytes = std::fs::read(path).map_err(|error| FileError::from_io(error, path))?;
+        return Ok(bytes.into());
+    }
+    let modified = metadata.modified().ok();
+    let mut memory_maps = MEMORY_MAPS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    match memory_maps.get(path) {
+        Some(memory_map) if memory_map.len == len && memory_map.modified == modified => {
+            return Ok(Bytes::from_static(memory_map.mmap));
+        }
+        // Mapping it again would keep both mappings alive until the process exits.
+        Some(_) => {
+            let bytes = std::fs::read(path).map_err(|error| FileError::from_io(error, path))?;
+            return Ok(bytes.into());
+        }
+        None => (),
+    }
+    // SAFETY: The file must not be modified in place while it is mapped, see
+    // `FileSystemResolver::with_memory_map()`.
+    let mmap =
+        unsafe { memmap2::Mmap::map(&file) }.map_err(|error| FileError::from_io(error, path))?;
+    let mmap: &'static memmap2::Mmap = Box::leak(Box::new(mmap));
+    memory_maps.insert(
+        path.to_path_buf(),
+        MemoryMap {
+            mmap,
+            len,
+            modified,
+        },
+    );
+    Ok(Bytes::from_static(mmap))
+}
+
+/// Walk from `dir` along the components of `vpath` and match each of them regardless of its
+/// case. Backslashes are treated as separators.
+fn find_case_insensitive(dir: &Path, vpath: &VirtualPath) -> Option<PathBuf> {
+    let rootless = vpath
+        .as_rootless_path()
+        .to_string_lossy()
+        .replace('\\', "/");
+    let mut path = dir.to_path_buf();
+    for component in rootless.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if component == ".." {
+            return None;
+        }
+        let component = component.to_lowercase();
+        let matched = std::fs::read_dir(&path).ok()?.find_map(|entry| {
+            let file_name = entry.ok()?.file_name();
+            (file_name.to_string_lossy().to_lowercase() == component).then_some(file_name)
+        })?;
+        path.push(matched);
+    }
+    Some(path)
 }
 
 impl IntoCachedFileResolver for FileSystemResolver {
@@ -187,14 +881,158 @@ impl IntoCachedFileResolver for FileSystemResolver {
 }
 
 impl FileResolver for FileSystemResolver {
-    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<Bytes>> {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        #[cfg(feature = "mmap")]
+        if let Some(min_size) = self.memory_map_min_size {
+            let bytes = self.resolve_with(id, |path| read_memory_mapped(path, min_size))?;
+            return Ok(Cow::Owned(bytes));
+        }
         let b = self.resolve_bytes(id)?;
         Ok(Cow::Owned(b.into()))
     }
 
-    fn resolve_source(&self, id: FileId) -> FileResult<Cow<Source>> {
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
         let file = self.resolve_bytes(id)?;
         let source = bytes_to_source(id, &file)?;
         Ok(Cow::Owned(source))
     }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        let Self {
+            roots,
+            local_package_root,
+            ..
+        } = self;
+        roots.iter().chain(local_package_root).cloned().collect()
+    }
+}
+
+/// Resolves packages of configured namespaces from local directories, without the typst
+/// registry, e.g. for developing in-house packages (`@local/mylib:0.1.0`) alongside the
+/// application. Files, that are not part of one of these packages, are not resolved.
+///
+/// Example:
+/// ```rust
+/// # use std::str::FromStr;
+/// # use typst::syntax::package::PackageSpec;
+/// # use typst_as_lib::file_resolver::LocalPackageResolver;
+/// let resolver = LocalPackageResolver::new()
+///     // `@local/mylib:0.1.0` -> `./packages/mylib/0.1.0/`
+///     .with_namespace("local", "./packages")
+///     // `@acme/letterhead:1.0.0` -> `../letterhead/`
+///     .with_package(PackageSpec::from_str("@acme/letterhead:1.0.0").unwrap(), "../letterhead");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LocalPackageResolver {
+    namespaces: HashMap<String, PathBuf>,
+    packages: HashMap<PackageSpec, PathBuf>,
+}
+
+impl LocalPackageResolver {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Resolve packages `@<namespace>/<name>:<version>` from `<path>/<name>/<version>/`.
+    pub fn with_namespace<S, P>(mut self, namespace: S, path: P) -> Self
+    where
+        S: Into<String>,
+        P: Into<PathBuf>,
+    {
+        self.namespaces.insert(namespace.into(), path.into());
+        self
+    }
+
+    /// Resolve the package from the directory `path`. Takes precedence over
+    /// `LocalPackageResolver::with_namespace()`.
+    pub fn with_package<P>(mut self, package: PackageSpec, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.packages.insert(package, path.into());
+        self
+    }
+
+    fn resolve_bytes(&self, id: FileId) -> FileResult<Vec<u8>> {
+        let Self {
+            namespaces,
+            packages,
+        } = self;
+        let Some(package) = id.package() else {
+            return Err(not_found(id));
+        };
+        let dir = if let Some(dir) = packages.get(package) {
+            Cow::Borrowed(dir)
+        } else if let Some(dir) = namespaces.get(package.namespace.as_str()) {
+            Cow::Owned(
+                dir.join(package.name.as_str())
+                    .join(package.version.to_string()),
+            )
+        } else {
+            return Err(not_found(id));
+        };
+        let path = id.vpath().resolve(&dir).ok_or_else(|| not_found(id))?;
+        std::fs::read(&path).map_err(|error| FileError::from_io(error, &path))
+    }
+}
+
+impl FileResolver for LocalPackageResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let bytes = self.resolve_bytes(id)?;
+        Ok(Cow::Owned(bytes.into()))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let bytes = self.resolve_bytes(id)?;
+        Ok(Cow::Owned(bytes_to_source(id, &bytes)?))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::test_util::{file_id, temp_dir};
+
+    /// `root/inside.typ`, `root/link.typ` -> `root/inside.typ` and `root/escape.typ` ->
+    /// `outside/secret.typ`.
+    fn resolver(name: &str, symlink_policy: SymlinkPolicy) -> FileSystemResolver {
+        let dir = temp_dir(name);
+        let (root, outside) = (dir.join("root"), dir.join("outside"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join("inside.typ"), "inside").unwrap();
+        std::fs::write(outside.join("secret.typ"), "secret").unwrap();
+        symlink(root.join("inside.typ"), root.join("link.typ")).unwrap();
+        symlink(outside.join("secret.typ"), root.join("escape.typ")).unwrap();
+        FileSystemResolver::new(root).with_symlink_policy(symlink_policy)
+    }
+
+    fn read(resolver: &FileSystemResolver, path: &str) -> FileResult<String> {
+        let source = resolver.resolve_source(file_id(path))?;
+        Ok(source.text().to_owned())
+    }
+
+    #[test]
+    fn follow_reads_outside_of_root() {
+        let resolver = resolver("symlinks-follow", SymlinkPolicy::Follow);
+        assert_eq!(read(&resolver, "/escape.typ").unwrap(), "secret");
+    }
+
+    #[test]
+    fn within_root_denies_symlinks_out_of_root() {
+        let resolver = resolver("symlinks-within-root", SymlinkPolicy::WithinRoot);
+        assert_eq!(read(&resolver, "/link.typ").unwrap(), "inside");
+        assert_eq!(read(&resolver, "/escape.typ"), Err(FileError::AccessDenied));
+        assert!(read(&resolver, "/../outside/secret.typ").is_err());
+    }
+
+    #[test]
+    fn deny_denies_all_symlinks() {
+        let resolver = resolver("symlinks-deny", SymlinkPolicy::Deny);
+        assert_eq!(read(&resolver, "/inside.typ").unwrap(), "inside");
+        assert_eq!(read(&resolver, "/link.typ"), Err(FileError::AccessDenied));
+        assert_eq!(read(&resolver, "/escape.typ"), Err(FileError::AccessDenied));
+    }
 }
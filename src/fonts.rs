@@ -0,0 +1,113 @@
+use std::borrow::Cow;
+
+use typst::foundations::Bytes;
+use typst::text::Font;
+
+use crate::TypstAsLibError;
+
+#[cfg(feature = "woff")]
+static WOFF_SIGNATURE: &[u8] = b"wOFF";
+
+#[cfg(feature = "woff")]
+static WOFF2_SIGNATURE: &[u8] = b"wOF2";
+
+/// Fonts or font files, that can be added with `TypstTemplateCollection::try_add_fonts()`.
+/// Font files (bytes) are parsed with `fonts_from_bytes()`, so with feature `woff`, WOFF and
+/// WOFF2 files are converted to OTF/TTF.
+///
+/// Example:
+/// ```rust,no_run
+/// # use typst_as_lib::TypstTemplate;
+/// # static TEMPLATE_FILE: &str = include_str!("../examples/templates/template.typ");
+/// static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
+/// // ...
+/// let brand_font = std::fs::read("./fonts/brand.woff2")?;
+/// let template = TypstTemplate::new(vec![], TEMPLATE_FILE)
+///     .try_add_fonts(vec![FONT.to_vec(), brand_font])?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait IntoFonts {
+    fn into_fonts(self) -> Result<Vec<Font>, TypstAsLibError>;
+}
+
+impl IntoFonts for Font {
+    fn into_fonts(self) -> Result<Vec<Font>, TypstAsLibError> {
+        Ok(vec![self])
+    }
+}
+
+impl IntoFonts for &[u8] {
+    fn into_fonts(self) -> Result<Vec<Font>, TypstAsLibError> {
+        fonts_from_bytes(self)
+    }
+}
+
+impl IntoFonts for Vec<u8> {
+    fn into_fonts(self) -> Result<Vec<Font>, TypstAsLibError> {
+        fonts_from_bytes(self)
+    }
+}
+
+impl IntoFonts for Bytes {
+    fn into_fonts(self) -> Result<Vec<Font>, TypstAsLibError> {
+        fonts_from_bytes(self)
+    }
+}
+
+impl<T> IntoFonts for Vec<T>
+where
+    T: IntoFonts,
+{
+    fn into_fonts(self) -> Result<Vec<Font>, TypstAsLibError> {
+        let mut fonts = Vec::new();
+        for item in self {
+            fonts.extend(item.into_fonts()?);
+        }
+        Ok(fonts)
+    }
+}
+
+/// Parses all fonts contained in `data` (TTF, OTF or font collections). With
+/// feature `woff`, WOFF and WOFF2 files are converted before parsing.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::{fonts::fonts_from_bytes, TypstTemplate};
+/// # static TEMPLATE_FILE: &str = include_str!("../examples/templates/template.typ");
+/// static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
+/// // ...
+/// let fonts = fonts_from_bytes(FONT).expect("Could not parse font!");
+/// let template = TypstTemplate::new(fonts, TEMPLATE_FILE);
+/// ```
+pub fn fonts_from_bytes<B>(data: B) -> Result<Vec<Font>, TypstAsLibError>
+where
+    B: AsRef<[u8]>,
+{
+    let data = decode_web_font(data.as_ref())?;
+    let fonts: Vec<Font> = Font::iter(Bytes::from(data.as_ref())).collect();
+    if fonts.is_empty() {
+        return Err(TypstAsLibError::InvalidFont(
+            "Data does not contain any font".to_owned(),
+        ));
+    }
+    Ok(fonts)
+}
+
+#[cfg(feature = "woff")]
+fn decode_web_font(data: &[u8]) -> Result<Cow<'_, [u8]>, TypstAsLibError> {
+    let decoded = if data.starts_with(WOFF_SIGNATURE) {
+        wuff::decompress_woff1(data)
+    } else if data.starts_with(WOFF2_SIGNATURE) {
+        wuff::decompress_woff2(data)
+    } else {
+        return Ok(Cow::Borrowed(data));
+    };
+    decoded
+        .map(Cow::Owned)
+        .map_err(|error| TypstAsLibError::InvalidFont(format!("{error:?}")))
+}
+
+#[cfg(not(feature = "woff"))]
+fn decode_web_font(data: &[u8]) -> Result<Cow<'_, [u8]>, TypstAsLibError> {
+    Ok(Cow::Borrowed(data))
+}
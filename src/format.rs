@@ -0,0 +1,62 @@
+use typst::diag::SourceDiagnostic;
+use typstyle_core::{Config, Typstyle};
+
+use crate::schema::InputValidationError;
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Maximal line width of formatted sources, the default of typstyle.
+const FORMAT_WIDTH: usize = 120;
+
+impl TypstTemplateCollection {
+    /// Format the source, that the file resolvers return for `file_id`, with typstyle
+    /// (feature `format`), e.g. for a "format template" button of an editor. typstyle parses
+    /// with the same typst version, that compiles the templates. The formatted text is
+    /// returned, the stored source is not changed.
+    ///
+    /// Fails with `TypstAsLibError::TypstSource`, if the source has syntax errors.
+    ///
+    /// Example:
+    /// ```rust
+    /// # struct Store;
+    /// # impl Store {
+    /// #     fn save(&self, _path: &str, _text: &str) -> Result<(), typst_as_lib::TypstAsLibError> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// # let store = Store;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([("/invoice.typ", "#set page(width:10cm)\n= Invoice")]);
+    /// let formatted = template_collection.format_source("/invoice.typ")?;
+    /// store.save("/invoice.typ", &formatted)?;
+    /// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+    /// ```
+    pub fn format_source<F>(&self, file_id: F) -> Result<String, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let source = self.source(file_id)?;
+        let errors = source.root().errors();
+        if !errors.is_empty() {
+            return Err(TypstAsLibError::TypstSource(
+                errors.into_iter().map(SourceDiagnostic::from).collect(),
+            ));
+        }
+        Typstyle::new(Config::new().with_width(FORMAT_WIDTH))
+            .format_source(&source)
+            .map_err(|err| {
+                TypstAsLibError::InvalidInput(InputValidationError::other(err.to_string()))
+            })
+    }
+}
+
+impl TypstTemplate {
+    /// Format a source with typstyle. See `TypstTemplateCollection::format_source()`.
+    pub fn format_source<F>(&self, file_id: F) -> Result<String, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        self.collection.format_source(file_id)
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use typst::diag::Warned;
+use typst::foundations::Dict;
+use typst::syntax::package::PackageSpec;
+use typst::syntax::FileId;
+
+use crate::cached_file_resolver::poisoned_locks_recovered;
+use crate::cancellation::CancellationToken;
+use crate::escape::escape_typst_string;
+use crate::report::{MissingFont, Reported};
+use crate::{FileIdNewType, TypstTemplate, TypstTemplateCollection};
+
+/// Default deadline of a `HealthCheck`.
+pub const DEFAULT_HEALTH_CHECK_DEADLINE: Duration = Duration::from_secs(10);
+
+/// What `TypstTemplateCollection::health_check()` checks, e.g. for a readiness probe. By
+/// default a tiny document is compiled with the first font.
+///
+/// Example:
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use typst_as_lib::health::HealthCheck;
+/// # #[derive(Debug)]
+/// # struct ServiceUnavailable(String);
+/// # fn main() -> Result<(), ServiceUnavailable> {
+/// # use typst_as_lib::TypstTemplateCollection;
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template_collection = TypstTemplateCollection::new(vec![font])
+/// #     .with_static_source_file_resolver([("/invoice.typ", "= Invoice")]);
+/// let check = HealthCheck::new()
+///     .with_deadline(Duration::from_secs(5))
+///     .with_file("/invoice.typ")
+///     .with_package("@preview/cetz:0.3.1".parse().unwrap());
+/// // In the readiness handler:
+/// let report = template_collection.health_check(&check);
+/// if !report.healthy {
+///     return Err(ServiceUnavailable(report.errors.join("; ")));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    deadline: Duration,
+    files: Vec<FileId>,
+    package: Option<PackageSpec>,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            deadline: DEFAULT_HEALTH_CHECK_DEADLINE,
+            files: Vec::new(),
+            package: None,
+        }
+    }
+}
+
+impl HealthCheck {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The check fails, if it takes longer. Package downloads are cancelled at the deadline.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Check, that the file resolvers can resolve the file, e.g. the main file of a template.
+    pub fn with_file<F>(mut self, file_id: F) -> Self
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(file_id) = file_id.into();
+        self.files.push(file_id);
+        self
+    }
+
+    /// Import the package in the document, which downloads it, if it is not cached.
+    pub fn with_package(mut self, package: PackageSpec) -> Self {
+        self.package = Some(package);
+        self
+    }
+}
+
+/// Result of `TypstTemplateCollection::health_check()`.
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    /// No errors and within the deadline.
+    pub healthy: bool,
+    pub duration: Duration,
+    pub errors: Vec<String>,
+    /// Font problems of the compiled document. See `CompilationReport::missing_fonts`.
+    pub missing_fonts: Vec<MissingFont>,
+    /// See `cached_file_resolver::poisoned_locks_recovered()`. Not counted as error.
+    pub poisoned_locks_recovered: u64,
+}
+
+impl TypstTemplateCollection {
+    /// Compile a tiny built-in document, that uses the first font (and the package of
+    /// `check`), and resolve the files of `check` with the file resolvers, within the
+    /// deadline of `check`.
+    pub fn health_check(&self, check: &HealthCheck) -> HealthReport {
+        let HealthCheck {
+            deadline,
+            files,
+            package,
+        } = check;
+        let start = Instant::now();
+        let token = CancellationToken::new();
+        let (done, finished) = mpsc::channel::<()>();
+        let (errors, missing_fonts) = thread::scope(|scope| {
+            // Cancel downloads, that run past the deadline.
+            let watchdog_token = token.clone();
+            let deadline = *deadline;
+            scope.spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(deadline) {
+                    watchdog_token.cancel();
+                }
+            });
+            let result = token.run(|| self.run_health_check(files, package.as_ref()));
+            let _ = done.send(());
+            result
+        });
+
+        let duration = start.elapsed();
+        let mut errors = errors;
+        if duration > *deadline {
+            errors.push(format!(
+                "took {} ms, longer than the deadline of {} ms",
+                duration.as_millis(),
+                deadline.as_millis()
+            ));
+        }
+        if !missing_fonts.is_empty() {
+            errors.push(format!("missing fonts: {missing_fonts:?}"));
+        }
+        HealthReport {
+            healthy: errors.is_empty(),
+            duration,
+            errors,
+            missing_fonts,
+            poisoned_locks_recovered: poisoned_locks_recovered(),
+        }
+    }
+
+    fn run_health_check(
+        &self,
+        files: &[FileId],
+        package: Option<&PackageSpec>,
+    ) -> (Vec<String>, Vec<MissingFont>) {
+        let mut errors = Vec::new();
+        for &id in files {
+            if let Err(err) = self.file(id) {
+                errors.push(format!("{:?}: {err}", id.vpath()));
+            }
+        }
+
+        let Some(font) = self.fonts.first() else {
+            errors.push("no fonts".to_owned());
+            return (errors, Vec::new());
+        };
+        let mut source = format!(
+            "#set text(font: \"{}\")\n",
+            escape_typst_string(&font.info().family)
+        );
+        if let Some(package) = package {
+            source.push_str(&format!("#import \"{package}\" as _package\n"));
+        }
+        source.push_str("Health check 0123456789\n");
+
+        let Reported { output, report } = self.compile_source(source, Dict::new());
+        let Warned { output, .. } = output;
+        if let Err(err) = output {
+            errors.push(format!("compilation failed: {err}"));
+        }
+        (errors, report.missing_fonts)
+    }
+}
+
+impl TypstTemplate {
+    /// Compile a tiny built-in document. See `TypstTemplateCollection::health_check()`.
+    pub fn health_check(&self, check: &HealthCheck) -> HealthReport {
+        self.collection.health_check(check)
+    }
+}
@@ -0,0 +1,107 @@
+use std::ops::Range;
+
+use typst::syntax::{ast, highlight, LinkedNode, Source, SyntaxKind, Tag};
+
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// A highlighted range of a source. See `highlight_source()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightToken {
+    /// Byte range in the text of the source.
+    pub range: Range<usize>,
+    /// Category of the token. `Tag::css_class()` returns a CSS class for it, e.g.
+    /// `typ-key`.
+    pub tag: Tag,
+    /// The path of an `#import` or `#include`, that the file resolvers can't resolve. Only
+    /// set by `TypstTemplateCollection::highlight()`.
+    pub unresolved: bool,
+}
+
+/// Highlighting tokens of `source` in the order of the text, with typst's own highlighter.
+/// Ranges without a token (e.g. plain markup text) are not highlighted. Tokens can be nested:
+/// the token of e.g. a heading or strong text comes before the tokens within its range.
+pub fn highlight_source(source: &Source) -> Vec<HighlightToken> {
+    let mut tokens = Vec::new();
+    collect_tokens(&LinkedNode::new(source.root()), &mut |node, tag| {
+        tokens.push(HighlightToken {
+            range: node.range(),
+            tag,
+            unresolved: false,
+        });
+    });
+    tokens
+}
+
+fn collect_tokens<F>(node: &LinkedNode, f: &mut F)
+where
+    F: FnMut(&LinkedNode, Tag),
+{
+    if let Some(tag) = highlight(node) {
+        f(node, tag);
+    }
+    for child in node.children() {
+        collect_tokens(&child, f);
+    }
+}
+
+/// The path of a string literal, that is the source of an `#import` or `#include`.
+/// Package imports are ignored, so highlighting never downloads packages.
+fn imported_path(node: &LinkedNode) -> Option<String> {
+    let parent = node.parent()?;
+    if !matches!(
+        parent.kind(),
+        SyntaxKind::ModuleImport | SyntaxKind::ModuleInclude
+    ) {
+        return None;
+    }
+    let path = node.cast::<ast::Str>()?.get();
+    (!path.starts_with('@')).then(|| path.to_string())
+}
+
+impl TypstTemplateCollection {
+    /// Highlighting tokens of the source, that the file resolvers return for `file_id`, e.g.
+    /// for a web editor. Like `highlight_source()`, but paths of imports and includes, that
+    /// the file resolvers can't resolve, are marked with `HighlightToken::unresolved`.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([("/invoice.typ", r#"#import "/style.typ": card"#)]);
+    /// # let mut spans = Vec::new();
+    /// for token in template_collection.highlight("/invoice.typ")? {
+    ///     let class = if token.unresolved { "typ-error" } else { token.tag.css_class() };
+    ///     spans.push((token.range, class));
+    /// }
+    /// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+    /// ```
+    pub fn highlight<F>(&self, file_id: F) -> Result<Vec<HighlightToken>, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let source = self.source(file_id)?;
+        let id = source.id();
+        let mut tokens = Vec::new();
+        collect_tokens(&LinkedNode::new(source.root()), &mut |node, tag| {
+            let unresolved =
+                imported_path(node).is_some_and(|path| self.source(id.join(&path)).is_err());
+            tokens.push(HighlightToken {
+                range: node.range(),
+                tag,
+                unresolved,
+            });
+        });
+        Ok(tokens)
+    }
+}
+
+impl TypstTemplate {
+    /// Highlighting tokens of a source. See `TypstTemplateCollection::highlight()`.
+    pub fn highlight<F>(&self, file_id: F) -> Result<Vec<HighlightToken>, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        self.collection.highlight(file_id)
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Duration, Utc};
+use typst::foundations::{Dict, IntoValue, Value};
+
+use crate::util;
+
+/// Module, that the host information is injected into (`#import host: version`).
+pub(crate) const HOST_MODULE: &str = "host";
+
+/// Information about the host application, that is made available in typst with
+/// `#import host: env, version, commit, timestamp`, e.g. for "generated by version X at Y"
+/// footers.
+///
+///   - `env`: Dictionary of the selected environment variables, that are set.
+///   - `version`: Version of the application or `none`.
+///   - `commit`: Git commit of the application or `none`.
+///   - `timestamp`: Time of the compilation (UTC).
+///   - `datetime`: Time of the compilation in a fixed UTC offset, if enabled with
+///     `HostInfo::with_datetime()`.
+///
+/// See `TypstTemplateCollection::with_host_info()`.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::host::HostInfo;
+/// let host_info = HostInfo::new()
+///     .with_version(env!("CARGO_PKG_VERSION"))
+///     .with_commit(option_env!("GIT_COMMIT").unwrap_or("unknown"))
+///     .with_env_var("DEPLOYMENT");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HostInfo {
+    env_vars: Vec<String>,
+    version: Option<String>,
+    commit: Option<String>,
+    /// UTC offset of `host.datetime` in minutes.
+    datetime_offset: Option<i32>,
+}
+
+impl HostInfo {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Make the environment variable available in `host.env`. Only environment variables,
+    /// that are selected like this, are exposed to templates.
+    pub fn with_env_var<S>(mut self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.env_vars.push(name.into());
+        self
+    }
+
+    pub fn with_version<S>(mut self, version: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn with_commit<S>(mut self, commit: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.commit = Some(commit.into());
+        self
+    }
+
+    /// Make the time of the compilation available in `host.datetime`, in the UTC offset
+    /// `offset_minutes` (e.g. `120` for CEST). Unlike `datetime.today()`, it includes the time
+    /// of day.
+    pub fn with_datetime(mut self, offset_minutes: i32) -> Self {
+        self.datetime_offset = Some(offset_minutes);
+        self
+    }
+
+    /// Values, that don't change between compilations. Environment variables are read once.
+    pub(crate) fn static_values(&self) -> [(&'static str, Value); 3] {
+        let Self {
+            env_vars,
+            version,
+            commit,
+            ..
+        } = self;
+        let mut env = Dict::new();
+        for name in env_vars {
+            if let Ok(value) = std::env::var(name) {
+                env.insert(name.as_str().into(), value.into_value());
+            }
+        }
+        [
+            ("env", env.into_value()),
+            ("version", version.clone().into_value()),
+            ("commit", commit.clone().into_value()),
+        ]
+    }
+
+    /// Values, that are injected for each compilation.
+    pub(crate) fn dynamic_values(&self, now: DateTime<Utc>) -> Vec<(&'static str, Value)> {
+        let mut values = vec![("timestamp", util::datetime(now).into_value())];
+        if let Some(offset) = self.datetime_offset {
+            let local = now + Duration::minutes(offset.into());
+            values.push(("datetime", util::datetime(local).into_value()));
+        }
+        values
+    }
+}
@@ -0,0 +1,44 @@
+use typst::foundations::{Dict, Value};
+
+/// How the inputs of a compilation are merged with the default inputs.
+/// See `TypstTemplateCollection::default_inputs()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum InputMerge {
+    /// Nested dictionaries are merged recursively. Other values of the inputs replace the
+    /// default values.
+    #[default]
+    Deep,
+    /// Top level keys of the inputs replace the default values.
+    Shallow,
+    /// The default inputs are only used, when no inputs are given.
+    Replace,
+}
+
+impl InputMerge {
+    pub fn merge(self, defaults: &Dict, inputs: Dict) -> Dict {
+        match self {
+            InputMerge::Deep => deep_merge(defaults.clone(), inputs),
+            InputMerge::Shallow => {
+                let mut merged = defaults.clone();
+                for (key, value) in inputs {
+                    merged.insert(key, value);
+                }
+                merged
+            }
+            InputMerge::Replace => inputs,
+        }
+    }
+}
+
+fn deep_merge(mut defaults: Dict, inputs: Dict) -> Dict {
+    for (key, value) in inputs {
+        let value = match (defaults.get(&key), value) {
+            (Ok(Value::Dict(default)), Value::Dict(value)) => {
+                Value::Dict(deep_merge(default.clone(), value))
+            }
+            (_, value) => value,
+        };
+        defaults.insert(key, value);
+    }
+    defaults
+}
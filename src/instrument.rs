@@ -0,0 +1,149 @@
+//! Events and spans, that are emitted with feature `tracing`. With feature `log`, the events
+//! are emitted with the `log` crate. Without both, they compile to nothing.
+#![cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+
+use std::time::Duration;
+
+use typst::syntax::FileId;
+use typst::text::Font;
+
+#[cfg(feature = "packages")]
+use typst::syntax::package::PackageSpec;
+
+use crate::report::ResolveAttempt;
+use crate::resolver_middleware::ResolveKind;
+
+pub(crate) struct SpanGuard {
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+pub(crate) fn compile_span(main: FileId) -> SpanGuard {
+    SpanGuard {
+        #[cfg(feature = "tracing")]
+        _span: tracing::debug_span!("typst_compile", main = ?main).entered(),
+    }
+}
+
+/// Span of the resolution of a file, around the attempts of the file resolvers.
+pub(crate) fn resolve_span(id: FileId, kind: ResolveKind) -> SpanGuard {
+    SpanGuard {
+        #[cfg(feature = "tracing")]
+        _span: tracing::trace_span!("typst_resolve", id = ?id, ?kind).entered(),
+    }
+}
+
+pub(crate) fn compile_finished(main: FileId, succeeded: bool, warnings: usize, duration: Duration) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(main = ?main, succeeded, warnings, ?duration, "compile finished");
+    #[cfg(feature = "log")]
+    log::debug!("compile finished: main={main:?} succeeded={succeeded} warnings={warnings} duration={duration:?}");
+}
+
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub(crate) fn compile_warning(warning: &typst::diag::SourceDiagnostic) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(message = %warning.message, hints = ?warning.hints, "typst warning");
+    #[cfg(feature = "log")]
+    log::warn!(
+        "typst warning: {} hints={:?}",
+        warning.message,
+        warning.hints
+    );
+}
+
+pub(crate) fn cache_lock_poisoned() {
+    #[cfg(feature = "tracing")]
+    tracing::warn!("recovered poisoned cache lock");
+    #[cfg(feature = "log")]
+    log::warn!("recovered poisoned cache lock");
+}
+
+pub(crate) fn source_decoded(id: FileId, encoding: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(path = ?id.vpath(), encoding, "decoded source, that is not UTF-8");
+    #[cfg(feature = "log")]
+    log::warn!(
+        "decoded source {:?}, that is not UTF-8, as {encoding}",
+        id.vpath()
+    );
+}
+
+pub(crate) fn library_injected(module_name: &str, value_name: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(module_name, value_name, "injected input into library");
+    #[cfg(feature = "log")]
+    log::trace!("injected input into library: {module_name}.{value_name}");
+}
+
+pub(crate) fn file_resolved(attempt: &ResolveAttempt) {
+    #[cfg(feature = "tracing")]
+    {
+        let ResolveAttempt {
+            resolver,
+            id,
+            kind,
+            duration,
+            result,
+        } = attempt;
+        match result {
+            Ok(()) => tracing::debug!(resolver, id = ?id, ?kind, ?duration, "file resolved"),
+            Err(error) => {
+                tracing::trace!(resolver, id = ?id, ?kind, ?duration, %error, "file not resolved")
+            }
+        };
+    }
+    #[cfg(feature = "log")]
+    {
+        let ResolveAttempt {
+            resolver,
+            id,
+            kind,
+            duration,
+            result,
+        } = attempt;
+        match result {
+            Ok(()) => log::debug!("file resolved: {id:?} ({kind:?}) by {resolver} in {duration:?}"),
+            Err(error) => log::trace!(
+                "file not resolved: {id:?} ({kind:?}) by {resolver} in {duration:?}: {error}"
+            ),
+        };
+    }
+}
+
+#[cfg(feature = "packages")]
+pub(crate) fn package_download_started(package: &PackageSpec, url: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(%package, url, "downloading package");
+    #[cfg(feature = "log")]
+    log::info!("downloading package {package} from {url}");
+}
+
+#[cfg(feature = "packages")]
+pub(crate) fn package_download_failed(package: &PackageSpec, attempt: u32, error: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(%package, attempt, error, "package download failed");
+    #[cfg(feature = "log")]
+    log::warn!("package download of {package} failed (attempt {attempt}): {error}");
+}
+
+#[cfg(feature = "packages")]
+pub(crate) fn package_downloaded(package: &PackageSpec, duration: Duration) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(%package, ?duration, "package downloaded");
+    #[cfg(feature = "log")]
+    log::info!("package {package} downloaded in {duration:?}");
+}
+
+pub(crate) fn font_loaded(index: usize, font: Option<&Font>) {
+    #[cfg(feature = "tracing")]
+    match font {
+        Some(font) => tracing::trace!(index, family = %font.info().family, "font loaded"),
+        None => tracing::warn!(index, "font could not be loaded"),
+    };
+    #[cfg(feature = "log")]
+    match font {
+        Some(font) => log::trace!("font {index} loaded: {}", font.info().family),
+        None => log::warn!("font {index} could not be loaded"),
+    };
+}
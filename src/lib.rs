@@ -1,40 +1,169 @@
 use std::borrow::Cow;
-use std::ops::Deref;
+use std::collections::HashMap;
+use std::ops::{Deref, Range};
 use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
 
+use artifact_cache::ArtifactCache;
 use cached_file_resolver::IntoCachedFileResolver;
 use chrono::{DateTime, Datelike, Duration, Utc};
+use clock::{Clock, SystemClock};
 use ecow::EcoVec;
+use eviction::EvictionStrategy;
 use file_resolver::{
-    FileResolver, FileSystemResolver, MainSourceFileResolver, StaticFileResolver,
-    StaticSourceFileResolver,
+    is_image, FileResolver, FileResolverStats, FileSystemResolver, MainSourceFileResolver,
+    PlaceholderResolver, StaticFileResolver, StaticSourceFileResolver,
 };
+use fonts::IntoFonts;
+use host::{HostInfo, HOST_MODULE};
+use inputs::InputMerge;
+use memory::MemoryFootprint;
+use normalize::SourceNormalization;
+use paths::PathNormalization;
+use registry::{InputValidator, RegisteredTemplate, TemplateRegistry};
+use report::{
+    CompilationReport, DecodedSource, MissingFont, ReportRecorder, Reported, ResolutionCandidate,
+    ResolutionExplanation, ResolveAttempt, SubstitutedFile,
+};
+use resolver_middleware::ResolveKind;
+use result_cache::{CachedResult, Dependency, ResultCache};
+use schema::{InputSchema, InputValidationError};
 use thiserror::Error;
 use typst::diag::{FileError, FileResult, HintedString, SourceDiagnostic, Warned};
-use typst::foundations::{Bytes, Datetime, Dict, Module, Scope, Value};
+use typst::foundations::{Bytes, Datetime, Dict, IntoValue, Module, Scope, Value};
 use typst::model::Document;
 use typst::syntax::{package::PackageSpec, FileId, Source, VirtualPath};
 use typst::text::{Font, FontBook};
-use typst::utils::LazyHash;
+use typst::utils::{hash128, LazyHash};
 use typst::Library;
 use util::not_found;
 
+pub mod artifact_cache;
+pub mod assets;
+pub mod benchmark;
+pub mod bibliography;
+pub mod build_error;
 pub mod cached_file_resolver;
+pub mod cancellation;
+pub mod clock;
+pub mod data;
+pub mod describe;
+pub mod escape;
+pub mod eviction;
+pub mod feedback;
 pub mod file_resolver;
+pub mod fonts;
+pub mod health;
+pub mod highlight;
+pub mod host;
+pub mod inputs;
+pub(crate) mod instrument;
+pub mod links;
+pub mod lint;
+pub mod memory;
+pub mod merge;
+pub mod normalize;
+pub mod overlay;
+pub mod pages;
+pub mod paths;
+pub mod plugins;
+mod preamble;
+pub mod registry;
+pub mod report;
+pub mod resolver_middleware;
+pub mod result_cache;
+pub mod schema;
+pub mod session;
+pub mod tenant;
+pub mod text;
 pub(crate) mod util;
+pub mod warned;
+
+#[cfg(feature = "bundle")]
+pub mod bundle;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+#[cfg(feature = "format")]
+mod format;
+
+#[cfg(feature = "lsp-types")]
+pub mod lsp;
 
 #[cfg(feature = "packages")]
 pub mod package_resolver;
 
+#[cfg(feature = "embed")]
+pub use typst_as_lib_macros::embed_templates;
+
+#[cfg(feature = "pdf")]
+pub mod pdf;
+
+#[cfg(feature = "pdf")]
+pub mod pdf_attachments;
+
+#[cfg(feature = "pdf")]
+pub mod pdf_metadata;
+
+#[cfg(feature = "pdf")]
+mod pdf_update;
+
+#[cfg(feature = "pdf-validation")]
+pub mod pdf_validation;
+
+#[cfg(feature = "packages")]
+pub mod prefetch;
+
+#[cfg(feature = "render")]
+pub mod render;
+
+#[cfg(feature = "svg")]
+pub mod svg;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "packages")]
+pub mod vendor;
+
+#[cfg(feature = "watch")]
+pub mod watch;
+
+#[cfg(test)]
+mod test_util;
+
 // Inspired by https://github.com/tfachmann/typst-as-library/blob/main/src/lib.rs
 
+/// Virtual path of sources compiled with `TypstTemplateCollection::compile_source()`.
+const DETACHED_SOURCE_PATH: &str = "/__source__.typ";
+
+/// Output of `TypstTemplateCollection::compile_tracked()`.
+type ReportedCompilation = Reported<Warned<Result<Document, TypstAsLibError>>>;
+
 pub struct TypstTemplateCollection {
     book: LazyHash<FontBook>,
     fonts: Vec<Font>,
     inject_location: Option<InjectLocation>,
+    injections: Vec<Injection>,
+    default_inputs: Dict,
+    input_merge: InputMerge,
+    host_info: Option<HostInfo>,
     file_resolvers: Vec<Box<dyn FileResolver + Send + Sync + 'static>>,
+    fallback_file_resolver: Option<Box<dyn FileResolver + Send + Sync + 'static>>,
     library: LazyHash<Library>,
-    comemo_evict_max_age: Option<usize>,
+    eviction_strategy: EvictionStrategy,
+    compile_count: AtomicUsize,
+    registry: TemplateRegistry,
+    result_cache: Option<ResultCache>,
+    artifact_cache: Option<ArtifactCache>,
+    path_normalization: Option<PathNormalization>,
+    preamble: Option<String>,
+    source_normalization: Option<SourceNormalization>,
+    clock: Box<dyn Clock + Send + Sync + 'static>,
+    deny_network: bool,
 }
 
 impl TypstTemplateCollection {
@@ -42,13 +171,16 @@ impl TypstTemplateCollection {
     ///
     /// Example:
     /// ```rust
-    /// static TEMPLATE: &str = include_str!("./templates/template.typ");
-    /// static FONT: &[u8] = include_bytes!("./fonts/texgyrecursor-regular.otf");
+    /// # use typst::foundations::Bytes;
+    /// # use typst::text::Font;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+    /// static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
     /// // ...
     /// let font = Font::new(Bytes::from(FONT), 0)
     ///     .expect("Could not parse font!");
-    /// let template = TypstTemplate::new(vec![font])
-    ///     .with_static_file_resolver([TEMPLATE], []);
+    /// let template_collection = TypstTemplateCollection::new(vec![font])
+    ///     .with_static_source_file_resolver([("/template.typ", TEMPLATE)]);
     /// ```
     pub fn new<V>(fonts: V) -> Self
     where
@@ -59,9 +191,23 @@ impl TypstTemplateCollection {
             book: LazyHash::new(FontBook::from_fonts(&fonts)),
             fonts,
             inject_location: Default::default(),
+            injections: Default::default(),
+            default_inputs: Default::default(),
+            input_merge: Default::default(),
+            host_info: None,
             file_resolvers: Default::default(),
+            fallback_file_resolver: None,
             library: Default::default(),
-            comemo_evict_max_age: Some(0),
+            eviction_strategy: Default::default(),
+            compile_count: Default::default(),
+            registry: Default::default(),
+            result_cache: None,
+            artifact_cache: None,
+            path_normalization: None,
+            preamble: None,
+            source_normalization: None,
+            clock: Box::new(SystemClock),
+            deny_network: false,
         }
     }
 
@@ -89,6 +235,115 @@ impl TypstTemplateCollection {
         });
     }
 
+    /// Make a value available in typst under `#import module_name: value_name`. Can be called
+    /// multiple times, also for the same module. Other definitions of the module (e.g. of
+    /// the standard library) are kept. Fails, if `module_name` or `value_name` is a binding,
+    /// that can't be overwritten.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst::foundations::array;
+    /// # use typst::visualize::Color;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// let colors = array![Color::from_u8(0, 90, 160, 255), Color::BLACK];
+    /// let template_collection = TypstTemplateCollection::new(vec![font])
+    ///     .add_injection("company", "name", "Example Inc.")?
+    ///     .add_injection("company", "colors", colors)?;
+    /// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+    /// ```
+    pub fn add_injection<S, V>(
+        mut self,
+        module_name: S,
+        value_name: S,
+        value: V,
+    ) -> Result<Self, TypstAsLibError>
+    where
+        S: Into<String>,
+        V: IntoValue,
+    {
+        self.add_injection_mut(module_name, value_name, value)?;
+        Ok(self)
+    }
+
+    /// Make a value available in typst under `#import module_name: value_name`.
+    /// See `TypstTemplateCollection::add_injection()`.
+    pub fn add_injection_mut<S, V>(
+        &mut self,
+        module_name: S,
+        value_name: S,
+        value: V,
+    ) -> Result<(), TypstAsLibError>
+    where
+        S: Into<String>,
+        V: IntoValue,
+    {
+        let injection = Injection {
+            module_name: module_name.into(),
+            value_name: value_name.into(),
+            value: value.into_value(),
+        };
+        let Injection {
+            module_name,
+            value_name,
+            value,
+        } = &injection;
+        inject_value_into_library(&mut self.library, module_name, value_name, value.clone())?;
+        instrument::library_injected(module_name, value_name);
+        self.injections.push(injection);
+        Ok(())
+    }
+
+    /// Make information about the host application available in typst with
+    /// `#import host: env, version, commit, timestamp`. See `HostInfo`.
+    ///
+    /// The `ResultCache` is not used, as `timestamp` (and `datetime`) change with each
+    /// compilation.
+    pub fn with_host_info(mut self, host_info: HostInfo) -> Result<Self, TypstAsLibError> {
+        self.with_host_info_mut(host_info)?;
+        Ok(self)
+    }
+
+    /// Make information about the host application available in typst. See `HostInfo`.
+    pub fn with_host_info_mut(&mut self, host_info: HostInfo) -> Result<(), TypstAsLibError> {
+        for (value_name, value) in host_info.static_values() {
+            self.add_injection_mut(HOST_MODULE, value_name, value)?;
+        }
+        self.host_info = Some(host_info);
+        Ok(())
+    }
+
+    /// Inputs, that are merged with the inputs of each compilation, e.g. for branding or
+    /// configuration. How they are merged can be set with
+    /// `TypstTemplateCollection::input_merge()`.
+    pub fn default_inputs<D>(mut self, default_inputs: D) -> Self
+    where
+        D: Into<Dict>,
+    {
+        self.default_inputs_mut(default_inputs);
+        self
+    }
+
+    /// Inputs, that are merged with the inputs of each compilation.
+    /// See `TypstTemplateCollection::default_inputs()`.
+    pub fn default_inputs_mut<D>(&mut self, default_inputs: D)
+    where
+        D: Into<Dict>,
+    {
+        self.default_inputs = default_inputs.into();
+    }
+
+    /// Set how the inputs are merged with the default inputs. Default: `InputMerge::Deep`.
+    pub fn input_merge(mut self, input_merge: InputMerge) -> Self {
+        self.input_merge_mut(input_merge);
+        self
+    }
+
+    /// Set how the inputs are merged with the default inputs. Default: `InputMerge::Deep`.
+    pub fn input_merge_mut(&mut self, input_merge: InputMerge) {
+        self.input_merge = input_merge;
+    }
+
     /// Add Fonts
     pub fn add_fonts<I, F>(mut self, fonts: I) -> Self
     where
@@ -105,11 +360,30 @@ impl TypstTemplateCollection {
         I: IntoIterator<Item = F>,
         F: Into<Font>,
     {
-        let fonts = fonts.into_iter().map(Into::into);
-        self.fonts.extend(fonts);
+        for font in fonts.into_iter().map(Into::into) {
+            self.book.push(font.info().clone());
+            self.fonts.push(font);
+        }
         self
     }
 
+    /// Add fonts or font files, e.g. WOFF2 files with feature `woff`. See `IntoFonts`.
+    pub fn try_add_fonts<F>(mut self, fonts: F) -> Result<Self, TypstAsLibError>
+    where
+        F: IntoFonts,
+    {
+        self.try_add_fonts_mut(fonts)?;
+        Ok(self)
+    }
+
+    /// Add fonts or font files. See `TypstTemplateCollection::try_add_fonts()`.
+    pub fn try_add_fonts_mut<F>(&mut self, fonts: F) -> Result<&mut Self, TypstAsLibError>
+    where
+        F: IntoFonts,
+    {
+        Ok(self.add_fonts_mut(fonts.into_fonts()?))
+    }
+
     /// Add file resolver, that implements the `FileResolver`` trait to a vec of file resolvers.
     /// When a `FileId`` needs to be resolved by Typst, the vec will be iterated over until
     /// one file resolver returns a file.
@@ -131,6 +405,39 @@ impl TypstTemplateCollection {
         self.file_resolvers.push(Box::new(file_resolver));
     }
 
+    /// Set a file resolver, that is consulted for images, that all other file resolvers could
+    /// not find. Substituted files are listed in the `CompilationReport`, instead of failing the
+    /// document. Other errors (e.g. `FileError::AccessDenied`) and other files are not
+    /// substituted.
+    pub fn with_fallback_file_resolver<F>(mut self, file_resolver: F) -> Self
+    where
+        F: FileResolver + Send + Sync + 'static,
+    {
+        self.with_fallback_file_resolver_mut(file_resolver);
+        self
+    }
+
+    /// Set a file resolver, that is consulted for images, that all other file resolvers could
+    /// not find. Substituted files are listed in the `CompilationReport`, instead of failing the
+    /// document. Other errors (e.g. `FileError::AccessDenied`) and other files are not
+    /// substituted.
+    pub fn with_fallback_file_resolver_mut<F>(&mut self, file_resolver: F)
+    where
+        F: FileResolver + Send + Sync + 'static,
+    {
+        self.fallback_file_resolver = Some(Box::new(file_resolver));
+    }
+
+    /// Serve `placeholder` (e.g. a "missing image" PNG) for images, that could not be found.
+    /// See `TypstTemplateCollection::with_fallback_file_resolver()`.
+    pub fn with_placeholder<B>(mut self, placeholder: B) -> Self
+    where
+        B: Into<Bytes>,
+    {
+        self.with_fallback_file_resolver_mut(PlaceholderResolver::new(placeholder));
+        self
+    }
+
     /// Adds the `StaticSourceFileResolver` to the file resolvers. It creates `HashMap`s for sources.
     ///
     /// `sources` The item of the IntoIterator can be of types:
@@ -199,17 +506,109 @@ impl TypstTemplateCollection {
         self.add_file_resolver_mut(FileSystemResolver::new(root.into()).into_cached());
     }
 
+    /// Adds `FileSystemResolver` with multiple roots to the file resolvers. The roots are
+    /// consulted in the given order, so e.g. tenant overrides can be put before shared partials.
+    pub fn with_file_system_roots<I, P>(mut self, roots: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.with_file_system_roots_mut(roots);
+        self
+    }
+
+    /// Adds `FileSystemResolver` with multiple roots to the file resolvers. The roots are
+    /// consulted in the given order, so e.g. tenant overrides can be put before shared partials.
+    pub fn with_file_system_roots_mut<I, P>(&mut self, roots: I)
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.add_file_resolver_mut(FileSystemResolver::new_with_roots(roots).into_cached());
+    }
+
+    #[deprecated(
+        since = "0.12.0",
+        note = "Use TypstTemplateCollection::eviction_strategy() instead!"
+    )]
     pub fn comemo_evict_max_age(&mut self, comemo_evict_max_age: Option<usize>) -> &mut Self {
-        self.comemo_evict_max_age = comemo_evict_max_age;
+        self.eviction_strategy = comemo_evict_max_age.into();
+        self
+    }
+
+    /// Set when `comemo::evict()` is called after a compilation.
+    /// Default: `EvictionStrategy::AfterEveryCompile { max_age: 0 }`.
+    pub fn eviction_strategy(&mut self, eviction_strategy: EvictionStrategy) -> &mut Self {
+        self.eviction_strategy = eviction_strategy;
+        self
+    }
+
+    /// Register a template by a logical name, so it can be compiled with
+    /// `TypstTemplateCollection::compile_template()`.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst::foundations::Dict;
+    /// # use typst_as_lib::registry::RegisteredTemplate;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # static INVOICE: &str = "= Invoice";
+    /// # let inputs = Dict::new();
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// let template_collection = TypstTemplateCollection::new(vec![font])
+    ///     .with_static_source_file_resolver([("/invoice.typ", INVOICE)])
+    ///     .register_template("invoice", RegisteredTemplate::new("/invoice.typ"));
+    /// let doc = template_collection.compile_template("invoice", inputs);
+    /// ```
+    pub fn register_template<S>(mut self, name: S, template: RegisteredTemplate) -> Self
+    where
+        S: Into<String>,
+    {
+        self.register_template_mut(name, template);
+        self
+    }
+
+    /// Register a template by a logical name, so it can be compiled with
+    /// `TypstTemplateCollection::compile_template()`.
+    pub fn register_template_mut<S>(&mut self, name: S, template: RegisteredTemplate)
+    where
+        S: Into<String>,
+    {
+        self.registry.register(name, template);
+    }
+
+    pub fn registry(&self) -> &TemplateRegistry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut TemplateRegistry {
+        &mut self.registry
+    }
+
+    /// Cache up to `capacity` compiled documents. When a template is compiled again with the
+    /// same inputs and none of the files, it read, changed, the cached document is returned.
+    /// See `ResultCache`.
+    pub fn with_result_cache(mut self, capacity: usize) -> Self {
+        self.with_result_cache_mut(capacity);
         self
     }
 
+    /// Cache up to `capacity` compiled documents. See `ResultCache`.
+    pub fn with_result_cache_mut(&mut self, capacity: usize) {
+        self.result_cache = Some(ResultCache::new(capacity));
+    }
+
+    pub fn result_cache(&self) -> Option<&ResultCache> {
+        self.result_cache.as_ref()
+    }
+
     #[cfg(feature = "packages")]
     /// Adds `PackageResolver` to the file resolvers.
     /// When `package` is set in `FileId`, it will download the package from the typst package
     /// repository. It caches the results into `cache` (which is either in memory or cache folder (default)).
     /// Example
     /// ```rust
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
     ///     let template = TypstTemplateCollection::new(vec![font])
     ///         .with_package_file_resolver(None);
     /// ```
@@ -234,17 +633,21 @@ impl TypstTemplateCollection {
     /// Example:
     ///
     /// ```rust
-    /// static TEMPLATE: &str = include_str!("./templates/template.typ");
-    /// static FONT: &[u8] = include_bytes!("./fonts/texgyrecursor-regular.otf");
+    /// # use typst::foundations::{dict, Array, Bytes};
+    /// # use typst::text::Font;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+    /// static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
     /// static TEMPLATE_ID: &str = "/template.typ";
     /// // ...
     /// let font = Font::new(Bytes::from(FONT), 0).expect("Could not parse font!");
     /// let template_collection = TypstTemplateCollection::new(vec![font])
-    ///     .add_static_file_resolver([(TEMPLATE_ID, TEMPLATE)]);
-    /// // Struct that implements Into<Dict>.
-    /// let inputs = todo!();
-    /// let tracer = Default::default();
-    /// let doc = template_collection.compile_with_input(&mut tracer, TEMPLATE_ID, inputs)
+    ///     .with_static_source_file_resolver([(TEMPLATE_ID, TEMPLATE)]);
+    /// // Or a struct that implements Into<Dict>.
+    /// let inputs = dict! { "v" => Array::new() };
+    /// let doc = template_collection
+    ///     .compile_with_input(TEMPLATE_ID, inputs)
+    ///     .output
     ///     .expect("Typst error!");
     /// ```
     pub fn compile_with_input<F, D>(
@@ -256,7 +659,8 @@ impl TypstTemplateCollection {
         F: Into<FileIdNewType>,
         D: Into<Dict>,
     {
-        self.compile_helper(main_source_id, Some(input))
+        self.compile_helper(main_source_id, Some(input), None)
+            .output
     }
 
     /// Call `typst::compile()` with our template and a `Dict` as input, that will be availible
@@ -265,17 +669,22 @@ impl TypstTemplateCollection {
     /// Example:
     ///
     /// ```rust
-    /// static TEMPLATE: &str = include_str!("./templates/template.typ");
-    /// static FONT: &[u8] = include_bytes!("./fonts/texgyrecursor-regular.otf");
+    /// # #![allow(deprecated)]
+    /// # use typst::foundations::{dict, Array, Bytes};
+    /// # use typst::text::Font;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+    /// static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
     /// static TEMPLATE_ID: &str = "/template.typ";
     /// // ...
     /// let font = Font::new(Bytes::from(FONT), 0).expect("Could not parse font!");
-    /// let template_collection = TypstTemplateCollection::new(vec![font])
-    ///     .add_static_file_resolver([(TEMPLATE_ID, TEMPLATE)]);
-    /// // Struct that implements Into<Dict>.
-    /// let inputs = todo!();
-    /// let tracer = Default::default();
-    /// let doc = template_collection.compile_with_input_fast(&mut tracer, TEMPLATE_ID, inputs)
+    /// let mut template_collection = TypstTemplateCollection::new(vec![font])
+    ///     .with_static_source_file_resolver([(TEMPLATE_ID, TEMPLATE)]);
+    /// // Or a struct that implements Into<Dict>.
+    /// let inputs = dict! { "v" => Array::new() };
+    /// let doc = template_collection
+    ///     .compile_with_input_fast(TEMPLATE_ID, inputs)
+    ///     .output
     ///     .expect("Typst error!");
     /// ```
     #[deprecated(
@@ -312,10 +721,20 @@ impl TypstTemplateCollection {
         let world = TypstWorld {
             collection,
             main_source_id,
+            preamble: collection.preamble_source(main_source_id),
             library: Cow::Borrowed(&collection.library),
-            now: Utc::now(),
+            now: collection.now(),
+            source_overrides: None,
+            tenant: None,
+            recorder: Default::default(),
+            dependencies: None,
         };
-        let Warned { output, warnings } = typst::compile(&world);
+        let Warned {
+            output,
+            mut warnings,
+        } = typst::compile(&world);
+        let (report, _) = world.into_report_and_dependencies();
+        warnings.extend(report.decoded_source_warnings());
 
         Warned {
             output: output.map_err(Into::into),
@@ -328,82 +747,529 @@ impl TypstTemplateCollection {
     where
         F: Into<FileIdNewType>,
     {
-        self.compile_helper::<_, Dict>(main_source_id, None)
+        self.compile_helper::<_, Dict>(main_source_id, None, None)
+            .output
     }
 
-    fn compile_helper<F, D>(
+    /// Like `TypstTemplateCollection::compile_with_input()`, but also returns a
+    /// `CompilationReport`, e.g. with the fonts, that were missing.
+    pub fn compile_with_input_and_report<F, D>(
         &self,
         main_source_id: F,
-        inputs: Option<D>,
+        input: D,
+    ) -> Reported<Warned<Result<Document, TypstAsLibError>>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        self.compile_helper(main_source_id, Some(input), None)
+    }
+
+    /// Like `TypstTemplateCollection::compile()`, but also returns a
+    /// `CompilationReport`, e.g. with the fonts, that were missing.
+    pub fn compile_with_report<F>(
+        &self,
+        main_source_id: F,
+    ) -> Reported<Warned<Result<Document, TypstAsLibError>>>
+    where
+        F: Into<FileIdNewType>,
+    {
+        self.compile_helper::<_, Dict>(main_source_id, None, None)
+    }
+
+    /// Compile `source` as main file, without adding it to a file resolver first, e.g. for
+    /// snippets, that users enter. It can import the files of the file resolvers. Relative
+    /// paths are resolved from the root. The result is never cached.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst::foundations::Dict;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # static STYLE: &str = "#let card(body) = block(stroke: 1pt, inset: 8pt, body)";
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([("/style.typ", STYLE)]);
+    /// # let inputs = Dict::new();
+    /// let doc = template_collection
+    ///     .compile_source("#import \"/style.typ\": card\n#card[Hello]", inputs)
+    ///     .output
+    ///     .output?;
+    /// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+    /// ```
+    pub fn compile_source<S, D>(
+        &self,
+        source: S,
+        input: D,
+    ) -> Reported<Warned<Result<Document, TypstAsLibError>>>
+    where
+        S: Into<String>,
+        D: Into<Dict>,
+    {
+        let id = FileId::new(None, VirtualPath::new(DETACHED_SOURCE_PATH));
+        let source_overrides = HashMap::from([(id, Source::new(id, source.into()))]);
+        self.compile_helper(id, Some(input), Some(&source_overrides))
+    }
+
+    /// Compile the template, that was registered with the name, with a `Dict` as input.
+    /// The input is validated first, if the template has an `InputValidator`.
+    pub fn compile_template<D>(
+        &self,
+        name: &str,
+        input: D,
     ) -> Warned<Result<Document, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        self.compile_template_with_report(name, input).output
+    }
+
+    /// Like `TypstTemplateCollection::compile_template()`, but also returns a
+    /// `CompilationReport`, e.g. with the fonts, that were missing.
+    pub fn compile_template_with_report<D>(
+        &self,
+        name: &str,
+        input: D,
+    ) -> Reported<Warned<Result<Document, TypstAsLibError>>>
+    where
+        D: Into<Dict>,
+    {
+        let Some(template) = self.registry.get(name) else {
+            return Reported {
+                output: Warned {
+                    output: Err(TypstAsLibError::UnknownTemplate(name.to_owned())),
+                    warnings: Default::default(),
+                },
+                report: Default::default(),
+            };
+        };
+        let input = match self.validate_input(template.input_validator(), input.into()) {
+            Ok(input) => input,
+            Err(err) => {
+                return Reported {
+                    output: Warned {
+                        output: Err(err),
+                        warnings: Default::default(),
+                    },
+                    report: Default::default(),
+                };
+            }
+        };
+        self.compile_helper(template.main_source_id(), Some(input), None)
+    }
+
+    pub(crate) fn compile_helper<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: Option<D>,
+        source_overrides: Option<&HashMap<FileId, Source>>,
+    ) -> Reported<Warned<Result<Document, TypstAsLibError>>>
     where
         F: Into<FileIdNewType>,
         D: Into<Dict>,
     {
-        let FileIdNewType(main_source_id) = main_source_id.into();
-        let world = TypstWorld {
-            collection: self,
+        self.compile_tracked(
             main_source_id,
-            library: if let Some(inputs) = inputs {
-                let lib = self.create_injected_library(inputs);
-                match lib {
-                    Ok(lib) => Cow::Owned(lib),
-                    Err(err) => {
-                        return Warned {
+            inputs,
+            source_overrides,
+            None,
+            false,
+            self.now(),
+        )
+        .0
+    }
+
+    /// Also returns the files, that were read during the compilation, if the result cache is
+    /// enabled or `track_dependencies` is set. `now` is the current time of the `World`.
+    pub(crate) fn compile_tracked<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: Option<D>,
+        source_overrides: Option<&HashMap<FileId, Source>>,
+        tenant: Option<&str>,
+        track_dependencies: bool,
+        now: DateTime<Utc>,
+    ) -> (ReportedCompilation, Option<Vec<Dependency>>)
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let FileIdNewType(main_source_id) = main_source_id.into();
+        let _span = instrument::compile_span(main_source_id);
+        let stats_before = self.file_resolver_stats();
+
+        let inputs = self.merge_default_inputs(inputs.map(Into::into));
+        // Sources of a session are not part of the key, so their results are not cached.
+        // Neither are results with the current time of `HostInfo`.
+        let result_cache_key = self
+            .result_cache
+            .as_ref()
+            .filter(|_| source_overrides.is_none() && self.host_info.is_none())
+            .map(|_| {
+                hash128(&(
+                    main_source_id,
+                    tenant,
+                    &inputs,
+                    &self.inject_location,
+                    &self.injections,
+                    &self.book,
+                    &self.preamble,
+                ))
+            });
+        if let Some(CachedResult {
+            document,
+            warnings,
+            dependencies,
+        }) = result_cache_key.and_then(|key| self.cached_result(key, now))
+        {
+            let reported = Reported {
+                output: Warned {
+                    output: Ok(document),
+                    warnings,
+                },
+                report: CompilationReport {
+                    from_result_cache: true,
+                    ..Default::default()
+                },
+            };
+            return (reported, Some(dependencies));
+        }
+
+        let start = Instant::now();
+        let library = if inputs.is_some() || self.host_info.is_some() {
+            let lib = self.create_injected_library(inputs, now);
+            match lib {
+                Ok(lib) => Cow::Owned(lib),
+                Err(err) => {
+                    let reported = Reported {
+                        output: Warned {
                             output: Err(err),
                             warnings: Default::default(),
-                        };
-                    }
+                        },
+                        report: Default::default(),
+                    };
+                    return (reported, None);
                 }
-            } else {
-                Cow::Borrowed(&self.library)
-            },
-            now: Utc::now(),
+            }
+        } else {
+            Cow::Borrowed(&self.library)
         };
-        let Warned { output, warnings } = typst::compile(&world);
+        let library_injection = start.elapsed();
 
-        if let Some(comemo_evict_max_age) = self.comemo_evict_max_age {
-            comemo::evict(comemo_evict_max_age);
+        let world = TypstWorld {
+            collection: self,
+            main_source_id,
+            preamble: self.preamble_source(main_source_id),
+            library,
+            now,
+            source_overrides,
+            tenant,
+            recorder: Default::default(),
+            dependencies: (result_cache_key.is_some() || track_dependencies).then(Default::default),
+        };
+        let start = Instant::now();
+        let Warned {
+            output,
+            mut warnings,
+        } = typst::compile(&world);
+        let compile = start.elapsed();
+
+        let start = Instant::now();
+        self.eviction_strategy
+            .evict_after_compile(&self.compile_count);
+        let eviction = start.elapsed();
+        instrument::compile_finished(main_source_id, output.is_ok(), warnings.len(), compile);
+
+        let (mut report, dependencies) = world.into_report_and_dependencies();
+        report.timings.library_injection = library_injection;
+        report.timings.compile = compile;
+        report.timings.eviction = eviction;
+        report.record_file_resolver_stats(self.file_resolver_stats().saturating_sub(stats_before));
+        report.collect_unknown_font_families(&warnings);
+        warnings.extend(report.decoded_source_warnings());
+        if let Ok(document) = &output {
+            report.collect_missing_glyphs(document);
         }
 
-        Warned {
-            output: output.map_err(Into::into),
-            warnings,
+        if let (Some(key), Some(dependencies), Some(result_cache), Ok(document)) =
+            (result_cache_key, &dependencies, &self.result_cache, &output)
+        {
+            result_cache.insert(
+                key,
+                CachedResult {
+                    dependencies: dependencies.clone(),
+                    document: document.clone(),
+                    warnings: warnings.clone(),
+                },
+            );
+        }
+
+        let reported = Reported {
+            output: Warned {
+                output: output.map_err(Into::into),
+                warnings,
+            },
+            report,
+        };
+        (reported, dependencies)
+    }
+
+    /// Approximate memory held by fonts and file resolvers (including their caches), e.g. for
+    /// monitoring. Also contains the resident memory of the process, where available.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let Self {
+            fonts,
+            file_resolvers,
+            fallback_file_resolver,
+            ..
+        } = self;
+        MemoryFootprint {
+            fonts: fonts.iter().map(|font| font.data().len()).sum(),
+            file_resolvers: file_resolvers
+                .iter()
+                .chain(fallback_file_resolver)
+                .map(|file_resolver| {
+                    (
+                        file_resolver.name().into_owned(),
+                        file_resolver.memory_footprint(),
+                    )
+                })
+                .collect(),
+            process_resident: eviction::resident_memory(),
         }
     }
 
-    fn create_injected_library<D>(&self, input: D) -> Result<LazyHash<Library>, TypstAsLibError>
+    /// Remove the file from the caches of all file resolvers, e.g. because it changed on disk.
+    pub fn invalidate<F>(&self, file_id: F)
     where
-        D: Into<Dict>,
+        F: Into<FileIdNewType>,
     {
+        let FileIdNewType(file_id) = file_id.into();
+        let Self {
+            file_resolvers,
+            fallback_file_resolver,
+            ..
+        } = self;
+        for file_resolver in file_resolvers.iter().chain(fallback_file_resolver) {
+            file_resolver.invalidate(file_id);
+        }
+    }
+
+    fn file_resolver_stats(&self) -> FileResolverStats {
+        let Self {
+            file_resolvers,
+            fallback_file_resolver,
+            ..
+        } = self;
+        file_resolvers
+            .iter()
+            .chain(fallback_file_resolver)
+            .map(|file_resolver| file_resolver.stats())
+            .fold(Default::default(), |sum, stats| sum + stats)
+    }
+
+    /// Merges the default inputs into the inputs and validates the result. Merging again is a
+    /// no-op, so the result can be passed to `compile_helper()`.
+    pub(crate) fn validate_input(
+        &self,
+        input_validator: Option<&(dyn InputValidator + Send + Sync)>,
+        input: Dict,
+    ) -> Result<Dict, TypstAsLibError> {
+        let Some(input_validator) = input_validator else {
+            return Ok(input);
+        };
+        let input = self.merge_default_inputs(Some(input)).unwrap_or_default();
+        input_validator.validate(&input)?;
+        Ok(input)
+    }
+
+    fn merge_default_inputs(&self, inputs: Option<Dict>) -> Option<Dict> {
+        let Self {
+            default_inputs,
+            input_merge,
+            ..
+        } = self;
+        if default_inputs.is_empty() {
+            return inputs;
+        }
+        match inputs {
+            Some(inputs) => Some(input_merge.merge(default_inputs, inputs)),
+            None => Some(default_inputs.clone()),
+        }
+    }
+
+    fn create_injected_library(
+        &self,
+        input: Option<Dict>,
+        now: DateTime<Utc>,
+    ) -> Result<LazyHash<Library>, TypstAsLibError> {
         let Self {
             inject_location,
             library,
+            host_info,
             ..
         } = self;
         let mut lib = library.deref().clone();
-        inject_input_into_library(&mut lib, inject_location.as_ref(), input)?;
+        if let Some(input) = input {
+            inject_input_into_library(&mut lib, inject_location.as_ref(), input)?;
+        }
+        if let Some(host_info) = host_info {
+            for (value_name, value) in host_info.dynamic_values(now) {
+                inject_value_into_library(&mut lib, HOST_MODULE, value_name, value)?;
+            }
+        }
         Ok(LazyHash::new(lib))
     }
 
-    fn resolve_file(&self, file_id: FileId) -> FileResult<Cow<Bytes>> {
-        let TypstTemplateCollection { file_resolvers, .. } = self;
-        let mut last_error = not_found(file_id);
-        for file_resolver in file_resolvers {
-            match file_resolver.resolve_binary(file_id) {
-                Ok(source) => return Ok(source),
-                Err(error) => last_error = error,
-            }
+    /// Resolve the source with the file resolvers, like a compilation would, e.g. to show the
+    /// exact template text, that is used, in an audit log or editor.
+    pub fn source<F>(&self, file_id: F) -> Result<Source, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(id) = file_id.into();
+        let source = self.resolve_source(id, |_| ())?;
+        Ok(source.into_owned())
+    }
+
+    /// Resolve the binary with the file resolvers, like a compilation would. The fallback file
+    /// resolver is not used.
+    pub fn file<F>(&self, file_id: F) -> Result<Bytes, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(id) = file_id.into();
+        let bytes = self.resolve_file(id, |_| ())?;
+        Ok(bytes.into_owned())
+    }
+
+    /// Ask every file resolver for the file (as source and as binary) and report, which one
+    /// is used and which ones are shadowed. Note, that e.g. the `PackageResolver` downloads
+    /// packages for this.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # static HEADER: &str = "= Header";
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([("/partials/header.typ", HEADER)]);
+    /// println!("{}", template_collection.explain_resolution("/partials/header.typ"));
+    /// ```
+    pub fn explain_resolution<F>(&self, file_id: F) -> ResolutionExplanation
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(id) = file_id.into();
+        let candidates = self
+            .file_resolvers
+            .iter()
+            .map(|file_resolver| ResolutionCandidate {
+                resolver: file_resolver.name().into_owned(),
+                source: file_resolver.resolve_source(id).map(|_| ()),
+                binary: file_resolver.resolve_binary(id).map(|_| ()),
+            })
+            .collect();
+        ResolutionExplanation { id, candidates }
+    }
+
+    fn resolve_file<R>(&self, file_id: FileId, on_attempt: R) -> FileResult<Cow<'_, Bytes>>
+    where
+        R: FnMut(&ResolveAttempt),
+    {
+        let mut on_attempt = on_attempt;
+        let resolved = self.resolve_with(
+            file_id,
+            ResolveKind::Binary,
+            &mut on_attempt,
+            |file_resolver| file_resolver.resolve_binary(file_id),
+        );
+        match (resolved, self.normalized_file_id(file_id)) {
+            (Err(error), Some(normalized)) => self
+                .resolve_with(
+                    normalized,
+                    ResolveKind::Binary,
+                    on_attempt,
+                    |file_resolver| file_resolver.resolve_binary(normalized),
+                )
+                .map_err(|_| error),
+            (resolved, _) => resolved,
         }
-        Err(last_error)
     }
 
-    fn resolve_source(&self, file_id: FileId) -> FileResult<Cow<Source>> {
+    pub(crate) fn resolve_source<R>(
+        &self,
+        file_id: FileId,
+        on_attempt: R,
+    ) -> FileResult<Cow<'_, Source>>
+    where
+        R: FnMut(&ResolveAttempt),
+    {
+        let mut on_attempt = on_attempt;
+        let resolved = self.resolve_with(
+            file_id,
+            ResolveKind::Source,
+            &mut on_attempt,
+            |file_resolver| file_resolver.resolve_source(file_id),
+        );
+        let resolved = match (resolved, self.normalized_file_id(file_id)) {
+            (Err(error), Some(normalized)) => {
+                let source = self
+                    .resolve_with(
+                        normalized,
+                        ResolveKind::Source,
+                        on_attempt,
+                        |file_resolver| file_resolver.resolve_source(normalized),
+                    )
+                    .map_err(|_| error)?;
+                // Typst expects the source under the requested id.
+                Ok(Cow::Owned(Source::new(file_id, source.text().to_owned())))
+            }
+            (resolved, _) => resolved,
+        };
+        resolved.map(|source| self.normalize_source(source))
+    }
+
+    /// See `FileResolver::source_encoding()`.
+    fn source_encoding(&self, file_id: FileId) -> Option<&'static str> {
+        let TypstTemplateCollection { file_resolvers, .. } = self;
+        std::iter::once(file_id)
+            .chain(self.normalized_file_id(file_id))
+            .find_map(|id| {
+                file_resolvers
+                    .iter()
+                    .find_map(|file_resolver| file_resolver.source_encoding(id))
+            })
+    }
+
+    fn resolve_with<'a, T, R, F>(
+        &'a self,
+        file_id: FileId,
+        kind: ResolveKind,
+        mut on_attempt: R,
+        resolve: F,
+    ) -> FileResult<T>
+    where
+        R: FnMut(&ResolveAttempt),
+        F: Fn(&'a (dyn FileResolver + Send + Sync + 'static)) -> FileResult<T>,
+    {
         let TypstTemplateCollection { file_resolvers, .. } = self;
+        let _span = instrument::resolve_span(file_id, kind);
         let mut last_error = not_found(file_id);
         for file_resolver in file_resolvers {
-            match file_resolver.resolve_source(file_id) {
-                Ok(source) => return Ok(source),
+            let start = Instant::now();
+            let resolved = resolve(file_resolver.as_ref());
+            let name = file_resolver.name();
+            let attempt = ResolveAttempt {
+                resolver: &name,
+                id: file_id,
+                kind,
+                duration: start.elapsed(),
+                result: resolved.as_ref().map(|_| ()),
+            };
+            instrument::file_resolved(&attempt);
+            on_attempt(&attempt);
+            match resolved {
+                Ok(resolved) => return Ok(resolved),
                 Err(error) => last_error = error,
             }
         }
@@ -428,26 +1294,44 @@ where
     } else {
         ("sys", "inputs")
     };
+    inject_value_into_library(library, module_name, value_name, input.into().into_value())?;
+    instrument::library_injected(module_name, value_name);
+    Ok(library)
+}
+
+/// Defines the value in the module. Other definitions in the module are kept.
+fn inject_value_into_library(
+    library: &mut Library,
+    module_name: &str,
+    value_name: &str,
+    value: Value,
+) -> Result<(), TypstAsLibError> {
     let global = library.global.scope_mut();
-    let mut scope = Scope::new();
-    scope.define(value_name, input.into());
-    if let Some(value) = global.get_mut(module_name).transpose()? {
-        if let Value::Module(module) = value {
-            *module.scope_mut() = scope;
-        } else {
-            let module = Module::new(module_name, scope);
-            *value = Value::Module(module);
+    let new_module = |value| {
+        let mut scope = Scope::new();
+        scope.define(value_name, value);
+        Value::Module(Module::new(module_name, scope))
+    };
+    match global.get_mut(module_name).transpose()? {
+        Some(Value::Module(module)) => {
+            let scope = module.scope_mut();
+            // Redefining panics in debug builds, e.g. for `sys.inputs`.
+            match scope.get_mut(value_name).transpose()? {
+                Some(existing) => *existing = value,
+                None => scope.define(value_name, value),
+            }
         }
-    } else {
-        let module = Module::new(module_name, scope);
-        global.define_module(module);
+        Some(existing) => *existing = new_module(value),
+        None => global.define(module_name, new_module(value)),
     }
-    Ok(library)
+    Ok(())
 }
 
 pub struct TypstTemplate {
     source_id: FileId,
     collection: TypstTemplateCollection,
+    main_source: Option<Arc<RwLock<Source>>>,
+    input_validator: Option<Box<dyn InputValidator + Send + Sync + 'static>>,
 }
 
 impl TypstTemplate {
@@ -464,8 +1348,11 @@ impl TypstTemplate {
     ///
     /// Example:
     /// ```rust
-    /// static TEMPLATE: &str = include_str!("./templates/template.typ");
-    /// static FONT: &[u8] = include_bytes!("./fonts/texgyrecursor-regular.otf");
+    /// # use typst::foundations::Bytes;
+    /// # use typst::text::Font;
+    /// # use typst_as_lib::TypstTemplate;
+    /// static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+    /// static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
     /// // ...
     /// let font = Font::new(Bytes::from(FONT), 0).expect("Could not parse font!");
     /// let template = TypstTemplate::new(vec![font], TEMPLATE);
@@ -477,21 +1364,111 @@ impl TypstTemplate {
     {
         let SourceNewType(source) = source_id.into();
         let source_id = source.id();
+        let main_source = Arc::new(RwLock::new(source));
         let mut collection = TypstTemplateCollection::new(fonts);
         collection
             .file_resolvers
-            .push(Box::new(MainSourceFileResolver::new(source)));
+            .push(Box::new(MainSourceFileResolver::new(main_source.clone())));
         Self {
             collection,
             source_id,
+            main_source: Some(main_source),
+            input_validator: None,
         }
     }
 
+    /// Replace the main source file, while keeping fonts, file resolvers and their caches.
+    ///
+    /// `source` can be of the same types as in `TypstTemplate::new()`.
+    pub fn set_main_file<S>(&mut self, source: S)
+    where
+        S: Into<SourceNewType>,
+    {
+        let SourceNewType(source) = source.into();
+        self.source_id = source.id();
+        if let Some(main_source) = &self.main_source {
+            let mut main_source = main_source
+                .write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            *main_source = source;
+            return;
+        }
+        let main_source = Arc::new(RwLock::new(source));
+        self.collection.file_resolvers.insert(
+            0,
+            Box::new(MainSourceFileResolver::new(main_source.clone())),
+        );
+        self.main_source = Some(main_source);
+    }
+
+    /// Compile another main source file ad hoc, with the fonts and file resolvers of this
+    /// template. The main source file of the template stays the same.
+    ///
+    /// `source` can be of the same types as in `TypstTemplate::new()`.
+    pub fn compile_with_main<S, D>(
+        &self,
+        source: S,
+        inputs: D,
+    ) -> Warned<Result<Document, TypstAsLibError>>
+    where
+        S: Into<SourceNewType>,
+        D: Into<Dict>,
+    {
+        let SourceNewType(source) = source.into();
+        let main_source_id = source.id();
+        let sources = HashMap::from([(main_source_id, source)]);
+        self.collection
+            .compile_helper(main_source_id, Some(inputs), Some(&sources))
+            .output
+    }
+
+    #[deprecated(
+        since = "0.12.0",
+        note = "Use TypstTemplate::eviction_strategy() instead!"
+    )]
     pub fn comemo_evict_max_age(&mut self, comemo_evict_max_age: Option<usize>) -> &mut Self {
-        self.collection.comemo_evict_max_age = comemo_evict_max_age;
+        self.collection.eviction_strategy = comemo_evict_max_age.into();
+        self
+    }
+
+    /// Set when `comemo::evict()` is called after a compilation.
+    /// Default: `EvictionStrategy::AfterEveryCompile { max_age: 0 }`.
+    pub fn eviction_strategy(&mut self, eviction_strategy: EvictionStrategy) -> &mut Self {
+        self.collection.eviction_strategy = eviction_strategy;
         self
     }
 
+    /// Approximate memory held by fonts and file resolvers (including their caches), e.g. for
+    /// monitoring. Also contains the resident memory of the process, where available.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        self.collection.memory_footprint()
+    }
+
+    /// Resolve the source with the file resolvers. See `TypstTemplateCollection::source()`.
+    pub fn source<F>(&self, file_id: F) -> Result<Source, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        self.collection.source(file_id)
+    }
+
+    /// Resolve the binary with the file resolvers. See `TypstTemplateCollection::file()`.
+    pub fn file<F>(&self, file_id: F) -> Result<Bytes, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        self.collection.file(file_id)
+    }
+
+    /// Report, which file resolver serves the file and which ones are shadowed.
+    /// See `TypstTemplateCollection::explain_resolution()`.
+    pub fn explain_resolution<F>(&self, file_id: F) -> ResolutionExplanation
+    where
+        F: Into<FileIdNewType>,
+    {
+        self.collection.explain_resolution(file_id)
+    }
+
     /// Use other typst location for injected inputs
     /// (instead of`#import sys: inputs`, where `sys` is the `module_name`
     /// and `inputs` is the `value_name`).
@@ -504,6 +1481,62 @@ impl TypstTemplate {
         self
     }
 
+    /// Make a value available in typst under `#import module_name: value_name`.
+    /// See `TypstTemplateCollection::add_injection()`.
+    pub fn add_injection<S, V>(
+        mut self,
+        module_name: S,
+        value_name: S,
+        value: V,
+    ) -> Result<Self, TypstAsLibError>
+    where
+        S: Into<String>,
+        V: IntoValue,
+    {
+        self.collection
+            .add_injection_mut(module_name, value_name, value)?;
+        Ok(self)
+    }
+
+    /// Make information about the host application available in typst.
+    /// See `TypstTemplateCollection::with_host_info()`.
+    pub fn with_host_info(mut self, host_info: HostInfo) -> Result<Self, TypstAsLibError> {
+        self.collection.with_host_info_mut(host_info)?;
+        Ok(self)
+    }
+
+    /// Check the inputs against the schema (after merging the default inputs), before
+    /// compiling with `TypstTemplate::compile_with_input()`. See `InputSchema`.
+    pub fn with_input_schema(self, input_schema: InputSchema) -> Self {
+        self.with_input_validator(input_schema)
+    }
+
+    /// Validate the inputs (after merging the default inputs), before compiling with
+    /// `TypstTemplate::compile_with_input()`.
+    pub fn with_input_validator<V>(mut self, input_validator: V) -> Self
+    where
+        V: InputValidator + Send + Sync + 'static,
+    {
+        self.input_validator = Some(Box::new(input_validator));
+        self
+    }
+
+    /// Inputs, that are merged with the inputs of each compilation.
+    /// See `TypstTemplateCollection::default_inputs()`.
+    pub fn default_inputs<D>(mut self, default_inputs: D) -> Self
+    where
+        D: Into<Dict>,
+    {
+        self.collection.default_inputs_mut(default_inputs);
+        self
+    }
+
+    /// Set how the inputs are merged with the default inputs. Default: `InputMerge::Deep`.
+    pub fn input_merge(mut self, input_merge: InputMerge) -> Self {
+        self.collection.input_merge_mut(input_merge);
+        self
+    }
+
     /// Add Fonts
     pub fn add_fonts<I, F>(mut self, fonts: I) -> Self
     where
@@ -514,6 +1547,15 @@ impl TypstTemplate {
         self
     }
 
+    /// Add fonts or font files. See `TypstTemplateCollection::try_add_fonts()`.
+    pub fn try_add_fonts<F>(mut self, fonts: F) -> Result<Self, TypstAsLibError>
+    where
+        F: IntoFonts,
+    {
+        self.collection.try_add_fonts_mut(fonts)?;
+        Ok(self)
+    }
+
     /// Add file resolver, that implements the `FileResolver`` trait to a vec of file resolvers.
     /// When a `FileId`` needs to be resolved by Typst, the vec will be iterated over until
     /// one file resolver returns a file.
@@ -525,6 +1567,30 @@ impl TypstTemplate {
         self
     }
 
+    /// Set a file resolver, that is consulted for images, that all other file resolvers could
+    /// not find. Substituted files are listed in the `CompilationReport`, instead of failing the
+    /// document. Other errors (e.g. `FileError::AccessDenied`) and other files are not
+    /// substituted.
+    pub fn with_fallback_file_resolver<F>(mut self, file_resolver: F) -> Self
+    where
+        F: FileResolver + Send + Sync + 'static,
+    {
+        self.collection
+            .with_fallback_file_resolver_mut(file_resolver);
+        self
+    }
+
+    /// Serve `placeholder` (e.g. a "missing image" PNG) for images, that could not be found.
+    /// See `TypstTemplate::with_fallback_file_resolver()`.
+    pub fn with_placeholder<B>(mut self, placeholder: B) -> Self
+    where
+        B: Into<Bytes>,
+    {
+        self.collection
+            .with_fallback_file_resolver_mut(PlaceholderResolver::new(placeholder));
+        self
+    }
+
     /// Adds the `StaticFileResolver` to the file resolvers. It creates `HashMap`s for sources.
     ///
     /// `sources` The item of the IntoIterator can be of types:
@@ -566,12 +1632,26 @@ impl TypstTemplate {
         self
     }
 
+    /// Adds `FileSystemResolver` with multiple roots to the file resolvers. The roots are
+    /// consulted in the given order, so e.g. tenant overrides can be put before shared partials.
+    pub fn with_file_system_roots<I, P>(mut self, roots: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.collection.with_file_system_roots_mut(roots);
+        self
+    }
+
     #[cfg(feature = "packages")]
     /// Adds `PackageResolver` to the file resolvers.
     /// When `package` is set in `FileId`, it will download the package from the typst package
     /// repository. It caches the results into `cache` (which is either in memory or cache folder (default)).
     /// Example
     /// ```rust
+    /// # use typst_as_lib::TypstTemplate;
+    /// # static TEMPLATE_FILE: &str = include_str!("../examples/templates/template.typ");
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
     ///     let template = TypstTemplate::new(vec![font], TEMPLATE_FILE)
     ///         .with_package_file_resolver(None);
     /// ```
@@ -586,12 +1666,7 @@ impl TypstTemplate {
     where
         D: Into<Dict>,
     {
-        let Self {
-            source_id,
-            collection,
-            ..
-        } = self;
-        collection.compile_with_input(*source_id, inputs)
+        self.compile_with_input_and_report(inputs).output
     }
 
     /// Call `typst::compile()` with our template and a `Dict` as input, that will be availible
@@ -600,22 +1675,27 @@ impl TypstTemplate {
     /// Example:
     ///
     /// ```rust
-    /// static TEMPLATE: &str = include_str!("./templates/template.typ");
-    /// static FONT: &[u8] = include_bytes!("./fonts/texgyrecursor-regular.otf");
-    /// static TEMPLATE_ID: &str = "/template.typ";
+    /// # #![allow(deprecated)]
+    /// # use typst::foundations::{dict, Array, Bytes};
+    /// # use typst::text::Font;
+    /// # use typst_as_lib::TypstTemplate;
+    /// static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+    /// static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
     /// // ...
     /// let font = Font::new(Bytes::from(FONT), 0).expect("Could not parse font!");
-    /// let template = TypstTemplate::new(vec![font], TEMPLATE);
-    /// // Struct that implements Into<Dict>.
-    /// let inputs = todo!();
-    /// let tracer = Default::default();
-    /// let doc = template.compile_with_input_fast(&mut tracer, TEMPLATE_ID, inputs)
+    /// let mut template = TypstTemplate::new(vec![font], TEMPLATE);
+    /// // Or a struct that implements Into<Dict>.
+    /// let inputs = dict! { "v" => Array::new() };
+    /// let doc = template
+    ///     .compile_with_input_fast(inputs)
+    ///     .output
     ///     .expect("Typst error!");
     /// ```
     #[deprecated(
         since = "0.11.1",
         note = "Use TypstTemplate::compile_with_input() instead!"
     )]
+    #[allow(deprecated)]
     pub fn compile_with_input_fast<D>(
         &mut self,
         input: D,
@@ -631,6 +1711,11 @@ impl TypstTemplate {
         collection.compile_with_input_fast(*source_id, input)
     }
 
+    /// `FileId` of the main source file.
+    pub fn main_source_id(&self) -> FileId {
+        self.source_id
+    }
+
     /// Just call `typst::compile()`
     pub fn compile(&self) -> Warned<Result<Document, TypstAsLibError>> {
         let Self {
@@ -640,13 +1725,136 @@ impl TypstTemplate {
         } = self;
         collection.compile(*source_id)
     }
+
+    /// Like `TypstTemplate::compile_with_input()`, but also returns a
+    /// `CompilationReport`, e.g. with the fonts, that were missing.
+    pub fn compile_with_input_and_report<D>(
+        &self,
+        inputs: D,
+    ) -> Reported<Warned<Result<Document, TypstAsLibError>>>
+    where
+        D: Into<Dict>,
+    {
+        let inputs = match self.validated_input(inputs) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                return Reported {
+                    output: Warned {
+                        output: Err(err),
+                        warnings: Default::default(),
+                    },
+                    report: Default::default(),
+                };
+            }
+        };
+        self.collection
+            .compile_with_input_and_report(self.source_id, inputs)
+    }
+
+    /// Inputs merged with the default inputs, if there is an `InputValidator`.
+    pub(crate) fn validated_input<D>(&self, inputs: D) -> Result<Dict, TypstAsLibError>
+    where
+        D: Into<Dict>,
+    {
+        let Self {
+            collection,
+            input_validator,
+            ..
+        } = self;
+        collection.validate_input(input_validator.as_deref(), inputs.into())
+    }
+
+    /// Like `TypstTemplate::compile()`, but also returns a
+    /// `CompilationReport`, e.g. with the fonts, that were missing.
+    pub fn compile_with_report(&self) -> Reported<Warned<Result<Document, TypstAsLibError>>> {
+        let Self {
+            source_id,
+            collection,
+            ..
+        } = self;
+        collection.compile_with_report(*source_id)
+    }
+
+    /// Compile `source` instead of the main file of the template, with its fonts and file
+    /// resolvers. See `TypstTemplateCollection::compile_source()`.
+    pub fn compile_source<S, D>(
+        &self,
+        source: S,
+        inputs: D,
+    ) -> Reported<Warned<Result<Document, TypstAsLibError>>>
+    where
+        S: Into<String>,
+        D: Into<Dict>,
+    {
+        self.collection.compile_source(source, inputs)
+    }
 }
 
 struct TypstWorld<'a> {
     main_source_id: FileId,
+    /// Generated main file, that includes the main source. See
+    /// `TypstTemplateCollection::with_preamble()`.
+    preamble: Option<Source>,
     collection: &'a TypstTemplateCollection,
     library: Cow<'a, LazyHash<Library>>,
     now: DateTime<Utc>,
+    /// Sources, that take precedence over the file resolvers.
+    source_overrides: Option<&'a HashMap<FileId, Source>>,
+    /// Non-package files are resolved under `/<tenant>/`. See `TenantScopedResolver`.
+    tenant: Option<&'a str>,
+    recorder: Mutex<ReportRecorder>,
+    /// Files read during the compilation. Only collected, if the result gets cached.
+    dependencies: Option<Mutex<Vec<Dependency>>>,
+}
+
+impl TypstWorld<'_> {
+    fn record<R>(&self, record: R)
+    where
+        R: FnOnce(&mut ReportRecorder),
+    {
+        if let Ok(mut recorder) = self.recorder.lock() {
+            record(&mut recorder);
+        }
+    }
+
+    fn record_resolve_attempt(&self, attempt: &ResolveAttempt) {
+        self.record(|recorder| recorder.record_resolve_attempt(attempt));
+    }
+
+    /// The main source file is shared by all tenants, unless it is already under the prefix
+    /// of the tenant.
+    fn scoped(&self, id: FileId) -> FileId {
+        match self.tenant {
+            Some(tenant) if id != self.main_source_id => tenant::scope_file_id(tenant, id),
+            _ => id,
+        }
+    }
+
+    fn source_override(&self, id: FileId) -> Option<&Source> {
+        self.source_overrides
+            .and_then(|source_overrides| source_overrides.get(&id))
+    }
+
+    fn record_dependency<D>(&self, dependency: D)
+    where
+        D: FnOnce() -> Dependency,
+    {
+        if let Some(Ok(mut dependencies)) = self.dependencies.as_ref().map(Mutex::lock) {
+            dependencies.push(dependency());
+        }
+    }
+
+    fn into_report_and_dependencies(self) -> (CompilationReport, Option<Vec<Dependency>>) {
+        let report = self
+            .recorder
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .finish();
+        let dependencies = self
+            .dependencies
+            .and_then(|dependencies| dependencies.into_inner().ok());
+        (report, dependencies)
+    }
 }
 
 impl typst::World for TypstWorld<'_> {
@@ -659,50 +1867,266 @@ impl typst::World for TypstWorld<'_> {
     }
 
     fn main(&self) -> FileId {
-        self.main_source_id.clone()
+        match &self.preamble {
+            Some(preamble) => preamble.id(),
+            None => self.main_source_id,
+        }
     }
 
     fn source(&self, id: FileId) -> FileResult<Source> {
-        self.collection.resolve_source(id).map(|s| s.into_owned())
+        if let Some(preamble) = self.preamble.as_ref().filter(|p| p.id() == id) {
+            return Ok(preamble.clone());
+        }
+        let id = self.scoped(id);
+        if let Some(source) = self.source_override(id) {
+            return Ok(self
+                .collection
+                .normalize_source(Cow::Borrowed(source))
+                .into_owned());
+        }
+        let source = self
+            .collection
+            .resolve_source(id, |attempt| self.record_resolve_attempt(attempt))?;
+        let encoding = self.collection.source_encoding(id);
+        self.record(|recorder| {
+            recorder.record_source_bytes(source.text().len());
+            recorder.record_source(&source);
+            if let Some(encoding) = encoding {
+                recorder
+                    .report
+                    .record_decoded_source(DecodedSource { id, encoding });
+            }
+        });
+        self.record_dependency(|| Dependency::Source(id, hash128(source.text())));
+        Ok(source.into_owned())
     }
 
     fn file(&self, id: FileId) -> FileResult<Bytes> {
-        self.collection.resolve_file(id).map(|b| b.into_owned())
+        if let Some(preamble) = self.preamble.as_ref().filter(|p| p.id() == id) {
+            return Ok(Bytes::from(preamble.text().as_bytes()));
+        }
+        let id = self.scoped(id);
+        if let Some(source) = self.source_override(id) {
+            return Ok(Bytes::from(source.text().as_bytes()));
+        }
+        let resolved = self
+            .collection
+            .resolve_file(id, |attempt| self.record_resolve_attempt(attempt));
+        let error = match resolved {
+            Ok(b) => {
+                self.record(|recorder| recorder.record_file(id));
+                self.record_dependency(|| Dependency::File(id, hash128(b.as_ref())));
+                return Ok(b.into_owned());
+            }
+            Err(error) => error,
+        };
+        // Denied files must stay denied and data files (e.g. CSV) can't be substituted.
+        let substitutable = matches!(error, FileError::NotFound(_)) && is_image(id);
+        let Some(fallback_file_resolver) = self
+            .collection
+            .fallback_file_resolver
+            .as_ref()
+            .filter(|_| substitutable)
+        else {
+            return Err(error);
+        };
+        let Ok(placeholder) = fallback_file_resolver.resolve_binary(id) else {
+            return Err(error);
+        };
+        self.record(|recorder| {
+            recorder
+                .report
+                .record_substituted_file(SubstitutedFile { id, error })
+        });
+        self.record_dependency(|| Dependency::File(id, hash128(placeholder.as_ref())));
+        Ok(placeholder.into_owned())
     }
 
     fn font(&self, id: usize) -> Option<Font> {
-        self.collection.fonts.get(id).cloned()
+        let font = self.collection.fonts.get(id).cloned();
+        instrument::font_loaded(id, font.as_ref());
+        if font.is_none() {
+            self.record(|recorder| {
+                recorder
+                    .report
+                    .record_missing_font(MissingFont::FailedToLoad(id))
+            });
+        }
+        font
     }
 
     fn today(&self, offset: Option<i64>) -> Option<Datetime> {
-        let mut now = self.now;
-        if let Some(offset) = offset {
-            now += Duration::hours(offset);
-        }
-        let date = now.date_naive();
-        let year = date.year();
-        let month = (date.month0() + 1) as u8;
-        let day = (date.day0() + 1) as u8;
-        Datetime::from_ymd(year, month, day)
+        let today = today(self.now, offset);
+        self.record_dependency(|| Dependency::Today(offset, today));
+        today
+    }
+}
+
+pub(crate) fn today(now: DateTime<Utc>, offset: Option<i64>) -> Option<Datetime> {
+    let mut now = now;
+    if let Some(offset) = offset {
+        now += Duration::hours(offset);
     }
+    let date = now.date_naive();
+    let year = date.year();
+    let month = (date.month0() + 1) as u8;
+    let day = (date.day0() + 1) as u8;
+    Datetime::from_ymd(year, month, day)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash)]
 struct InjectLocation {
     module_name: String,
     value_name: String,
 }
 
+#[derive(Debug, Clone, Hash)]
+struct Injection {
+    module_name: String,
+    value_name: String,
+    value: Value,
+}
+
+/// Errors of this crate. Use `TypstAsLibError::code()` and `TypstAsLibError::category()`
+/// for machine-readable error reporting, as new variants can be added.
 #[derive(Debug, Clone, Error)]
+#[non_exhaustive]
 pub enum TypstAsLibError {
-    #[error("Typst source error: {}", 0.to_string())]
+    #[error("Typst source error: {}", format_diagnostics(.0))]
     TypstSource(EcoVec<SourceDiagnostic>),
-    #[error("Typst file error: {}", 0.to_string())]
+    #[error("Typst file error: {0}")]
     TypstFile(#[from] FileError),
     #[error("Source file does not exist in collection: {0:?}")]
     MainSourceFileDoesNotExist(FileId),
-    #[error("Typst hinted String: {}", 0.to_string())]
+    #[error("Typst hinted String: {}", .0.message())]
     HintedString(HintedString),
+    #[error("Could not load font: {0}")]
+    InvalidFont(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Could not watch files: {0}")]
+    Watch(String),
+    #[error("Invalid edit of {0:?}: {1:?} is not a valid range")]
+    InvalidEdit(FileId, Range<usize>),
+    #[error("No template registered with name: {0}")]
+    UnknownTemplate(String),
+    #[error("Invalid export options: {0}")]
+    InvalidExportOptions(String),
+    #[error("Could not export document: {0}")]
+    Export(String),
+    #[error("Invalid bundle: {0}")]
+    InvalidBundle(String),
+    #[error("Invalid input: {0}")]
+    InvalidInput(#[from] InputValidationError),
+    #[error("Invalid tenant: {0:?}")]
+    InvalidTenant(String),
+    #[error("Invalid metadata: {0}")]
+    InvalidMetadata(String),
+    #[error("Feedback of the template did not converge after {0} passes")]
+    FeedbackDidNotConverge(usize),
+    #[error("Source contains unescaped user data: {0:?}")]
+    UnescapedUserData(String),
+    #[cfg(feature = "pdf-validation")]
+    #[error("PDF violates the selected standard: {}", format_violations(.0))]
+    PdfValidation(Vec<pdf_validation::PdfViolation>),
+}
+
+/// Category of a `TypstAsLibError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// A file could not be resolved.
+    Resolution,
+    /// A package could not be downloaded or read.
+    Package,
+    Font,
+    /// Typst reported errors in the template.
+    Compilation,
+    Export,
+    /// Inputs or arguments, that were passed to this crate, are invalid.
+    Input,
+    Configuration,
+    Io,
+}
+
+impl TypstAsLibError {
+    /// Stable, machine-readable code of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypstAsLibError::TypstSource(_) => "typst_source",
+            TypstAsLibError::TypstFile(FileError::Package(_)) => "package",
+            TypstAsLibError::TypstFile(_) => "file",
+            TypstAsLibError::MainSourceFileDoesNotExist(_) => "main_source_missing",
+            TypstAsLibError::HintedString(_) => "typst_hinted",
+            TypstAsLibError::InvalidFont(_) => "invalid_font",
+            TypstAsLibError::InvalidConfig(_) => "invalid_config",
+            TypstAsLibError::Watch(_) => "watch",
+            TypstAsLibError::InvalidEdit(..) => "invalid_edit",
+            TypstAsLibError::UnknownTemplate(_) => "unknown_template",
+            TypstAsLibError::InvalidExportOptions(_) => "invalid_export_options",
+            TypstAsLibError::Export(_) => "export",
+            TypstAsLibError::InvalidInput(_) => "invalid_input",
+            TypstAsLibError::InvalidBundle(_) => "invalid_bundle",
+            TypstAsLibError::InvalidTenant(_) => "invalid_tenant",
+            TypstAsLibError::InvalidMetadata(_) => "invalid_metadata",
+            TypstAsLibError::FeedbackDidNotConverge(_) => "feedback_did_not_converge",
+            TypstAsLibError::UnescapedUserData(_) => "unescaped_user_data",
+            #[cfg(feature = "pdf-validation")]
+            TypstAsLibError::PdfValidation(_) => "pdf_validation",
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            TypstAsLibError::TypstSource(_)
+            | TypstAsLibError::HintedString(_)
+            | TypstAsLibError::InvalidMetadata(_)
+            | TypstAsLibError::FeedbackDidNotConverge(_) => ErrorCategory::Compilation,
+            TypstAsLibError::TypstFile(FileError::Package(_)) => ErrorCategory::Package,
+            TypstAsLibError::TypstFile(_) | TypstAsLibError::MainSourceFileDoesNotExist(_) => {
+                ErrorCategory::Resolution
+            }
+            TypstAsLibError::InvalidFont(_) => ErrorCategory::Font,
+            TypstAsLibError::InvalidConfig(_) => ErrorCategory::Configuration,
+            TypstAsLibError::Watch(_) => ErrorCategory::Io,
+            TypstAsLibError::InvalidEdit(..)
+            | TypstAsLibError::UnknownTemplate(_)
+            | TypstAsLibError::InvalidInput(_)
+            | TypstAsLibError::InvalidBundle(_)
+            | TypstAsLibError::InvalidTenant(_)
+            | TypstAsLibError::UnescapedUserData(_) => ErrorCategory::Input,
+            TypstAsLibError::InvalidExportOptions(_) | TypstAsLibError::Export(_) => {
+                ErrorCategory::Export
+            }
+            #[cfg(feature = "pdf-validation")]
+            TypstAsLibError::PdfValidation(_) => ErrorCategory::Export,
+        }
+    }
+
+    /// Diagnostics of typst (with spans and hints), if the error was reported by typst.
+    pub fn diagnostics(&self) -> &[SourceDiagnostic] {
+        match self {
+            TypstAsLibError::TypstSource(diagnostics) => diagnostics,
+            _ => &[],
+        }
+    }
+}
+
+fn format_diagnostics(diagnostics: &[SourceDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(feature = "pdf-validation")]
+fn format_violations(violations: &[pdf_validation::PdfViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 impl From<HintedString> for TypstAsLibError {
@@ -801,6 +2225,38 @@ impl From<&str> for SourceNewType {
     }
 }
 
+// Owned text is moved into the `Source`, borrowed text is copied once. Clones of a `Source`
+// share its text.
+impl From<Cow<'_, str>> for SourceNewType {
+    fn from(source: Cow<'_, str>) -> Self {
+        SourceNewType::from(source.into_owned())
+    }
+}
+
+impl From<(&str, Cow<'_, str>)> for SourceNewType {
+    fn from((path, source): (&str, Cow<'_, str>)) -> Self {
+        SourceNewType::from((path, source.into_owned()))
+    }
+}
+
+impl From<(FileId, Cow<'_, str>)> for SourceNewType {
+    fn from((id, source): (FileId, Cow<'_, str>)) -> Self {
+        SourceNewType::from((id, source.into_owned()))
+    }
+}
+
+impl AsRef<TypstTemplateCollection> for TypstTemplateCollection {
+    fn as_ref(&self) -> &TypstTemplateCollection {
+        self
+    }
+}
+
+impl AsRef<TypstTemplateCollection> for TypstTemplate {
+    fn as_ref(&self) -> &TypstTemplateCollection {
+        &self.collection
+    }
+}
+
 impl From<TypstTemplate> for TypstTemplateCollection {
     fn from(value: TypstTemplate) -> Self {
         value.collection
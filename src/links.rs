@@ -0,0 +1,118 @@
+use typst::layout::{Frame, FrameItem, Point, Position};
+use typst::model::{Destination, Document};
+
+use crate::pages::Rect;
+
+/// Where a link leads to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    /// An external link, e.g. `https://typst.app` or `mailto:`.
+    Url(String),
+    /// A position in the same document. Coordinates are in points from the top left corner.
+    Internal { page: usize, x: f64, y: f64 },
+}
+
+/// A link on a page of the document. See `document_links()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentLink {
+    /// 1-based page number of the link.
+    pub page: usize,
+    /// Clickable area of the link in points.
+    pub rect: Rect,
+    pub target: LinkTarget,
+}
+
+/// An element with a label, that links can point to. See `document_anchors()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anchor {
+    pub label: String,
+    /// 1-based page number.
+    pub page: usize,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// All links of the document in the order of the pages, e.g. to validate external links.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::links::{self, LinkTarget};
+/// # fn check_url(_url: &str) -> Result<(), typst_as_lib::TypstAsLibError> {
+/// #     Ok(())
+/// # }
+/// # use typst_as_lib::TypstTemplate;
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], r#"#link("https://typst.app")[Typst]"#);
+/// let doc = template.compile().output?;
+/// for link in links::document_links(&doc) {
+///     if let LinkTarget::Url(url) = &link.target {
+///         check_url(url)?;
+///     }
+/// }
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub fn document_links(document: &Document) -> Vec<DocumentLink> {
+    let mut links = Vec::new();
+    for (index, page) in document.pages.iter().enumerate() {
+        collect_links(document, &page.frame, Point::zero(), index + 1, &mut links);
+    }
+    links
+}
+
+/// All elements with a label (e.g. `= Introduction <intro>`), that have a position in the
+/// document, in document order.
+pub fn document_anchors(document: &Document) -> Vec<Anchor> {
+    document
+        .introspector
+        .all()
+        .filter_map(|content| {
+            let label = content.label()?;
+            let location = content.location()?;
+            let Position { page, point } = document.introspector.position(location);
+            Some(Anchor {
+                label: label.as_str().to_owned(),
+                page: page.get(),
+                x: point.x.to_pt(),
+                y: point.y.to_pt(),
+            })
+        })
+        .collect()
+}
+
+fn collect_links(
+    document: &Document,
+    frame: &Frame,
+    offset: Point,
+    page: usize,
+    links: &mut Vec<DocumentLink>,
+) {
+    for (pos, item) in frame.items() {
+        let pos = offset + *pos;
+        match item {
+            FrameItem::Group(group) => collect_links(document, &group.frame, pos, page, links),
+            FrameItem::Link(destination, size) => {
+                let target = match destination {
+                    Destination::Url(url) => LinkTarget::Url(url.as_str().to_owned()),
+                    Destination::Position(position) => internal(*position),
+                    Destination::Location(location) => {
+                        internal(document.introspector.position(*location))
+                    }
+                };
+                links.push(DocumentLink {
+                    page,
+                    rect: Rect::from_bounds(pos, pos + size.to_point()),
+                    target,
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+fn internal(Position { page, point }: Position) -> LinkTarget {
+    LinkTarget::Internal {
+        page: page.get(),
+        x: point.x.to_pt(),
+        y: point.y.to_pt(),
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use typst::diag::SourceDiagnostic;
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{ast, FileId, LinkedNode, Source, Span, SyntaxKind};
+
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Functions, that read files. Their first argument is a path.
+const FILE_FUNCTIONS: &[&str] = &[
+    "bibliography",
+    "cbor",
+    "csv",
+    "image",
+    "json",
+    "plugin",
+    "read",
+    "toml",
+    "xml",
+    "yaml",
+];
+
+/// Rules of `TypstTemplateCollection::lint()`. By default all rules are enabled.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::lint::LintConfig;
+/// let config = LintConfig {
+///     missing_page_setup: false,
+///     min_package_versions: vec!["@preview/cetz:0.3.0".parse().unwrap()],
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    /// Names of `#import "..": name`, that are never used.
+    pub unused_imports: bool,
+    /// Paths, that leave the root with `..` (typst silently stays in the root).
+    pub paths_outside_root: bool,
+    /// Imports of these packages with a lower version are reported, e.g. because the older
+    /// versions are deprecated.
+    pub min_package_versions: Vec<PackageSpec>,
+    /// The main file has no `#set page(..)`, so the page size depends on typst's default.
+    pub missing_page_setup: bool,
+    /// `sys.inputs.key` or `sys.inputs.at("key")` without `default:`, which fail, when the
+    /// input is missing.
+    pub inputs_without_default: bool,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            unused_imports: true,
+            paths_outside_root: true,
+            min_package_versions: Vec::new(),
+            missing_page_setup: true,
+            inputs_without_default: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    UnusedImport,
+    PathOutsideRoot,
+    DeprecatedPackageVersion,
+    MissingPageSetup,
+    InputWithoutDefault,
+}
+
+impl LintRule {
+    /// Stable, machine-readable code of the rule.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LintRule::UnusedImport => "unused_import",
+            LintRule::PathOutsideRoot => "path_outside_root",
+            LintRule::DeprecatedPackageVersion => "deprecated_package_version",
+            LintRule::MissingPageSetup => "missing_page_setup",
+            LintRule::InputWithoutDefault => "input_without_default",
+        }
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// A finding of `TypstTemplateCollection::lint()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintDiagnostic {
+    pub rule: LintRule,
+    pub id: FileId,
+    /// Byte range in the text of the file.
+    pub range: Range<usize>,
+    pub span: Span,
+    pub message: String,
+}
+
+impl LintDiagnostic {
+    /// As a warning of typst, e.g. to show it next to the warnings of the compilation.
+    pub fn to_source_diagnostic(&self) -> SourceDiagnostic {
+        SourceDiagnostic::warning(self.span, format!("{}: {}", self.rule, self.message))
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Check the main file and the files, that it imports or includes (except packages),
+    /// with the rules of `config`, e.g. to review templates, that users upload. Files, that
+    /// can't be resolved, are skipped; the compilation reports them.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::lint::LintConfig;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([("/invoice.typ", "#import \"/style.typ\": card\n= Invoice")]);
+    /// let diagnostics = template_collection.lint("/invoice.typ", &LintConfig::default())?;
+    /// for diagnostic in diagnostics {
+    ///     println!("{:?} {:?}: {}", diagnostic.id, diagnostic.range, diagnostic.message);
+    /// }
+    /// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+    /// ```
+    pub fn lint<F>(
+        &self,
+        main_source_id: F,
+        config: &LintConfig,
+    ) -> Result<Vec<LintDiagnostic>, TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let main = self.source(main_source_id)?;
+        let mut diagnostics = Vec::new();
+        if config.missing_page_setup && !has_page_setup(&LinkedNode::new(main.root())) {
+            diagnostics.push(LintDiagnostic {
+                rule: LintRule::MissingPageSetup,
+                id: main.id(),
+                range: 0..0,
+                span: main.root().span(),
+                message: "the main file has no `#set page(..)`".to_owned(),
+            });
+        }
+
+        let mut visited = HashSet::from([main.id()]);
+        let mut queue = vec![main];
+        while let Some(source) = queue.pop() {
+            let mut linter = Linter {
+                source: &source,
+                config,
+                diagnostics: &mut diagnostics,
+                dependencies: Vec::new(),
+            };
+            linter.lint_node(&LinkedNode::new(source.root()));
+            if config.unused_imports {
+                linter.lint_unused_imports();
+            }
+            for id in linter.dependencies {
+                if visited.insert(id) {
+                    if let Ok(dependency) = self.source(id) {
+                        queue.push(dependency);
+                    }
+                }
+            }
+        }
+        Ok(diagnostics)
+    }
+}
+
+impl TypstTemplate {
+    /// Check the main file of the template and its local imports.
+    /// See `TypstTemplateCollection::lint()`.
+    pub fn lint(&self, config: &LintConfig) -> Result<Vec<LintDiagnostic>, TypstAsLibError> {
+        self.collection.lint(self.source_id, config)
+    }
+}
+
+struct Linter<'a> {
+    source: &'a Source,
+    config: &'a LintConfig,
+    diagnostics: &'a mut Vec<LintDiagnostic>,
+    /// Local files, that are imported or included.
+    dependencies: Vec<FileId>,
+}
+
+impl Linter<'_> {
+    fn push(&mut self, rule: LintRule, node: &LinkedNode, message: String) {
+        self.diagnostics.push(LintDiagnostic {
+            rule,
+            id: self.source.id(),
+            range: node.range(),
+            span: node.span(),
+            message,
+        });
+    }
+
+    fn lint_node(&mut self, node: &LinkedNode) {
+        match node.kind() {
+            SyntaxKind::Str => self.lint_path(node),
+            SyntaxKind::FieldAccess if self.config.inputs_without_default => {
+                self.lint_input_access(node)
+            }
+            _ => (),
+        }
+        for child in node.children() {
+            self.lint_node(&child);
+        }
+    }
+
+    fn lint_path(&mut self, node: &LinkedNode) {
+        let Some(parent) = node.parent() else {
+            return;
+        };
+        let is_import = matches!(
+            parent.kind(),
+            SyntaxKind::ModuleImport | SyntaxKind::ModuleInclude
+        );
+        if !is_import && !is_file_function_argument(parent) {
+            return;
+        }
+        let Some(path) = node.cast::<ast::Str>().map(|path| path.get()) else {
+            return;
+        };
+        if path.starts_with('@') {
+            self.lint_package(node, &path);
+            return;
+        }
+        if self.config.paths_outside_root && leaves_root(self.source.id(), &path) {
+            self.push(
+                LintRule::PathOutsideRoot,
+                node,
+                format!("`{path}` points outside of the root"),
+            );
+        }
+        if is_import && self.source.id().package().is_none() {
+            self.dependencies.push(self.source.id().join(&path));
+        }
+    }
+
+    fn lint_package(&mut self, node: &LinkedNode, spec: &str) {
+        let Ok(package) = PackageSpec::from_str(spec) else {
+            return;
+        };
+        let minimum =
+            self.config.min_package_versions.iter().find(|minimum| {
+                minimum.namespace == package.namespace && minimum.name == package.name
+            });
+        if let Some(minimum) = minimum {
+            if package.version < minimum.version {
+                let message = format!("{package} is deprecated, use {minimum} or newer");
+                self.push(LintRule::DeprecatedPackageVersion, node, message);
+            }
+        }
+    }
+
+    /// `sys.inputs.key` and `sys.inputs.at("key")` without `default:`.
+    fn lint_input_access(&mut self, node: &LinkedNode) {
+        let Some(access) = node.cast::<ast::FieldAccess>() else {
+            return;
+        };
+        if !is_sys_inputs(access.target()) {
+            return;
+        }
+        let field = access.field();
+        if field.as_str() != "at" {
+            let message = format!(
+                "`sys.inputs.{}` fails, if the input is missing",
+                field.as_str()
+            );
+            self.push(LintRule::InputWithoutDefault, node, message);
+            return;
+        }
+        let Some(call) = node
+            .parent()
+            .and_then(|parent| parent.cast::<ast::FuncCall>())
+        else {
+            return;
+        };
+        let has_default = call
+            .args()
+            .items()
+            .any(|arg| matches!(arg, ast::Arg::Named(named) if named.name().as_str() == "default"));
+        if !has_default {
+            let message = "`sys.inputs.at()` without `default:` fails, if the input is missing";
+            self.push(LintRule::InputWithoutDefault, node, message.to_owned());
+        }
+    }
+
+    /// Names of `#import "..": name`, that are not used anywhere else in the file.
+    fn lint_unused_imports(&mut self) {
+        let root = LinkedNode::new(self.source.root());
+        let mut imported = Vec::new();
+        collect_imported_names(&root, &mut imported);
+        if imported.is_empty() {
+            return;
+        }
+        let mut used = HashSet::new();
+        collect_used_names(&root, &mut used);
+        for node in imported {
+            let name = node.text().to_string();
+            if !used.contains(&name) {
+                let message = format!("`{name}` is imported, but never used");
+                self.push(LintRule::UnusedImport, &node, message);
+            }
+        }
+    }
+}
+
+fn has_page_setup(node: &LinkedNode) -> bool {
+    if let Some(set_rule) = node.cast::<ast::SetRule>() {
+        if matches!(set_rule.target(), ast::Expr::Ident(ident) if ident.as_str() == "page") {
+            return true;
+        }
+    }
+    node.children().any(|child| has_page_setup(&child))
+}
+
+fn is_file_function_argument(args: &LinkedNode) -> bool {
+    if args.kind() != SyntaxKind::Args {
+        return false;
+    }
+    let Some(call) = args.parent().and_then(|call| call.cast::<ast::FuncCall>()) else {
+        return false;
+    };
+    matches!(call.callee(), ast::Expr::Ident(ident) if FILE_FUNCTIONS.contains(&ident.as_str()))
+}
+
+fn is_sys_inputs(expr: ast::Expr) -> bool {
+    let ast::Expr::FieldAccess(access) = expr else {
+        return false;
+    };
+    access.field().as_str() == "inputs"
+        && matches!(access.target(), ast::Expr::Ident(ident) if ident.as_str() == "sys")
+}
+
+/// Whether `path`, relative to the file `id`, goes above the root with `..`.
+fn leaves_root(id: FileId, path: &str) -> bool {
+    let mut depth = if path.starts_with('/') {
+        0
+    } else {
+        // Directories of the file.
+        id.vpath()
+            .as_rootless_path()
+            .components()
+            .count()
+            .saturating_sub(1) as isize
+    };
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+        if depth < 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// The identifiers, that `#import "..": a, b as c` binds.
+fn collect_imported_names<'a>(node: &LinkedNode<'a>, names: &mut Vec<LinkedNode<'a>>) {
+    if node.kind() == SyntaxKind::ImportItems {
+        for child in node.children() {
+            // The bound name is the last identifier: `a.b` binds `b`, `a as c` binds `c`.
+            if matches!(
+                child.kind(),
+                SyntaxKind::ImportItemPath | SyntaxKind::RenamedImportItem
+            ) {
+                let bound = child
+                    .children()
+                    .rfind(|part| part.kind() == SyntaxKind::Ident);
+                names.extend(bound);
+            }
+        }
+        return;
+    }
+    for child in node.children() {
+        collect_imported_names(&child, names);
+    }
+}
+
+/// Identifiers outside of import lists.
+fn collect_used_names(node: &LinkedNode, names: &mut HashSet<String>) {
+    match node.kind() {
+        SyntaxKind::ImportItems => return,
+        SyntaxKind::Ident | SyntaxKind::MathIdent => {
+            names.insert(node.text().to_string());
+        }
+        _ => (),
+    }
+    for child in node.children() {
+        collect_used_names(&child, names);
+    }
+}
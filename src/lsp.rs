@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use typst::diag::{Severity, SourceDiagnostic};
+use typst::syntax::{FileId, Source};
+
+use crate::{TypstTemplate, TypstTemplateCollection};
+
+/// Convert a diagnostic of typst, that points into `source`, to a LSP diagnostic. Hints are
+/// appended to the message. Positions are counted in UTF-16 code units, as LSP requires.
+pub fn to_lsp_diagnostic(source: &Source, diagnostic: &SourceDiagnostic) -> Diagnostic {
+    let range = source
+        .range(diagnostic.span)
+        .map(|range| Range::new(position(source, range.start), position(source, range.end)))
+        .unwrap_or_default();
+    let mut message = diagnostic.message.to_string();
+    for hint in &diagnostic.hints {
+        message.push_str("\nhint: ");
+        message.push_str(hint);
+    }
+    let severity = match diagnostic.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+    };
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        source: Some("typst".to_owned()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Zero-based line and UTF-16 column of `byte` in `source`.
+fn position(source: &Source, byte: usize) -> Position {
+    let line = source.byte_to_line(byte).unwrap_or_default();
+    let column = source
+        .line_to_byte(line)
+        .and_then(|line_start| {
+            Some(source.byte_to_utf16(byte)? - source.byte_to_utf16(line_start)?)
+        })
+        .unwrap_or_default();
+    Position::new(line as u32, column as u32)
+}
+
+impl TypstTemplateCollection {
+    /// Convert the diagnostics of a compilation (errors and warnings) to LSP diagnostics,
+    /// grouped by the file, that they point into (`FileId::vpath()`, and `FileId::package()`
+    /// for package files). The sources are resolved with the file resolvers. Diagnostics
+    /// without a file, or whose file can't be resolved, are left out.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use lsp_types::Diagnostic;
+    /// # use typst::diag::Warned;
+    /// # use typst::syntax::{FileId, VirtualPath};
+    /// # struct Client;
+    /// # impl Client {
+    /// #     fn publish_diagnostics(&self, _uri: String, _diagnostics: Vec<Diagnostic>, _version: Option<i32>) {}
+    /// # }
+    /// # fn uri_to_file_id(uri: &str) -> FileId {
+    /// #     FileId::new(None, VirtualPath::new(uri.trim_start_matches("file://")))
+    /// # }
+    /// # fn file_id_to_uri(id: FileId) -> String {
+    /// #     format!("file://{}", id.vpath().as_rootless_path().display())
+    /// # }
+    /// # let client = Client;
+    /// # let uri = "file:///invoice.typ";
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let template_collection = TypstTemplateCollection::new(vec![font])
+    /// #     .with_static_source_file_resolver([("/invoice.typ", "#let total = 1 +")]);
+    /// let Warned { output, warnings } = template_collection.compile(uri_to_file_id(&uri));
+    /// let mut diagnostics = warnings.to_vec();
+    /// if let Err(err) = &output {
+    ///     diagnostics.extend_from_slice(err.diagnostics());
+    /// }
+    /// for (id, diagnostics) in template_collection.lsp_diagnostics(&diagnostics) {
+    ///     client.publish_diagnostics(file_id_to_uri(id), diagnostics, None);
+    /// }
+    /// ```
+    pub fn lsp_diagnostics(
+        &self,
+        diagnostics: &[SourceDiagnostic],
+    ) -> HashMap<FileId, Vec<Diagnostic>> {
+        let mut sources: HashMap<FileId, Option<Source>> = HashMap::new();
+        let mut lsp_diagnostics: HashMap<FileId, Vec<Diagnostic>> = HashMap::new();
+        for diagnostic in diagnostics {
+            let Some(id) = diagnostic.span.id() else {
+                continue;
+            };
+            let source = sources.entry(id).or_insert_with(|| self.source(id).ok());
+            if let Some(source) = source {
+                lsp_diagnostics
+                    .entry(id)
+                    .or_default()
+                    .push(to_lsp_diagnostic(source, diagnostic));
+            }
+        }
+        lsp_diagnostics
+    }
+}
+
+impl TypstTemplate {
+    /// Convert the diagnostics of a compilation to LSP diagnostics.
+    /// See `TypstTemplateCollection::lsp_diagnostics()`.
+    pub fn lsp_diagnostics(
+        &self,
+        diagnostics: &[SourceDiagnostic],
+    ) -> HashMap<FileId, Vec<Diagnostic>> {
+        self.collection.lsp_diagnostics(diagnostics)
+    }
+}
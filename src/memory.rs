@@ -0,0 +1,21 @@
+/// Approximate memory held by a `TypstTemplateCollection`. Bytes, that are shared between
+/// multiple owners (e.g. the same `Bytes` in multiple caches), are counted multiple times.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFootprint {
+    /// Data of all fonts.
+    pub fonts: usize,
+    /// Sources and binaries held by each file resolver (by `FileResolver::name()`),
+    /// including their caches.
+    pub file_resolvers: Vec<(String, usize)>,
+    /// Resident memory of the whole process, which includes the memoization caches of comemo.
+    /// Only available on Linux.
+    pub process_resident: Option<usize>,
+}
+
+impl MemoryFootprint {
+    /// Bytes held by fonts and file resolvers.
+    pub fn total(&self) -> usize {
+        let file_resolvers: usize = self.file_resolvers.iter().map(|(_, bytes)| bytes).sum();
+        self.fonts + file_resolvers
+    }
+}
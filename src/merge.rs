@@ -0,0 +1,40 @@
+use typst::introspection::Introspector;
+use typst::model::Document;
+
+/// Combine multiple documents into one, e.g. to export the results of several compilations
+/// as one PDF. The pages are appended in order. The metadata (title, author, ...) is taken from
+/// the first document.
+///
+/// Example:
+/// ```rust
+/// # use typst::foundations::{dict, Dict};
+/// # use typst_as_lib::merge::merge_documents;
+/// # use typst_as_lib::TypstTemplate;
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], "#import sys: inputs\n= #inputs.name");
+/// # let customers: Vec<Dict> = vec![dict! { "name" => "Acme" }, dict! { "name" => "Globex" }];
+/// let documents = customers
+///     .iter()
+///     .map(|customer| template.compile_with_input(customer.clone()).output)
+///     .collect::<Result<Vec<_>, _>>()?;
+/// let document = merge_documents(&documents);
+/// let pdf = typst_pdf::pdf(&document, &Default::default())?;
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub fn merge_documents(documents: &[Document]) -> Document {
+    let pages: Vec<_> = documents
+        .iter()
+        .flat_map(|document| document.pages.iter().cloned())
+        .collect();
+    let info = documents
+        .first()
+        .map(|document| document.info.clone())
+        .unwrap_or_default();
+    // Links and the outline are resolved with the introspector, so it has to know all pages.
+    let introspector = Introspector::new(&pages);
+    Document {
+        pages,
+        info,
+        introspector,
+    }
+}
@@ -0,0 +1,102 @@
+use std::borrow::Cow;
+
+use typst::syntax::Source;
+
+use crate::{TypstTemplate, TypstTemplateCollection};
+
+/// Normalization of the text of sources, so they behave the same, no matter on which platform
+/// they were written. Line endings (`\r\n` and `\r`) are always converted to `\n`.
+/// See `TypstTemplateCollection::with_source_normalization()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SourceNormalization {
+    /// Replace tabs with spaces up to the next multiple of the width.
+    pub tab_width: Option<usize>,
+}
+
+impl SourceNormalization {
+    /// Only normalize line endings.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = Some(tab_width);
+        self
+    }
+
+    /// `None`, if the text is normalized already.
+    fn normalize(&self, text: &str) -> Option<String> {
+        let expand_tabs = self.tab_width.is_some_and(|width| width > 0) && text.contains('\t');
+        if !text.contains('\r') && !expand_tabs {
+            return None;
+        }
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let Some(tab_width) = self.tab_width.filter(|_| expand_tabs) else {
+            return Some(text);
+        };
+        let mut expanded = String::with_capacity(text.len());
+        let mut column = 0;
+        for c in text.chars() {
+            match c {
+                '\t' => {
+                    let spaces = tab_width - column % tab_width;
+                    expanded.extend(std::iter::repeat_n(' ', spaces));
+                    column += spaces;
+                }
+                '\n' => {
+                    expanded.push(c);
+                    column = 0;
+                }
+                c => {
+                    expanded.push(c);
+                    column += 1;
+                }
+            }
+        }
+        Some(expanded)
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Normalize the sources of all file resolvers (and the sources passed directly, e.g. to
+    /// `compile_source()`), so spans and rendering don't depend on the platform, on which a
+    /// file was written. Binaries (e.g. `read()`) are not changed.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::normalize::SourceNormalization;
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let fonts = vec![font];
+    /// let template_collection = TypstTemplateCollection::new(fonts)
+    ///     .with_source_normalization(SourceNormalization::new().with_tab_width(4));
+    /// ```
+    pub fn with_source_normalization(mut self, normalization: SourceNormalization) -> Self {
+        self.with_source_normalization_mut(normalization);
+        self
+    }
+
+    /// Normalize the sources of all file resolvers.
+    /// See `TypstTemplateCollection::with_source_normalization()`.
+    pub fn with_source_normalization_mut(&mut self, normalization: SourceNormalization) {
+        self.source_normalization = Some(normalization);
+    }
+
+    pub(crate) fn normalize_source<'a>(&self, source: Cow<'a, Source>) -> Cow<'a, Source> {
+        let normalized = self
+            .source_normalization
+            .and_then(|normalization| normalization.normalize(source.text()));
+        match normalized {
+            Some(text) => Cow::Owned(Source::new(source.id(), text)),
+            None => source,
+        }
+    }
+}
+
+impl TypstTemplate {
+    /// Normalize all sources. See `TypstTemplateCollection::with_source_normalization()`.
+    pub fn with_source_normalization(mut self, normalization: SourceNormalization) -> Self {
+        self.collection.with_source_normalization_mut(normalization);
+        self
+    }
+}
@@ -0,0 +1,168 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use typst::diag::FileResult;
+use typst::foundations::Bytes;
+use typst::syntax::{FileId, Source};
+
+use crate::file_resolver::{FileResolver, FileResolverStats};
+use crate::util::bytes_to_source;
+use crate::FileIdNewType;
+
+/// Overrides single files of a base file resolver, e.g. `/partials/footer.typ` for one
+/// tenant, while all other files are served by the base.
+///
+/// Precedence: a file, that is added to the overlay, always shadows the file of the base, for
+/// sources and binaries (`read()`) alike. Files, that are not in the overlay, are resolved by
+/// the base only. Errors of the base are returned unchanged. Package files can't be
+/// overridden.
+///
+/// Example:
+/// ```rust
+/// # use std::sync::Arc;
+/// # use typst_as_lib::file_resolver::FileSystemResolver;
+/// # use typst_as_lib::overlay::OverlayResolver;
+/// # use typst_as_lib::tenant::TenantScopedResolver;
+/// # use typst_as_lib::TypstTemplateCollection;
+/// # static ACME_FOOTER: &str = "ACME Corp.";
+/// # static ACME_LOGO: &[u8] = include_bytes!("../examples/templates/images/typst.png");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// let base = Arc::new(FileSystemResolver::new("/srv/templates".into()));
+/// let collection = TypstTemplateCollection::new(fonts).add_file_resolver(
+///     TenantScopedResolver::new()
+///         .with_tenant(
+///             "acme",
+///             OverlayResolver::new(Arc::clone(&base))
+///                 .with_source("/partials/footer.typ", ACME_FOOTER)
+///                 .with_binary("/logo.png", ACME_LOGO),
+///         )
+///         .with_tenant("globex", base),
+/// );
+/// ```
+pub struct OverlayResolver<B> {
+    pub base: B,
+    sources: HashMap<FileId, Source>,
+    binaries: HashMap<FileId, Bytes>,
+}
+
+impl<B> OverlayResolver<B> {
+    pub fn new(base: B) -> Self {
+        Self {
+            base,
+            sources: Default::default(),
+            binaries: Default::default(),
+        }
+    }
+
+    /// Override the source at `id`, e.g. `/partials/footer.typ`.
+    pub fn with_source<F, S>(mut self, id: F, text: S) -> Self
+    where
+        F: Into<FileIdNewType>,
+        S: Into<String>,
+    {
+        self.with_source_mut(id, text);
+        self
+    }
+
+    pub fn with_source_mut<F, S>(&mut self, id: F, text: S)
+    where
+        F: Into<FileIdNewType>,
+        S: Into<String>,
+    {
+        let FileIdNewType(id) = id.into();
+        self.binaries.remove(&id);
+        self.sources.insert(id, Source::new(id, text.into()));
+    }
+
+    /// Override the binary at `id`, e.g. `/logo.png`.
+    pub fn with_binary<F, V>(mut self, id: F, bytes: V) -> Self
+    where
+        F: Into<FileIdNewType>,
+        V: Into<Bytes>,
+    {
+        self.with_binary_mut(id, bytes);
+        self
+    }
+
+    pub fn with_binary_mut<F, V>(&mut self, id: F, bytes: V)
+    where
+        F: Into<FileIdNewType>,
+        V: Into<Bytes>,
+    {
+        let FileIdNewType(id) = id.into();
+        self.sources.remove(&id);
+        self.binaries.insert(id, bytes.into());
+    }
+
+    /// Whether the overlay shadows the file of the base.
+    pub fn overrides(&self, id: FileId) -> bool {
+        self.sources.contains_key(&id) || self.binaries.contains_key(&id)
+    }
+}
+
+impl<B> FileResolver for OverlayResolver<B>
+where
+    B: FileResolver,
+{
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        if let Some(bytes) = self.binaries.get(&id) {
+            return Ok(Cow::Borrowed(bytes));
+        }
+        if let Some(source) = self.sources.get(&id) {
+            return Ok(Cow::Owned(Bytes::from(source.text().as_bytes())));
+        }
+        self.base.resolve_binary(id)
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        if let Some(source) = self.sources.get(&id) {
+            return Ok(Cow::Borrowed(source));
+        }
+        if let Some(bytes) = self.binaries.get(&id) {
+            return bytes_to_source(id, bytes).map(Cow::Owned);
+        }
+        self.base.resolve_source(id)
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.base.stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let sources: usize = self
+            .sources
+            .values()
+            .map(|source| source.text().len())
+            .sum();
+        let binaries: usize = self.binaries.values().map(|bytes| bytes.len()).sum();
+        sources + binaries + self.base.memory_footprint()
+    }
+
+    fn invalidate(&self, id: FileId) {
+        self.base.invalidate(id);
+    }
+
+    fn uses_network(&self) -> bool {
+        self.base.uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.base.roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        if self.overrides(id) {
+            return None;
+        }
+        self.base.source_encoding(id)
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        let mut file_ids = self.base.file_ids();
+        file_ids.retain(|id| !self.overrides(*id));
+        file_ids.extend(self.sources.keys().chain(self.binaries.keys()));
+        file_ids
+    }
+}
@@ -1,22 +1,34 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::Read,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use binstall_tar::Archive;
+use binstall_tar::{Archive, Builder, Header};
 use ecow::eco_format;
 use flate2::read::GzDecoder;
 use typst::{
     diag::{FileError, FileResult, PackageError},
     foundations::Bytes,
-    syntax::{package::PackageSpec, FileId, Source, VirtualPath},
+    syntax::{
+        package::{PackageSpec, PackageVersion},
+        FileId, Source, VirtualPath,
+    },
 };
 
 use crate::{
-    cached_file_resolver::{CachedFileResolver, IntoCachedFileResolver}, file_resolver::{FileResolver, DEFAULT_PACKAGES_SUBDIR}, util::{bytes_to_source, not_found}
+    cached_file_resolver::{lock_cache, CachedFileResolver, IntoCachedFileResolver},
+    cancellation::{CancellableReader, CancellationToken},
+    file_resolver::{FileResolver, FileResolverStats, DEFAULT_PACKAGES_SUBDIR},
+    instrument,
+    util::{bytes_to_source, not_found, write_atomically},
 };
 
 // https://github.com/typst/typst/blob/16736feb13eec87eb9ca114deaeb4f7eeb7409d2/crates/typst-kit/src/package.rs#L15
@@ -25,10 +37,15 @@ static PACKAGE_REPOSITORY_URL: &str = "https://packages.typst.org";
 
 static REQUEST_RETRY_COUNT: u32 = 3;
 
+/// Connect and read timeout of the default agent, so a stalled connection can't block a
+/// thread indefinitely.
+static DEFAULT_NETWORK_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone, Default)]
 pub struct PackageResolverBuilder<C = ()> {
-    ureq: Option<ureq::Agent>,
+    client: RegistryClient,
     cache: C,
+    policy: PackagePolicy,
 }
 
 impl PackageResolverBuilder<()> {
@@ -38,54 +55,194 @@ impl PackageResolverBuilder<()> {
 }
 
 impl<C> PackageResolverBuilder<C> {
-    pub fn ureq_agent(self, ureq: ureq::Agent) -> Self {
-        Self {
-            ureq: Some(ureq),
-            ..self
-        }
+    pub fn ureq_agent(mut self, ureq: ureq::Agent) -> Self {
+        self.client.ureq = ureq;
+        self
     }
 
-    pub fn set_cache<C1>(self, cache: C1) -> PackageResolverBuilder<C1> {
-        let Self { ureq, .. } = self;
-        PackageResolverBuilder { ureq, cache }
+    /// Add a package repository, that is tried after the already added ones. If no mirror is
+    /// added, packages are downloaded from `https://packages.typst.org`. Add
+    /// `PackageMirror::typst()` to use it as fallback.
+    pub fn with_mirror(mut self, mirror: PackageMirror) -> Self {
+        self.client = self.client.with_mirror(mirror);
+        self
     }
 
-    pub fn with_file_system_cache(self) -> PackageResolverBuilder<FileSystemCache> {
-        let Self { ureq, .. } = self;
+    /// `User-Agent` header of all requests.
+    pub fn user_agent<S>(mut self, user_agent: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.client = self.client.with_user_agent(user_agent);
+        self
+    }
+
+    /// Modify every request before it is sent, e.g. to add headers or sign it.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::package_resolver::PackageResolverBuilder;
+    /// # fn token() -> String {
+    /// #     "secret-token".to_owned()
+    /// # }
+    /// let resolver = PackageResolverBuilder::new()
+    ///     .user_agent("acme-invoices/1.4")
+    ///     .request_hook(|request| request.set("X-Artifactory-Token", &token()))
+    ///     .with_file_system_cache()
+    ///     .build();
+    /// ```
+    pub fn request_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(ureq::Request) -> ureq::Request + Send + Sync + 'static,
+    {
+        self.client = self.client.with_request_hook(hook);
+        self
+    }
+
+    /// Only resolve packages, that the policy allows. See `PackagePolicy`.
+    pub fn policy(self, policy: PackagePolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    pub fn set_cache<C1>(self, cache: C1) -> PackageResolverBuilder<C1> {
+        let Self { client, policy, .. } = self;
         PackageResolverBuilder {
-            ureq,
-            cache: FileSystemCache::new(),
+            client,
+            cache,
+            policy,
         }
     }
 
+    pub fn with_file_system_cache(self) -> PackageResolverBuilder<FileSystemCache> {
+        self.set_cache(FileSystemCache::new())
+    }
+
     pub fn with_in_memory_cache(self) -> PackageResolverBuilder<InMemoryCache> {
-        let Self { ureq, .. } = self;
-        PackageResolverBuilder {
-            ureq,
-            cache: InMemoryCache::new(),
-        }
+        self.set_cache(InMemoryCache::new())
+    }
+
+    /// Read packages from the read-only directory `base` first and cache downloaded
+    /// packages in `overlay`. See `LayeredCache`.
+    pub fn with_layered_cache(
+        self,
+        base: PathBuf,
+        overlay: PathBuf,
+    ) -> PackageResolverBuilder<LayeredCache<FileSystemCache>> {
+        self.set_cache(LayeredCache::new(base, FileSystemCache(overlay)))
     }
 
     pub fn build(self) -> PackageResolver<C> {
-        let Self { ureq, cache } = self;
-        let ureq = ureq.unwrap_or_else(|| ureq::Agent::new());
-        PackageResolver { ureq, cache }
+        let Self {
+            client,
+            cache,
+            policy,
+        } = self;
+        PackageResolver {
+            client,
+            cache,
+            policy,
+            downloads: Default::default(),
+            in_flight: Default::default(),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct PackageResolver<C> {
-    ureq: ureq::Agent,
+    client: RegistryClient,
     cache: C,
+    policy: PackagePolicy,
+    downloads: Arc<AtomicU64>,
+    /// Packages, that are being downloaded. Other threads, that need the same package, wait
+    /// for the download instead of downloading it again.
+    in_flight: Arc<InFlightDownloads>,
 }
 
 impl<C> PackageResolver<C> {
+    /// Latest version of the package `@preview/<name>` in the typst registry, e.g. to keep the
+    /// package imports of templates up to date. Only the `preview` namespace has an index.
+    /// The version is not recorded anywhere, so pin it in the imports of the templates or with
+    /// `VendorOptions::with_pinned_version()`.
+    pub fn resolve_latest(&self, name: &str) -> Result<PackageSpec, PackageError> {
+        resolve_latest(&self.client, name)
+    }
+
+    /// Blocks, while another thread downloads the package. The download of this thread ends,
+    /// when the guard is dropped. Fails, once the `CancellationToken` of the current thread is
+    /// cancelled while waiting.
+    fn lock_download(&self, package: &PackageSpec) -> Result<DownloadGuard, PackageError> {
+        let InFlightDownloads { packages, finished } = self.in_flight.as_ref();
+        let mut packages = packages
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        while packages.contains(package) {
+            if CancellationToken::is_current_cancelled() {
+                return Err(cancelled());
+            }
+            // The token can't wake up the condvar, so it is checked periodically.
+            (packages, _) = finished
+                .wait_timeout(packages, CANCELLATION_POLL_INTERVAL)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        packages.insert(package.clone());
+        Ok(DownloadGuard {
+            in_flight: Arc::clone(&self.in_flight),
+            package: package.clone(),
+        })
+    }
+
+    /// Download the package, if it is not cached. Returns `true`, if it was downloaded.
+    pub(crate) fn prefetch_package(&self, package: &PackageSpec) -> FileResult<bool>
+    where
+        C: PackageResolverCache,
+    {
+        let manifest = FileId::new(Some(package.clone()), VirtualPath::new("typst.toml"));
+        let downloads = self.downloads.load(Ordering::Relaxed);
+        let _: Bytes = self.resolve_bytes(manifest)?;
+        Ok(self.downloads.load(Ordering::Relaxed) > downloads)
+    }
+
+    pub(crate) fn revalidate_cached(&self, package: &PackageSpec) -> FileResult<bool>
+    where
+        C: PackageResolverCache,
+    {
+        let Self {
+            client,
+            cache,
+            policy,
+            ..
+        } = self;
+        policy.check(package)?;
+        // E.g. baked into the base of a `LayeredCache`.
+        if cache.is_read_only(package) {
+            return Ok(false);
+        }
+        let _download = self.lock_download(package)?;
+        let validators = cache.lookup_validators(package);
+        let Some((archive, validators)) = fetch_package(client, package, validators.as_ref())?
+        else {
+            return Ok(false);
+        };
+        self.downloads.fetch_add(1, Ordering::Relaxed);
+
+        cache.cache_archive(Archive::new(&archive[..]), package)?;
+        if let Some(validators) = validators {
+            cache.cache_validators(package, &validators);
+        }
+        Ok(true)
+    }
+
     fn resolve_bytes<T>(&self, id: FileId) -> FileResult<T>
     where
         SourceOrBytesCreator: CreateBytesOrSource<T>,
         C: PackageResolverCache,
     {
-        let Self { ureq, cache, .. } = self;
+        let Self {
+            client,
+            cache,
+            policy,
+            ..
+        } = self;
         let Some(package) = id.package() else {
             return Err(not_found(id));
         };
@@ -94,57 +251,81 @@ impl<C> PackageResolver<C> {
         if package.namespace != "preview" {
             return Err(not_found(id));
         }
+        policy.check(package)?;
 
         match cache.lookup_cached(package, id) {
             Ok(Some(cached)) => return Ok(cached),
+            // Files, that are missing in a read-only package, are not downloaded.
+            result if cache.is_read_only(package) => {
+                return result.and_then(|cached| cached.ok_or_else(|| not_found(id)))
+            }
             _ => (),
         }
 
-        let PackageSpec {
-            namespace,
-            name,
-            version,
-        } = package;
-
-        let url = format!(
-            "{}/{}/{}-{}.tar.gz",
-            PACKAGE_REPOSITORY_URL, namespace, name, version,
-        );
-
-        let mut last_error = eco_format!("");
-        let mut response = None;
-        for _ in 0..REQUEST_RETRY_COUNT {
-            let resp = match ureq.get(&url).call() {
-                Ok(resp) => resp,
-                Err(error) => {
-                    last_error = eco_format!("{error}");
-                    continue;
-                }
-            };
-
-            let status = resp.status();
-            if status != 200 {
-                last_error = eco_format!("response returned unsuccessful status code {status}");
-                continue;
-            }
-            response = Some(resp);
-            break;
+        let _download = self.lock_download(package)?;
+        // Another thread may have downloaded the package in the meantime.
+        if let Ok(Some(cached)) = cache.lookup_cached(package, id) {
+            return Ok(cached);
         }
-        let response = response.ok_or_else(|| PackageError::NetworkFailed(Some(last_error)))?;
 
-        let mut d = GzDecoder::new(response.into_reader());
-        let mut archive = Vec::new();
-        d.read_to_end(&mut archive)
-            .map_err(|error| PackageError::MalformedArchive(Some(eco_format!("{error}"))))?;
+        let (archive, validators) =
+            fetch_package(client, package, None)?.ok_or_else(|| not_found(id))?;
+        self.downloads.fetch_add(1, Ordering::Relaxed);
 
-        let archive = Archive::new(&archive[..]);
-        cache.cache_archive(archive, package)?;
+        cache.cache_archive(Archive::new(&archive[..]), package)?;
+        if let Some(validators) = validators {
+            cache.cache_validators(package, &validators);
+        }
         cache
             .lookup_cached(package, id)
             .and_then(|f| f.ok_or_else(|| not_found(id)))
     }
 }
 
+/// How often threads, that wait for the download of another thread, check for cancellation.
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Default)]
+struct InFlightDownloads {
+    packages: Mutex<HashSet<PackageSpec>>,
+    finished: Condvar,
+}
+
+/// Ends the download of the package and wakes up the threads, that wait for it.
+struct DownloadGuard {
+    in_flight: Arc<InFlightDownloads>,
+    package: PackageSpec,
+}
+
+impl Drop for DownloadGuard {
+    fn drop(&mut self) {
+        let InFlightDownloads { packages, finished } = self.in_flight.as_ref();
+        packages
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&self.package);
+        finished.notify_all();
+    }
+}
+
+impl PackageResolver<FileSystemCache> {
+    /// Check, whether the cached package is still up to date, with a conditional request
+    /// (`If-None-Match`/`If-Modified-Since`), and download it again, if it was modified.
+    /// The validators are stored next to the cached package. Returns `true`, if the package
+    /// was downloaded.
+    pub fn revalidate(&self, package: &PackageSpec) -> FileResult<bool> {
+        self.revalidate_cached(package)
+    }
+}
+
+impl PackageResolver<InMemoryCache> {
+    /// Download the package again. The in-memory cache stores no validators, so there is no
+    /// conditional request. Always returns `true`.
+    pub fn revalidate(&self, package: &PackageSpec) -> FileResult<bool> {
+        self.revalidate_cached(package)
+    }
+}
+
 impl<C> FileResolver for PackageResolver<C>
 where
     C: PackageResolverCache,
@@ -154,13 +335,507 @@ where
         Ok(Cow::Owned(cached))
     }
 
-    fn resolve_source(&self, id: FileId) -> FileResult<Cow<Source>> {
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
         let cached: Source = self.resolve_bytes(id)?;
         Ok(Cow::Owned(cached))
     }
+
+    fn stats(&self) -> FileResolverStats {
+        FileResolverStats {
+            packages_downloaded: self.downloads.load(Ordering::Relaxed),
+            ..Default::default()
+        }
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.cache.memory_footprint()
+    }
+
+    fn uses_network(&self) -> bool {
+        true
+    }
+}
+
+/// Decides, which packages may be resolved or vendored. Use an allowlist, when compiling
+/// untrusted templates, so they can't pull arbitrary packages.
+///
+/// Patterns are a namespace (`preview`), a namespace and a package name (`preview/cetz`) or
+/// a package with version (`preview/cetz:0.3.1`). Denied patterns take precedence over
+/// allowed ones.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::package_resolver::{PackagePolicy, PackageResolverBuilder};
+/// let policy = PackagePolicy::allowlist(["preview/cetz", "preview/tablex:0.0.8"]);
+/// let resolver = PackageResolverBuilder::new()
+///     .with_file_system_cache()
+///     .policy(policy)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PackagePolicy {
+    /// `None`: all packages are allowed.
+    allowed: Option<Vec<String>>,
+    denied: Vec<String>,
+}
+
+impl PackagePolicy {
+    /// Allow all packages, that are not denied. This is the default.
+    pub fn allow_all() -> Self {
+        Default::default()
+    }
+
+    /// Only allow packages, that match one of the patterns.
+    pub fn allowlist<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allowed: Some(patterns.into_iter().map(Into::into).collect()),
+            denied: Default::default(),
+        }
+    }
+
+    pub fn with_allowed<S>(mut self, pattern: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.allowed
+            .get_or_insert_with(Default::default)
+            .push(pattern.into());
+        self
+    }
+
+    pub fn with_denied<S>(mut self, pattern: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.denied.push(pattern.into());
+        self
+    }
+
+    pub fn allows(&self, package: &PackageSpec) -> bool {
+        let Self { allowed, denied } = self;
+        let is_allowed = allowed.as_ref().is_none_or(|allowed| {
+            allowed
+                .iter()
+                .any(|pattern| matches_package_pattern(pattern, package))
+        });
+        is_allowed
+            && !denied
+                .iter()
+                .any(|pattern| matches_package_pattern(pattern, package))
+    }
+
+    pub(crate) fn check(&self, package: &PackageSpec) -> Result<(), PackageError> {
+        if self.allows(package) {
+            return Ok(());
+        }
+        Err(PackageError::Other(Some(eco_format!(
+            "package {package} is not allowed by the package policy"
+        ))))
+    }
+}
+
+/// `pattern` is `namespace`, `namespace/name` or `namespace/name:version`, optionally with a
+/// leading `@`.
+pub(crate) fn matches_package_pattern(pattern: &str, package: &PackageSpec) -> bool {
+    let pattern = pattern.strip_prefix('@').unwrap_or(pattern);
+    let Some((namespace, rest)) = pattern.split_once('/') else {
+        return package.namespace == pattern;
+    };
+    let (name, version) = match rest.split_once(':') {
+        Some((name, version)) => (name, Some(version)),
+        None => (rest, None),
+    };
+    package.namespace == namespace
+        && package.name == name
+        && version.is_none_or(|version| package.version.to_string() == version)
+}
+
+/// A package repository, that serves packages under the same paths as
+/// `https://packages.typst.org` (`/<namespace>/<name>-<version>.tar.gz` and
+/// `/<namespace>/index.json`). See `PackageResolverBuilder::with_mirror()`.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::package_resolver::{PackageMirror, PackageResolverBuilder};
+/// let proxy = ureq::Proxy::new("http://proxy.corp.example:3128")?;
+/// let resolver = PackageResolverBuilder::new()
+///     .with_mirror(
+///         PackageMirror::new("https://typst-mirror.corp.example")
+///             .with_ureq_agent(ureq::AgentBuilder::new().proxy(proxy).build())
+///             .with_authorization("Bearer secret-token"),
+///     )
+///     .with_mirror(PackageMirror::typst())
+///     .with_file_system_cache()
+///     .build();
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct PackageMirror {
+    url: String,
+    ureq: Option<ureq::Agent>,
+    authorization: Option<String>,
+}
+
+impl std::fmt::Debug for PackageMirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The authorization header contains credentials.
+        f.debug_struct("PackageMirror")
+            .field("url", &self.url)
+            .field("ureq", &self.ureq)
+            .field(
+                "authorization",
+                &self.authorization.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+impl PackageMirror {
+    pub fn new<S>(url: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let url: String = url.into();
+        Self {
+            url: url.trim_end_matches('/').to_owned(),
+            ureq: None,
+            authorization: None,
+        }
+    }
+
+    /// The default Typst registry `https://packages.typst.org`.
+    pub fn typst() -> Self {
+        Self::new(PACKAGE_REPOSITORY_URL)
+    }
+
+    /// Use this agent (e.g. with custom TLS settings or a proxy) for the mirror instead of the
+    /// agent of the `PackageResolver`.
+    pub fn with_ureq_agent(self, ureq: ureq::Agent) -> Self {
+        Self {
+            ureq: Some(ureq),
+            ..self
+        }
+    }
+
+    /// Value of the `Authorization` header, that is sent to the mirror.
+    pub fn with_authorization<S>(self, authorization: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            authorization: Some(authorization.into()),
+            ..self
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// Function, that modifies requests before they are sent.
+#[derive(Clone)]
+struct RequestHook(Arc<dyn Fn(ureq::Request) -> ureq::Request + Send + Sync>);
+
+impl std::fmt::Debug for RequestHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RequestHook")
+    }
+}
+
+/// Everything, that is needed to send requests to the package repositories.
+#[derive(Debug, Clone)]
+pub(crate) struct RegistryClient {
+    ureq: ureq::Agent,
+    mirrors: Vec<PackageMirror>,
+    user_agent: Option<String>,
+    request_hook: Option<RequestHook>,
+}
+
+impl Default for RegistryClient {
+    fn default() -> Self {
+        Self {
+            ureq: ureq::AgentBuilder::new()
+                .timeout_connect(DEFAULT_NETWORK_TIMEOUT)
+                .timeout_read(DEFAULT_NETWORK_TIMEOUT)
+                .build(),
+            mirrors: Default::default(),
+            user_agent: None,
+            request_hook: None,
+        }
+    }
+}
+
+impl RegistryClient {
+    pub(crate) fn with_mirror(mut self, mirror: PackageMirror) -> Self {
+        self.mirrors.push(mirror);
+        self
+    }
+
+    pub(crate) fn with_user_agent<S>(mut self, user_agent: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub(crate) fn with_request_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(ureq::Request) -> ureq::Request + Send + Sync + 'static,
+    {
+        self.request_hook = Some(RequestHook(Arc::new(hook)));
+        self
+    }
+
+    /// `mirrors` or the default registry, if there are none.
+    fn mirrors(&self) -> Cow<'_, [PackageMirror]> {
+        if self.mirrors.is_empty() {
+            Cow::Owned(vec![PackageMirror::typst()])
+        } else {
+            Cow::Borrowed(&self.mirrors)
+        }
+    }
+
+    fn get(&self, mirror: &PackageMirror, path: &str) -> ureq::Request {
+        let Self {
+            ureq,
+            user_agent,
+            request_hook,
+            ..
+        } = self;
+        let PackageMirror {
+            url,
+            ureq: mirror_ureq,
+            authorization,
+        } = mirror;
+        let mut request = mirror_ureq
+            .as_ref()
+            .unwrap_or(ureq)
+            .get(&format!("{url}/{path}"));
+        if let Some(user_agent) = user_agent {
+            request = request.set("User-Agent", user_agent);
+        }
+        if let Some(authorization) = authorization {
+            request = request.set("Authorization", authorization);
+        }
+        match request_hook {
+            Some(RequestHook(hook)) => hook(request),
+            None => request,
+        }
+    }
 }
 
-fn compose_cache_file_path(root: &Path, package: &PackageSpec) -> FileResult<PathBuf> {
+/// Query the index of the `preview` namespace of the package repositories for the latest
+/// version of the package. The first mirror, that serves an index, is used.
+pub(crate) fn resolve_latest(
+    client: &RegistryClient,
+    name: &str,
+) -> Result<PackageSpec, PackageError> {
+    if cfg!(feature = "no-network") {
+        return Err(network_disabled());
+    }
+    // https://github.com/typst/typst/blob/16736feb13eec87eb9ca114deaeb4f7eeb7409d2/crates/typst-kit/src/package.rs#L102C16-L102C38
+    let namespace = "preview";
+    let mut last_error = eco_format!("");
+    let mut index = None;
+    for mirror in client.mirrors().iter() {
+        let response = match client
+            .get(mirror, &format!("{namespace}/index.json"))
+            .call()
+        {
+            Ok(response) => response,
+            Err(error) => {
+                last_error = eco_format!("{error}");
+                continue;
+            }
+        };
+        let reader = CancellableReader::new(response.into_reader());
+        match serde_json::from_reader::<_, Vec<serde_json::Value>>(reader) {
+            Ok(value) => {
+                index = Some(value);
+                break;
+            }
+            Err(error) => last_error = eco_format!("invalid package index: {error}"),
+        }
+    }
+    let index = index.ok_or_else(|| PackageError::NetworkFailed(Some(last_error)))?;
+    let version = index
+        .iter()
+        .filter(|package| package["name"].as_str() == Some(name))
+        .filter_map(|package| PackageVersion::from_str(package["version"].as_str()?).ok())
+        .max()
+        .ok_or_else(|| {
+            PackageError::Other(Some(eco_format!(
+                "package @{namespace}/{name} not found in the package index"
+            )))
+        })?;
+    Ok(PackageSpec {
+        namespace: namespace.into(),
+        name: name.into(),
+        version,
+    })
+}
+
+/// HTTP validators (`ETag`, `Last-Modified`) of a downloaded package, that are sent with
+/// conditional requests, when the package is revalidated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CacheValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    fn from_response(response: &ureq::Response) -> Option<Self> {
+        let validators = Self {
+            etag: response.header("ETag").map(ToOwned::to_owned),
+            last_modified: response.header("Last-Modified").map(ToOwned::to_owned),
+        };
+        (validators != Self::default()).then_some(validators)
+    }
+
+    /// One `<header>\t<value>` line per validator.
+    fn to_text(&self) -> String {
+        let Self {
+            etag,
+            last_modified,
+        } = self;
+        let mut text = String::new();
+        if let Some(etag) = etag {
+            text.push_str(&format!("etag\t{etag}\n"));
+        }
+        if let Some(last_modified) = last_modified {
+            text.push_str(&format!("last-modified\t{last_modified}\n"));
+        }
+        text
+    }
+
+    fn from_text(text: &str) -> Self {
+        let mut validators = Self::default();
+        for line in text.lines() {
+            match line.split_once('\t') {
+                Some(("etag", etag)) => validators.etag = Some(etag.to_owned()),
+                Some(("last-modified", last_modified)) => {
+                    validators.last_modified = Some(last_modified.to_owned())
+                }
+                _ => (),
+            }
+        }
+        validators
+    }
+
+    fn apply(&self, request: ureq::Request) -> ureq::Request {
+        let Self {
+            etag,
+            last_modified,
+        } = self;
+        let request = match etag {
+            Some(etag) => request.set("If-None-Match", etag),
+            None => request,
+        };
+        match last_modified {
+            Some(last_modified) => request.set("If-Modified-Since", last_modified),
+            None => request,
+        }
+    }
+}
+
+/// Download the package from the first package repository, that serves it, and decompress
+/// it. Every mirror is tried `REQUEST_RETRY_COUNT` times. Returns the tar archive.
+pub(crate) fn download_package(
+    client: &RegistryClient,
+    package: &PackageSpec,
+) -> FileResult<Vec<u8>> {
+    let (archive, _) = fetch_package(client, package, None)?
+        .ok_or_else(|| PackageError::NetworkFailed(Some(eco_format!("package not modified"))))?;
+    Ok(archive)
+}
+
+/// Like `download_package()`, but sends a conditional request, if there are `validators`.
+/// Returns `None`, if the package was not modified, otherwise the tar archive and the new
+/// validators.
+pub(crate) fn fetch_package(
+    client: &RegistryClient,
+    package: &PackageSpec,
+    validators: Option<&CacheValidators>,
+) -> FileResult<Option<(Vec<u8>, Option<CacheValidators>)>> {
+    let PackageSpec {
+        namespace,
+        name,
+        version,
+    } = package;
+    let path = format!("{namespace}/{name}-{version}.tar.gz");
+    if cfg!(feature = "no-network") {
+        return Err(network_disabled().into());
+    }
+
+    let start = Instant::now();
+    let mut last_error = eco_format!("");
+    let mut response = None;
+    'mirrors: for mirror in client.mirrors().iter() {
+        instrument::package_download_started(package, &format!("{}/{path}", mirror.url()));
+        for attempt in 0..REQUEST_RETRY_COUNT {
+            if CancellationToken::is_current_cancelled() {
+                return Err(cancelled().into());
+            }
+            let mut request = client.get(mirror, &path);
+            if let Some(validators) = validators {
+                request = validators.apply(request);
+            }
+            let resp = match request.call() {
+                Ok(resp) => resp,
+                Err(error) => {
+                    last_error = eco_format!("{error}");
+                    instrument::package_download_failed(package, attempt, &last_error);
+                    continue;
+                }
+            };
+
+            let status = resp.status();
+            if status == 304 && validators.is_some() {
+                return Ok(None);
+            }
+            if status != 200 {
+                last_error = eco_format!("response returned unsuccessful status code {status}");
+                instrument::package_download_failed(package, attempt, &last_error);
+                continue;
+            }
+            response = Some(resp);
+            break 'mirrors;
+        }
+    }
+    let response = response.ok_or_else(|| PackageError::NetworkFailed(Some(last_error)))?;
+    let validators = CacheValidators::from_response(&response);
+
+    let mut d = GzDecoder::new(CancellableReader::new(response.into_reader()));
+    let mut archive = Vec::new();
+    d.read_to_end(&mut archive).map_err(|error| {
+        if CancellationToken::is_current_cancelled() {
+            cancelled()
+        } else {
+            PackageError::MalformedArchive(Some(eco_format!("{error}")))
+        }
+    })?;
+
+    instrument::package_downloaded(package, start.elapsed());
+    Ok(Some((archive, validators)))
+}
+
+fn cancelled() -> PackageError {
+    PackageError::NetworkFailed(Some(eco_format!("download cancelled")))
+}
+
+fn network_disabled() -> PackageError {
+    PackageError::NetworkFailed(Some(eco_format!(
+        "network access is disabled by feature `no-network`"
+    )))
+}
+
+pub(crate) fn compose_cache_file_path(root: &Path, package: &PackageSpec) -> FileResult<PathBuf> {
     let subdir = Path::new(package.namespace.as_str())
         .join(package.name.as_str())
         .join(package.version.to_string());
@@ -168,11 +843,29 @@ fn compose_cache_file_path(root: &Path, package: &PackageSpec) -> FileResult<Pat
     Ok(root.join(subdir))
 }
 
-trait PackageResolverCache {
+pub(crate) trait PackageResolverCache {
     fn lookup_cached<T>(&self, package: &PackageSpec, id: FileId) -> FileResult<Option<T>>
     where
         SourceOrBytesCreator: CreateBytesOrSource<T>;
     fn cache_archive(&self, archive: Archive<&[u8]>, package: &PackageSpec) -> FileResult<()>;
+
+    /// Validators of the cached package, if the cache stores them.
+    fn lookup_validators(&self, _package: &PackageSpec) -> Option<CacheValidators> {
+        None
+    }
+
+    fn cache_validators(&self, _package: &PackageSpec, _validators: &CacheValidators) {}
+
+    /// Whether the package is complete in a read-only part of the cache, so it is never
+    /// downloaded.
+    fn is_read_only(&self, _package: &PackageSpec) -> bool {
+        false
+    }
+
+    /// Approximate bytes, that the cache holds in memory.
+    fn memory_footprint(&self) -> usize {
+        0
+    }
 }
 
 /// File system cache with given path
@@ -182,13 +875,19 @@ pub struct FileSystemCache(pub PathBuf);
 impl FileSystemCache {
     pub fn new() -> Self {
         let cache_dir = dirs::cache_dir()
-            .map(|p| Cow::Owned(p))
+            .map(Cow::Owned)
             .unwrap_or_else(|| Cow::Borrowed(Path::new(".")));
         let path = cache_dir.join(DEFAULT_PACKAGES_SUBDIR);
         Self(path)
     }
 }
 
+impl Default for FileSystemCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PackageResolverCache for FileSystemCache {
     fn lookup_cached<T>(&self, package: &PackageSpec, id: FileId) -> FileResult<Option<T>>
     where
@@ -200,29 +899,289 @@ impl PackageResolverCache for FileSystemCache {
         let Some(path) = id.vpath().resolve(&dir) else {
             return Ok(None);
         };
-        let content = std::fs::read(&path).map_err(|error| FileError::from_io(error, &path))?;
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                // Packages, that were cached by older versions, may be partially extracted.
+                if dir.exists() && !is_complete_package(&dir) {
+                    let _ = std::fs::remove_dir_all(&dir);
+                    return Ok(None);
+                }
+                return Err(FileError::from_io(error, &path));
+            }
+            Err(error) => return Err(FileError::from_io(error, &path)),
+        };
         let cached = SourceOrBytesCreator.try_create(id, &content)?;
         Ok(Some(cached))
     }
 
-    fn cache_archive(&self, mut archive: Archive<&[u8]>, package: &PackageSpec) -> FileResult<()> {
+    fn cache_archive(&self, archive: Archive<&[u8]>, package: &PackageSpec) -> FileResult<()> {
         let FileSystemCache(path) = self;
         let dir = compose_cache_file_path(path, package)?;
-        std::fs::create_dir_all(&dir).map_err(|error| FileError::from_io(error, &dir))?;
-        archive
-            .unpack(&dir)
-            .map_err(|error| FileError::from_io(error, &dir))?;
-        Ok(())
+        unpack_atomically(archive, &dir)
+    }
+
+    fn lookup_validators(&self, package: &PackageSpec) -> Option<CacheValidators> {
+        let FileSystemCache(path) = self;
+        let path = validators_file_path(path, package).ok()?;
+        let text = std::fs::read_to_string(path).ok()?;
+        Some(CacheValidators::from_text(&text))
+    }
+
+    fn cache_validators(&self, package: &PackageSpec, validators: &CacheValidators) {
+        let FileSystemCache(path) = self;
+        if let Ok(path) = validators_file_path(path, package) {
+            let _ = std::fs::write(path, validators.to_text());
+        }
+    }
+}
+
+/// Unpack the archive into a temporary directory next to `dir` and rename it to `dir`, so
+/// `dir` never contains a partially extracted package, e.g. after a crash. An existing `dir`
+/// is replaced.
+pub(crate) fn unpack_atomically(mut archive: Archive<&[u8]>, dir: &Path) -> FileResult<()> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.subsec_nanos());
+    let mut tmp_dir = dir.as_os_str().to_owned();
+    tmp_dir.push(format!(".tmp-{}-{nanos}", std::process::id()));
+    let tmp_dir = PathBuf::from(tmp_dir);
+    let result = std::fs::create_dir_all(&tmp_dir)
+        .and_then(|()| archive.unpack(&tmp_dir))
+        .map_err(|error| FileError::from_io(error, &tmp_dir))
+        .and_then(|()| replace_dir(&tmp_dir, dir));
+    if result.is_err() {
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+    }
+    result
+}
+
+/// Move an existing `to` aside before renaming `from` to `to` and delete it afterwards, so `to`
+/// is only missing between the two renames, e.g. for readers of a package, that is
+/// revalidated.
+fn replace_dir(from: &Path, to: &Path) -> FileResult<()> {
+    let old = to.exists().then(|| {
+        let mut old = from.as_os_str().to_owned();
+        old.push(".old");
+        PathBuf::from(old)
+    });
+    if let Some(old) = &old {
+        std::fs::rename(to, old).map_err(|error| FileError::from_io(error, to))?;
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => {
+            if let Some(old) = &old {
+                let _ = std::fs::remove_dir_all(old);
+            }
+            Ok(())
+        }
+        // Another process cached the package in the meantime.
+        Err(_) if is_complete_package(to) => {
+            let _ = std::fs::remove_dir_all(from);
+            if let Some(old) = &old {
+                let _ = std::fs::remove_dir_all(old);
+            }
+            Ok(())
+        }
+        Err(error) => {
+            if let Some(old) = &old {
+                let _ = std::fs::rename(old, to);
+            }
+            Err(FileError::from_io(error, to))
+        }
+    }
+}
+
+/// Whether the `typst.toml` and the entrypoint of the package exist.
+fn is_complete_package(dir: &Path) -> bool {
+    let Ok(manifest) = std::fs::read_to_string(dir.join("typst.toml")) else {
+        return false;
+    };
+    let entrypoint = manifest.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "entrypoint").then(|| value.trim().trim_matches('"').to_owned())
+    });
+    entrypoint.is_none_or(|entrypoint| dir.join(entrypoint).is_file())
+}
+
+/// `<root>/<namespace>/<name>/<version>.validators`, next to the directory of the package.
+fn validators_file_path(root: &Path, package: &PackageSpec) -> FileResult<PathBuf> {
+    let dir = compose_cache_file_path(root, package)?;
+    Ok(dir.with_file_name(format!("{}.validators", package.version)))
+}
+
+/// A read-only package cache directory, e.g. baked into a container image, with a writable
+/// overlay cache for packages, that are not in the base. Packages are never written to the
+/// base. Packages in the base are expected to be complete: they are served from the base only,
+/// without downloads. `FileSystemCache` or `InMemoryCache` can be used as overlay.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::package_resolver::PackageResolverBuilder;
+/// let resolver = PackageResolverBuilder::new()
+///     .with_layered_cache("/opt/typst/packages".into(), "/tmp/typst/packages".into())
+///     .build();
+/// ```
+pub struct LayeredCache<C> {
+    pub base: PathBuf,
+    pub overlay: C,
+}
+
+impl<C> LayeredCache<C> {
+    pub fn new(base: PathBuf, overlay: C) -> Self {
+        Self { base, overlay }
+    }
+
+    /// The directory of the package in the base, if it is there.
+    fn base_dir(&self, package: &PackageSpec) -> FileResult<Option<PathBuf>> {
+        let dir = compose_cache_file_path(&self.base, package)?;
+        Ok(dir.is_dir().then_some(dir))
+    }
+}
+
+impl<C> PackageResolverCache for LayeredCache<C>
+where
+    C: PackageResolverCache,
+{
+    fn lookup_cached<T>(&self, package: &PackageSpec, id: FileId) -> FileResult<Option<T>>
+    where
+        SourceOrBytesCreator: CreateBytesOrSource<T>,
+    {
+        let Some(dir) = self.base_dir(package)? else {
+            return self.overlay.lookup_cached(package, id);
+        };
+        // Packages in the base are complete, see `is_read_only()`.
+        let path = id.vpath().resolve(&dir).ok_or_else(|| not_found(id))?;
+        let content = std::fs::read(&path).map_err(|error| FileError::from_io(error, &path))?;
+        SourceOrBytesCreator.try_create(id, &content).map(Some)
+    }
+
+    fn cache_archive(&self, archive: Archive<&[u8]>, package: &PackageSpec) -> FileResult<()> {
+        self.overlay.cache_archive(archive, package)
+    }
+
+    fn is_read_only(&self, package: &PackageSpec) -> bool {
+        matches!(self.base_dir(package), Ok(Some(_)))
+    }
+
+    fn lookup_validators(&self, package: &PackageSpec) -> Option<CacheValidators> {
+        self.overlay.lookup_validators(package)
+    }
+
+    fn cache_validators(&self, package: &PackageSpec, validators: &CacheValidators) {
+        self.overlay.cache_validators(package, validators)
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.overlay.memory_footprint()
     }
 }
 
 /// In memory cache
+#[derive(Clone, Default)]
 pub struct InMemoryCache(pub Arc<Mutex<HashMap<FileId, Vec<u8>>>>);
 
 impl InMemoryCache {
     pub fn new() -> Self {
         Self(Default::default())
     }
+
+    /// Write the cached packages into a tar archive at `path`, e.g. to keep them across
+    /// invocations of a short-lived process. Files are stored as
+    /// `<namespace>/<name>/<version>/<path>`. Restore them with `InMemoryCache::load_from()`.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use typst_as_lib::package_resolver::{InMemoryCache, PackageResolverBuilder};
+    /// # static SNAPSHOT: &str = "/var/cache/typst-packages.snapshot";
+    /// let cache = InMemoryCache::load_from(SNAPSHOT).unwrap_or_else(|_| InMemoryCache::new());
+    /// let resolver = PackageResolverBuilder::new().set_cache(cache.clone()).build();
+    /// // ... compile ...
+    /// cache.save_to(SNAPSHOT)?;
+    /// # Ok::<(), typst::diag::FileError>(())
+    /// ```
+    pub fn save_to<P>(&self, path: P) -> FileResult<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let InMemoryCache(cache) = self;
+        let cache = lock_cache(cache);
+        let mut builder = Builder::new(Vec::new());
+        for (id, data) in cache.iter() {
+            let Some(package) = id.package() else {
+                continue;
+            };
+            let PackageSpec {
+                namespace,
+                name,
+                version,
+            } = package;
+            let entry_path = Path::new(namespace.as_str())
+                .join(name.as_str())
+                .join(version.to_string())
+                .join(id.vpath().as_rootless_path());
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, entry_path, data.as_slice())
+                .map_err(|error| FileError::from_io(error, path))?;
+        }
+        drop(cache);
+        let archive = builder
+            .into_inner()
+            .map_err(|error| FileError::from_io(error, path))?;
+
+        write_atomically(path, &archive).map_err(|error| FileError::from_io(error, path))
+    }
+
+    /// Read the packages, that were written with `InMemoryCache::save_to()`.
+    pub fn load_from<P>(path: P) -> FileResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let archive = std::fs::read(path).map_err(|error| FileError::from_io(error, path))?;
+        let mut archive = Archive::new(&archive[..]);
+        let malformed = |error: std::io::Error| {
+            PackageError::MalformedArchive(Some(eco_format!("{}: {error}", path.display())))
+        };
+        let mut files = HashMap::new();
+        for entry in archive.entries().map_err(malformed)? {
+            let mut entry = entry.map_err(malformed)?;
+            let entry_path = entry.path().map_err(malformed)?.into_owned();
+            let id = snapshot_file_id(&entry_path).ok_or_else(|| {
+                PackageError::MalformedArchive(Some(eco_format!(
+                    "{}: invalid entry {}",
+                    path.display(),
+                    entry_path.display()
+                )))
+            })?;
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(malformed)?;
+            files.insert(id, data);
+        }
+        Ok(Self(Arc::new(Mutex::new(files))))
+    }
+}
+
+/// `<namespace>/<name>/<version>/<path>` -> `FileId`
+fn snapshot_file_id(path: &Path) -> Option<FileId> {
+    let mut components = path.components();
+    let mut next = || components.next()?.as_os_str().to_str();
+    let namespace = next()?;
+    let name = next()?;
+    let version = PackageVersion::from_str(next()?).ok()?;
+    let package = PackageSpec {
+        namespace: namespace.into(),
+        name: name.into(),
+        version,
+    };
+    Some(FileId::new(
+        Some(package),
+        VirtualPath::new(components.as_path()),
+    ))
 }
 
 impl PackageResolverCache for InMemoryCache {
@@ -231,10 +1190,7 @@ impl PackageResolverCache for InMemoryCache {
         SourceOrBytesCreator: CreateBytesOrSource<T>,
     {
         let InMemoryCache(cache) = self;
-        let mutex_guard = cache
-            .as_ref()
-            .lock()
-            .map_err(|_| FileError::Other(Some(eco_format!("Could not lock cache"))))?;
+        let mutex_guard = lock_cache(cache);
         let cached = if let Some(value) = mutex_guard.get(&id) {
             let cached = SourceOrBytesCreator.try_create(id, value)?;
             Some(cached)
@@ -262,18 +1218,20 @@ impl PackageResolverCache for InMemoryCache {
             let Ok(_) = file.read_to_end(&mut buf) else {
                 continue;
             };
-            let mut mutex_guard = cache
-                .lock()
-                .map_err(|_| FileError::Other(Some(eco_format!("Could not lock cache"))))?;
-            mutex_guard.insert(file_id, buf);
+            lock_cache(cache).insert(file_id, buf);
         }
         Ok(())
     }
+
+    fn memory_footprint(&self) -> usize {
+        let InMemoryCache(cache) = self;
+        lock_cache(cache).values().map(|file| file.len()).sum()
+    }
 }
 
-struct SourceOrBytesCreator;
+pub(crate) struct SourceOrBytesCreator;
 
-trait CreateBytesOrSource<T> {
+pub(crate) trait CreateBytesOrSource<T> {
     fn try_create(&self, id: FileId, value: &[u8]) -> FileResult<T>;
 }
 
@@ -303,3 +1261,170 @@ impl IntoCachedFileResolver for PackageResolver<FileSystemCache> {
             .with_in_memory_binary_cache()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn package() -> PackageSpec {
+        PackageSpec::from_str("@preview/example:0.1.0").unwrap()
+    }
+
+    /// Tar archive of `package()`.
+    fn archive() -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        let files: [(&str, &[u8]); 2] = [
+            ("typst.toml", b"[package]\nentrypoint = \"lib.typ\"\n"),
+            ("lib.typ", b"#let greet = \"Hello\""),
+        ];
+        for (path, content) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// Serves `archive()` gzipped for every request and returns the url and the number of
+    /// requests. Each response takes a while, so concurrent resolutions overlap.
+    #[cfg(not(feature = "no-network"))]
+    fn serve_package() -> (String, Arc<AtomicU64>) {
+        use std::io::Write;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicU64::new(0));
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        gzipped.write_all(&archive()).unwrap();
+        let gzipped = gzipped.finish().unwrap();
+        std::thread::spawn({
+            let requests = Arc::clone(&requests);
+            move || {
+                for mut stream in listener.incoming().flatten() {
+                    requests.fetch_add(1, Ordering::Relaxed);
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buf) {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                    let header = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        gzipped.len()
+                    );
+                    let _ = stream.write_all(header.as_bytes());
+                    let _ = stream.write_all(&gzipped);
+                }
+            }
+        });
+        (url, requests)
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-network"))]
+    fn concurrent_resolutions_download_once() {
+        let (url, requests) = serve_package();
+        let resolver = PackageResolverBuilder::new()
+            .with_mirror(PackageMirror::new(url))
+            .with_in_memory_cache()
+            .build();
+        let id = FileId::new(Some(package()), VirtualPath::new("lib.typ"));
+        std::thread::scope(|scope| {
+            let resolutions: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| resolver.resolve_source(id).is_ok()))
+                .collect();
+            for resolution in resolutions {
+                assert!(resolution.join().unwrap());
+            }
+        });
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+        assert_eq!(resolver.stats().packages_downloaded, 1);
+    }
+
+    #[test]
+    fn cancelled_wait_for_download_fails() {
+        let resolver = PackageResolverBuilder::new().with_in_memory_cache().build();
+        let _download = resolver.lock_download(&package()).unwrap();
+
+        let token = CancellationToken::new();
+        let waiting = std::thread::scope(|scope| {
+            let waiting = scope.spawn(|| token.run(|| resolver.lock_download(&package()).err()));
+            std::thread::sleep(CANCELLATION_POLL_INTERVAL);
+            token.cancel();
+            waiting.join().unwrap()
+        });
+        assert!(waiting.is_some());
+    }
+
+    #[test]
+    fn wait_for_download_ends_with_download() {
+        let resolver = PackageResolverBuilder::new().with_in_memory_cache().build();
+        let download = resolver.lock_download(&package()).unwrap();
+
+        std::thread::scope(|scope| {
+            let waiting = scope.spawn(|| resolver.lock_download(&package()).is_ok());
+            std::thread::sleep(CANCELLATION_POLL_INTERVAL);
+            drop(download);
+            assert!(waiting.join().unwrap());
+        });
+    }
+
+    /// Names of the entries of the directory, e.g. to find leftover temporary directories.
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut entries: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn unpack_replaces_existing_package() {
+        let root = temp_dir("unpack-replace");
+        let dir = root.join("0.1.0");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("stale.typ"), "").unwrap();
+
+        unpack_atomically(Archive::new(&archive()[..]), &dir).unwrap();
+        assert!(is_complete_package(&dir));
+        assert_eq!(entries(&dir), ["lib.typ", "typst.toml"]);
+        assert_eq!(entries(&root), ["0.1.0"]);
+    }
+
+    #[test]
+    fn failed_unpack_keeps_existing_package() {
+        let root = temp_dir("unpack-failed");
+        let dir = root.join("0.1.0");
+        unpack_atomically(Archive::new(&archive()[..]), &dir).unwrap();
+
+        let archive = archive();
+        let truncated = &archive[..archive.len() / 2];
+        assert!(unpack_atomically(Archive::new(truncated), &dir).is_err());
+        assert!(is_complete_package(&dir));
+        assert_eq!(entries(&root), ["0.1.0"]);
+    }
+
+    #[test]
+    fn partial_package_is_removed() {
+        let root = temp_dir("partial-package");
+        let cache = FileSystemCache(root.clone());
+        let dir = compose_cache_file_path(&root, &package()).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("typst.toml"),
+            "[package]\nentrypoint = \"lib.typ\"\n",
+        )
+        .unwrap();
+
+        let id = FileId::new(Some(package()), VirtualPath::new("lib.typ"));
+        let cached: Option<Bytes> = cache.lookup_cached(&package(), id).unwrap();
+        assert!(cached.is_none());
+        assert!(!dir.exists());
+    }
+}
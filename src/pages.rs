@@ -0,0 +1,255 @@
+use std::ops::RangeInclusive;
+
+use typst::layout::{Frame, FrameItem, Page, Point, Size};
+use typst::model::Document;
+
+/// Pages of a document, that are exported. Page numbers are 1-based.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::pages::PageSelection;
+/// let first_three = PageSelection::from(1..=3);
+/// let thumbnail = PageSelection::First;
+/// let summary = PageSelection::Last;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum PageSelection {
+    #[default]
+    All,
+    First,
+    Last,
+    /// Inclusive ranges of page numbers. Pages, that don't exist, are ignored.
+    Ranges(Vec<RangeInclusive<usize>>),
+}
+
+impl PageSelection {
+    pub fn is_all(&self) -> bool {
+        matches!(self, PageSelection::All)
+    }
+
+    /// Zero-based indices of the selected pages in a document with `page_count` pages, in
+    /// ascending order.
+    pub fn indices(&self, page_count: usize) -> Vec<usize> {
+        match self {
+            PageSelection::All => (0..page_count).collect(),
+            PageSelection::First => (0..page_count.min(1)).collect(),
+            PageSelection::Last => page_count.checked_sub(1).into_iter().collect(),
+            PageSelection::Ranges(ranges) => (0..page_count)
+                .filter(|index| ranges.iter().any(|range| range.contains(&(index + 1))))
+                .collect(),
+        }
+    }
+
+    /// Selected pages of the document.
+    pub fn select<'a>(&self, document: &'a Document) -> Vec<&'a Page> {
+        self.indices(document.pages.len())
+            .into_iter()
+            .map(|index| &document.pages[index])
+            .collect()
+    }
+
+    /// Apply `f` to the selected pages with their zero-based index, in the order of the pages.
+    /// With feature `rayon`, the pages are processed in parallel on rayon's global thread pool.
+    #[cfg(any(feature = "render", feature = "svg"))]
+    pub(crate) fn map_pages<T, F>(&self, document: &Document, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize, &Page) -> T + Send + Sync,
+    {
+        let indices = self.indices(document.pages.len());
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            indices
+                .into_par_iter()
+                .map(|index| f(index, &document.pages[index]))
+                .collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            indices
+                .into_iter()
+                .map(|index| f(index, &document.pages[index]))
+                .collect()
+        }
+    }
+
+    /// `None`, if all pages are selected.
+    #[cfg(feature = "pdf")]
+    pub(crate) fn to_page_ranges(&self, page_count: usize) -> Option<typst::layout::PageRanges> {
+        if self.is_all() {
+            return None;
+        }
+        let ranges = self
+            .indices(page_count)
+            .into_iter()
+            .map(|index| {
+                let number = std::num::NonZeroUsize::new(index + 1);
+                number..=number
+            })
+            .collect();
+        Some(typst::layout::PageRanges::new(ranges))
+    }
+}
+
+impl From<RangeInclusive<usize>> for PageSelection {
+    fn from(value: RangeInclusive<usize>) -> Self {
+        PageSelection::Ranges(vec![value])
+    }
+}
+
+impl From<usize> for PageSelection {
+    fn from(value: usize) -> Self {
+        PageSelection::Ranges(vec![value..=value])
+    }
+}
+
+impl From<Vec<RangeInclusive<usize>>> for PageSelection {
+    fn from(value: Vec<RangeInclusive<usize>>) -> Self {
+        PageSelection::Ranges(value)
+    }
+}
+
+/// A rectangle on a page in points (1/72 inch), measured from the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub(crate) fn from_bounds(min: Point, max: Point) -> Self {
+        Self {
+            x: min.x.to_pt(),
+            y: min.y.to_pt(),
+            width: (max.x - min.x).to_pt(),
+            height: (max.y - min.y).to_pt(),
+        }
+    }
+}
+
+/// Geometry of a page of a compiled document. See `page_geometry()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageGeometry {
+    /// 1-based page number, as in `PageSelection`.
+    pub number: usize,
+    /// Width of the page in points.
+    pub width: f64,
+    /// Height of the page in points.
+    pub height: f64,
+    /// Bounding box of everything on the page (text, shapes and images), `None` for an empty
+    /// page.
+    pub content_bounds: Option<Rect>,
+    /// Bounding boxes of the frames on the page, e.g. of boxes and blocks, that typst keeps as
+    /// separate frames, in the order, in which they were laid out.
+    pub frames: Vec<Rect>,
+}
+
+impl PageGeometry {
+    /// Space between the content and the edges of the page: `[top, right, bottom, left]` in
+    /// points. Typst doesn't keep the configured margins in the document, so these are the
+    /// margins, that the content actually leaves free. `None` for an empty page.
+    pub fn content_insets(&self) -> Option<[f64; 4]> {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = self.content_bounds?;
+        Some([y, self.width - x - width, self.height - y - height, x])
+    }
+}
+
+/// Size and bounding boxes of each page of the document, e.g. to place stamps or overlays
+/// after the compilation without parsing the exported PDF.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::pages;
+/// # use typst_as_lib::TypstTemplate;
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], "= Invoice");
+/// let doc = template.compile().output?;
+/// for page in pages::page_geometry(&doc) {
+///     let [_, _, bottom, _] = page.content_insets().unwrap_or_default();
+///     println!("page {}: {} pt free at the bottom", page.number, bottom);
+/// }
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub fn page_geometry(document: &Document) -> Vec<PageGeometry> {
+    document
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| {
+            let Size {
+                x: width,
+                y: height,
+            } = page.frame.size();
+            let mut bounds = Bounds::default();
+            bounds.add_frame(&page.frame, Point::zero(), true);
+            let Bounds { min_max, frames } = bounds;
+            PageGeometry {
+                number: index + 1,
+                width: width.to_pt(),
+                height: height.to_pt(),
+                content_bounds: min_max.map(|(min, max)| Rect::from_bounds(min, max)),
+                frames,
+            }
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Bounds {
+    min_max: Option<(Point, Point)>,
+    frames: Vec<Rect>,
+}
+
+impl Bounds {
+    fn add_frame(&mut self, frame: &Frame, offset: Point, top_level: bool) {
+        for (pos, item) in frame.items() {
+            let pos = offset + *pos;
+            match item {
+                FrameItem::Group(group) => {
+                    // Transformations (e.g. rotations) are ignored, only the position counts.
+                    if top_level {
+                        self.frames
+                            .push(Rect::from_bounds(pos, pos + group.frame.size().to_point()));
+                    }
+                    self.add_frame(&group.frame, pos, false);
+                }
+                FrameItem::Text(text) => {
+                    let metrics = text.font.metrics();
+                    let ascender = metrics.ascender.at(text.size);
+                    let descender = metrics.descender.at(text.size);
+                    self.add(
+                        Point::new(pos.x, pos.y - ascender),
+                        Point::new(pos.x + text.width(), pos.y - descender),
+                    );
+                }
+                FrameItem::Shape(shape, _) => {
+                    self.add(pos, pos + shape.geometry.bbox_size().to_point());
+                }
+                FrameItem::Image(_, size, _) => self.add(pos, pos + size.to_point()),
+                FrameItem::Link(..) | FrameItem::Tag(_) => (),
+            }
+        }
+    }
+
+    fn add(&mut self, min: Point, max: Point) {
+        let (min, max) = (
+            Point::new(min.x.min(max.x), min.y.min(max.y)),
+            Point::new(min.x.max(max.x), min.y.max(max.y)),
+        );
+        self.min_max = Some(match self.min_max {
+            None => (min, max),
+            Some((old_min, old_max)) => (
+                Point::new(old_min.x.min(min.x), old_min.y.min(min.y)),
+                Point::new(old_max.x.max(max.x), old_max.y.max(max.y)),
+            ),
+        });
+    }
+}
@@ -0,0 +1,128 @@
+use typst::syntax::{FileId, VirtualPath};
+
+use crate::{TypstTemplate, TypstTemplateCollection};
+
+/// How virtual paths are normalized, when no file resolver can resolve a file as requested.
+/// `./` and duplicate slashes are always normalized by typst's `VirtualPath`.
+///
+/// Templates authored on Windows often contain backslashes or a different case than the
+/// files on disk, which only works on case insensitive file systems.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::paths::PathNormalization;
+/// # use typst_as_lib::TypstTemplate;
+/// # static source: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// let template = TypstTemplate::new(fonts, source)
+///     .with_path_normalization(PathNormalization::new().with_case_insensitive(true));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathNormalization {
+    backslashes: bool,
+    case_insensitive: bool,
+}
+
+impl Default for PathNormalization {
+    fn default() -> Self {
+        Self {
+            backslashes: true,
+            case_insensitive: false,
+        }
+    }
+}
+
+impl PathNormalization {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Treat backslashes as path separators (default: `true`).
+    pub fn with_backslashes(self, backslashes: bool) -> Self {
+        Self {
+            backslashes,
+            ..self
+        }
+    }
+
+    /// Match paths regardless of their case (default: `false`). Only file resolvers, that list
+    /// their files (see `FileResolver::file_ids()`), can be matched this way. For
+    /// `FileSystemResolver`, use `FileSystemResolver::with_case_insensitive_paths()`.
+    pub fn with_case_insensitive(self, case_insensitive: bool) -> Self {
+        Self {
+            case_insensitive,
+            ..self
+        }
+    }
+
+    /// Normalized `id` or `None`, if it doesn't change. `known` are the files, that are
+    /// matched case insensitively.
+    pub(crate) fn normalize<I>(&self, id: FileId, known: I) -> Option<FileId>
+    where
+        I: IntoIterator<Item = FileId>,
+    {
+        let Self {
+            backslashes,
+            case_insensitive,
+        } = *self;
+        let path = id.vpath().as_rootless_path().to_string_lossy();
+        let path = if backslashes {
+            path.replace('\\', "/")
+        } else {
+            path.into_owned()
+        };
+        let mut normalized = FileId::new(id.package().cloned(), VirtualPath::new(&path));
+        if case_insensitive {
+            let path = normalized.vpath().as_rootless_path().to_string_lossy();
+            if let Some(matched) = known.into_iter().find(|known| {
+                known.package() == id.package()
+                    && known
+                        .vpath()
+                        .as_rootless_path()
+                        .to_string_lossy()
+                        .to_lowercase()
+                        == path.to_lowercase()
+            }) {
+                normalized = matched;
+            }
+        }
+        (normalized != id).then_some(normalized)
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Normalize virtual paths, when files can't be resolved as requested. See
+    /// `PathNormalization`.
+    pub fn with_path_normalization(mut self, path_normalization: PathNormalization) -> Self {
+        self.with_path_normalization_mut(path_normalization);
+        self
+    }
+
+    /// Normalize virtual paths, when files can't be resolved as requested. See
+    /// `PathNormalization`.
+    pub fn with_path_normalization_mut(&mut self, path_normalization: PathNormalization) {
+        self.path_normalization = Some(path_normalization);
+    }
+
+    /// The id, that is resolved instead of `id`, if `id` can't be resolved.
+    pub(crate) fn normalized_file_id(&self, id: FileId) -> Option<FileId> {
+        let path_normalization = self.path_normalization.as_ref()?;
+        path_normalization.normalize(
+            id,
+            self.file_resolvers
+                .iter()
+                .flat_map(|file_resolver| file_resolver.file_ids()),
+        )
+    }
+}
+
+impl TypstTemplate {
+    /// Normalize virtual paths, when files can't be resolved as requested. See
+    /// `PathNormalization`.
+    pub fn with_path_normalization(mut self, path_normalization: PathNormalization) -> Self {
+        self.collection
+            .with_path_normalization_mut(path_normalization);
+        self
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+use typst::diag::Warned;
+use typst::foundations::{Dict, Smart};
+use typst::layout::{Frame, FrameItem};
+use typst::model::Document;
+use typst::text::Font;
+use typst::utils::hash128;
+use typst_pdf::{PdfOptions, PdfStandards};
+
+use crate::pages::PageSelection;
+use crate::pdf_attachments::{embed_attachments, FacturX, PdfAttachment};
+use crate::pdf_metadata::PdfMetadata;
+use crate::pdf_update::PdfUpdate;
+use crate::report::{EmbeddedFont, Reported};
+use crate::{util, FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+pub use typst_pdf::PdfStandard;
+
+/// Identifier of the export, whose bytes are hashed for `PdfExportOptions::ident_from_content`.
+const CONTENT_IDENT_PLACEHOLDER: &str = "typst-as-lib-content-ident";
+
+/// Options for the PDF export, that are mapped to `typst_pdf::PdfOptions`.
+/// See `TypstTemplateCollection::compile_to_pdf_with_options()`.
+///
+/// Example:
+/// ```rust
+/// # use typst::foundations::Dict;
+/// # use typst_as_lib::pdf::{PdfExportOptions, PdfStandard, PdfTimestamp};
+/// # use typst_as_lib::TypstTemplate;
+/// # let inputs = Dict::new();
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], "= Invoice");
+/// let options = PdfExportOptions {
+///     ident: Some("invoice-2024-001".to_owned()),
+///     timestamp: PdfTimestamp::None,
+///     standards: vec![PdfStandard::A_2b],
+///     ..Default::default()
+/// };
+/// let pdf = template.compile_to_pdf_with_options(inputs, &options).output?;
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PdfExportOptions {
+    /// Stable identifier of the document. Should be the same for different versions of the
+    /// same document. If `None`, typst derives it from the title and author of the document.
+    pub ident: Option<String>,
+    /// If `ident` is `None`, derive the identifier from a hash of the content and metadata of
+    /// the document, so identical documents get identical ids. See
+    /// `PdfExportOptions::reproducible()`.
+    pub ident_from_content: bool,
+    pub timestamp: PdfTimestamp,
+    /// Pages to export. Default: all pages.
+    pub pages: PageSelection,
+    /// PDF standards to conform to, e.g. `PdfStandard::A_2b`. Default: PDF 1.7.
+    pub standards: Vec<PdfStandard>,
+    /// Fail with `TypstAsLibError::Export`, if the embedded fonts are larger in total (see
+    /// `EmbeddedFont::embedded_bytes`), e.g. when a CJK font is used for a few characters,
+    /// that a smaller font could display. The fonts of the exported pages are subsetted once
+    /// more to measure them. Default: no limit.
+    pub max_font_bytes: Option<usize>,
+    /// Check the exported PDF with `pdf_validation::validate_pdf_a()`, if a PDF/A standard is
+    /// selected, and fail with `TypstAsLibError::PdfValidation` on violations
+    /// (feature `pdf-validation`). Without the feature, the export fails with
+    /// `TypstAsLibError::InvalidExportOptions`.
+    pub validate: bool,
+    /// Files, that are embedded into the PDF. See `PdfAttachment`.
+    pub attachments: Vec<PdfAttachment>,
+    /// Identify the PDF as ZUGFeRD/Factur-X e-invoice in the XMP metadata.
+    pub factur_x: Option<FacturX>,
+    /// Overrides the metadata, that the template sets. See `PdfMetadata`.
+    pub metadata: PdfMetadata,
+}
+
+/// Creation timestamp, that is written into the PDF metadata.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum PdfTimestamp {
+    /// Time of the compilation. The same time is used for `datetime.today()` in the document.
+    #[default]
+    CompileTime,
+    Fixed(DateTime<Utc>),
+    /// No timestamp, e.g. for reproducible output.
+    None,
+}
+
+impl PdfExportOptions {
+    /// Options, with which the same document is always exported to the same bytes, e.g. for
+    /// byte-diff regression tests: no timestamp and an identifier, that is derived from the
+    /// content. typst-pdf derives the instance id from the written bytes.
+    pub fn reproducible() -> Self {
+        Self {
+            timestamp: PdfTimestamp::None,
+            ident_from_content: true,
+            ..Default::default()
+        }
+    }
+
+    /// Creation timestamp, `now` is the current time of the compilation.
+    fn timestamp(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.timestamp {
+            PdfTimestamp::CompileTime => Some(now),
+            PdfTimestamp::Fixed(timestamp) => Some(timestamp),
+            PdfTimestamp::None => None,
+        }
+    }
+
+    /// Identifier derived from the content, if `ident_from_content` is set. The PDF is
+    /// exported with a placeholder identifier and its bytes are hashed: the document itself
+    /// contains spans, whose `FileId`s are interned per process.
+    fn content_ident(
+        &self,
+        document: &Document,
+        now: DateTime<Utc>,
+    ) -> Result<Option<String>, TypstAsLibError> {
+        if self.ident.is_some() || !self.ident_from_content {
+            return Ok(None);
+        }
+        let pdf_options =
+            self.to_pdf_options(now, document.pages.len(), Some(CONTENT_IDENT_PLACEHOLDER))?;
+        let pdf = typst_pdf::pdf(document, &pdf_options)?;
+        Ok(Some(format!("{:032x}", hash128(&pdf))))
+    }
+
+    /// Write metadata and attachments, that typst-pdf doesn't support, with an incremental
+    /// update.
+    fn update_pdf(&self, pdf: Vec<u8>, now: DateTime<Utc>) -> Result<Vec<u8>, TypstAsLibError> {
+        let Self {
+            attachments,
+            factur_x,
+            metadata,
+            ..
+        } = self;
+        if attachments.is_empty() && factur_x.is_none() && !metadata.updates_pdf() {
+            return Ok(pdf);
+        }
+        let mut update = PdfUpdate::new(&pdf)?;
+        metadata.apply_to_pdf(&mut update)?;
+        embed_attachments(
+            &mut update,
+            attachments,
+            factur_x.as_ref(),
+            self.timestamp(now),
+        )?;
+        Ok(update.finish())
+    }
+
+    /// `now` is the current time of the compilation.
+    fn to_pdf_options<'a>(
+        &'a self,
+        now: DateTime<Utc>,
+        page_count: usize,
+        content_ident: Option<&'a str>,
+    ) -> Result<PdfOptions<'a>, TypstAsLibError> {
+        let Self {
+            ident,
+            pages,
+            standards,
+            ..
+        } = self;
+        if self.validate && !cfg!(feature = "pdf-validation") {
+            return Err(TypstAsLibError::InvalidExportOptions(
+                "`validate` requires feature `pdf-validation`".to_owned(),
+            ));
+        }
+        let timestamp = self.timestamp(now);
+        let standards = PdfStandards::new(standards)
+            .map_err(|err| TypstAsLibError::InvalidExportOptions(err.to_string()))?;
+        Ok(PdfOptions {
+            ident: ident
+                .as_deref()
+                .or(content_ident)
+                .map_or(Smart::Auto, Smart::Custom),
+            timestamp: timestamp.and_then(util::datetime),
+            page_ranges: pages.to_page_ranges(page_count),
+            standards,
+        })
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Compile with a `Dict` as input and export the document as PDF with `typst_pdf::pdf()`.
+    pub fn compile_to_pdf_with_options<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        options: &PdfExportOptions,
+    ) -> Warned<Result<Vec<u8>, TypstAsLibError>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let embedded_fonts = options.max_font_bytes.is_some();
+        self.compile_to_pdf_helper(main_source_id, inputs, options, embedded_fonts)
+            .output
+    }
+
+    /// Like `TypstTemplateCollection::compile_to_pdf_with_options()`, but also returns a
+    /// `CompilationReport` including the sizes of the embedded fonts
+    /// (`CompilationReport::embedded_fonts`).
+    pub fn compile_to_pdf_with_report<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        options: &PdfExportOptions,
+    ) -> Reported<Warned<Result<Vec<u8>, TypstAsLibError>>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        self.compile_to_pdf_helper(main_source_id, inputs, options, true)
+    }
+
+    /// `embedded_fonts`: Whether to set `CompilationReport::embedded_fonts`, which subsets
+    /// the fonts in addition to typst-pdf.
+    pub(crate) fn compile_to_pdf_helper<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        options: &PdfExportOptions,
+        embedded_fonts: bool,
+    ) -> Reported<Warned<Result<Vec<u8>, TypstAsLibError>>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let now = self.now();
+        let (Reported { output, mut report }, _) =
+            self.compile_tracked(main_source_id, Some(inputs), None, None, false, now);
+        let Warned { output, warnings } = output;
+        let output = output.and_then(|mut document| {
+            options.metadata.apply_to_document(&mut document);
+            if embedded_fonts {
+                report.embedded_fonts = self::embedded_fonts(&document, &options.pages);
+                check_font_bytes(&report.embedded_fonts, options.max_font_bytes)?;
+            }
+            report.collect_accessibility_issues(&document, &options.pages);
+            let content_ident = options.content_ident(&document, now)?;
+            let pdf_options =
+                options.to_pdf_options(now, document.pages.len(), content_ident.as_deref())?;
+            let pdf = typst_pdf::pdf(&document, &pdf_options)?;
+            let pdf = options.update_pdf(pdf, now)?;
+            #[cfg(feature = "pdf-validation")]
+            if options.validate {
+                let violations = crate::pdf_validation::validate_pdf_a(&pdf, &options.standards);
+                if !violations.is_empty() {
+                    return Err(TypstAsLibError::PdfValidation(violations));
+                }
+            }
+            Ok(pdf)
+        });
+        Reported {
+            output: Warned { output, warnings },
+            report,
+        }
+    }
+}
+
+impl TypstTemplate {
+    /// Compile with a `Dict` as input and export the document as PDF with `typst_pdf::pdf()`.
+    /// See `PdfExportOptions`.
+    pub fn compile_to_pdf_with_options<D>(
+        &self,
+        inputs: D,
+        options: &PdfExportOptions,
+    ) -> Warned<Result<Vec<u8>, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        let embedded_fonts = options.max_font_bytes.is_some();
+        self.compile_to_pdf_helper(inputs, options, embedded_fonts)
+            .output
+    }
+
+    /// Like `TypstTemplate::compile_to_pdf_with_options()`, but also returns a
+    /// `CompilationReport` including the sizes of the embedded fonts.
+    pub fn compile_to_pdf_with_report<D>(
+        &self,
+        inputs: D,
+        options: &PdfExportOptions,
+    ) -> Reported<Warned<Result<Vec<u8>, TypstAsLibError>>>
+    where
+        D: Into<Dict>,
+    {
+        self.compile_to_pdf_helper(inputs, options, true)
+    }
+
+    fn compile_to_pdf_helper<D>(
+        &self,
+        inputs: D,
+        options: &PdfExportOptions,
+        embedded_fonts: bool,
+    ) -> Reported<Warned<Result<Vec<u8>, TypstAsLibError>>>
+    where
+        D: Into<Dict>,
+    {
+        let inputs = match self.validated_input(inputs) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                return Reported {
+                    output: Warned {
+                        output: Err(err),
+                        warnings: Default::default(),
+                    },
+                    report: Default::default(),
+                }
+            }
+        };
+        self.collection
+            .compile_to_pdf_helper(self.source_id, inputs, options, embedded_fonts)
+    }
+}
+
+/// The fonts, that typst-pdf embeds into the PDF of the `pages` of `document`, with the sizes
+/// of their subsets. Ordered by `EmbeddedFont::embedded_bytes`, largest first.
+#[allow(clippy::mutable_key_type)]
+pub fn embedded_fonts(document: &Document, pages: &PageSelection) -> Vec<EmbeddedFont> {
+    let mut glyphs_by_font = HashMap::new();
+    for page in pages.select(document) {
+        collect_glyphs(&page.frame, &mut glyphs_by_font);
+    }
+    let mut embedded_fonts: Vec<_> = glyphs_by_font
+        .into_iter()
+        .map(|(font, glyphs)| {
+            let glyphs: Vec<u16> = glyphs.into_iter().collect();
+            let remapper = subsetter::GlyphRemapper::new_from_glyphs_sorted(&glyphs);
+            // If subsetting fails, typst-pdf embeds the whole font.
+            let embedded_bytes = subsetter::subset(font.data(), font.index(), &remapper)
+                .map_or(font.data().len(), |subset| subset.len());
+            EmbeddedFont {
+                family: font.info().family.clone(),
+                variant: format!("{:?}", font.info().variant),
+                glyphs: glyphs.len(),
+                font_bytes: font.data().len(),
+                embedded_bytes,
+            }
+        })
+        .collect();
+    embedded_fonts.sort_by_key(|font| std::cmp::Reverse(font.embedded_bytes));
+    embedded_fonts
+}
+
+#[allow(clippy::mutable_key_type)]
+fn collect_glyphs(frame: &Frame, glyphs_by_font: &mut HashMap<Font, BTreeSet<u16>>) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => collect_glyphs(&group.frame, glyphs_by_font),
+            FrameItem::Text(text) => glyphs_by_font
+                .entry(text.font.clone())
+                .or_default()
+                .extend(text.glyphs.iter().map(|glyph| glyph.id)),
+            _ => (),
+        }
+    }
+}
+
+fn check_font_bytes(
+    embedded_fonts: &[EmbeddedFont],
+    max_font_bytes: Option<usize>,
+) -> Result<(), TypstAsLibError> {
+    let Some(max_font_bytes) = max_font_bytes else {
+        return Ok(());
+    };
+    let font_bytes: usize = embedded_fonts.iter().map(|font| font.embedded_bytes).sum();
+    if font_bytes > max_font_bytes {
+        let largest = embedded_fonts
+            .first()
+            .map(|font| format!(", largest: {} ({} bytes)", font.family, font.embedded_bytes))
+            .unwrap_or_default();
+        return Err(TypstAsLibError::Export(format!(
+            "embedded fonts have {font_bytes} bytes, more than {max_font_bytes}{largest}"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,396 @@
+use chrono::{DateTime, Utc};
+
+use crate::pdf_update::{
+    date, entry, insert_entries, insert_into_entry, name, reference, stream, text_string,
+    xml_escape, PdfUpdate,
+};
+use crate::TypstAsLibError;
+
+/// A file, that is embedded into the exported PDF, e.g. the XML of an e-invoice.
+/// See `PdfExportOptions::attachments`.
+///
+/// Embedded files are associated with the document (`/AF`), as PDF/A-3 requires. typst-pdf
+/// 0.12 only supports PDF/A-2b, which doesn't allow attachments, that aren't PDF/A themselves.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::pdf::PdfExportOptions;
+/// # use typst_as_lib::pdf_attachments::{FacturX, PdfAttachment};
+/// # use typst::foundations::{dict, Array};
+/// # use typst_as_lib::TypstTemplate;
+/// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], TEMPLATE);
+/// # let inputs = dict! { "v" => Array::new() };
+/// # let xml = "<rsm:CrossIndustryInvoice/>";
+/// let options = PdfExportOptions {
+///     attachments: vec![PdfAttachment::factur_x(xml)],
+///     factur_x: Some(FacturX::default()),
+///     ..Default::default()
+/// };
+/// let pdf = template.compile_to_pdf_with_options(inputs, &options).output?;
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PdfAttachment {
+    /// File name, that is shown by PDF viewers.
+    pub name: String,
+    pub data: Vec<u8>,
+    /// MIME type, e.g. `text/xml`.
+    pub mime_type: String,
+    pub description: Option<String>,
+    pub relationship: AttachmentRelationship,
+}
+
+/// How an attachment relates to the document (`/AFRelationship`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AttachmentRelationship {
+    /// The original source of the document.
+    Source,
+    /// Data, that is used to derive the document, e.g. the XML of an e-invoice.
+    Data,
+    /// An alternative representation of the document.
+    Alternative,
+    Supplement,
+    #[default]
+    Unspecified,
+}
+
+impl AttachmentRelationship {
+    fn as_name(&self) -> &'static str {
+        match self {
+            AttachmentRelationship::Source => "/Source",
+            AttachmentRelationship::Data => "/Data",
+            AttachmentRelationship::Alternative => "/Alternative",
+            AttachmentRelationship::Supplement => "/Supplement",
+            AttachmentRelationship::Unspecified => "/Unspecified",
+        }
+    }
+}
+
+impl PdfAttachment {
+    pub fn new<S, V, M>(name: S, data: V, mime_type: M) -> Self
+    where
+        S: Into<String>,
+        V: Into<Vec<u8>>,
+        M: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            data: data.into(),
+            mime_type: mime_type.into(),
+            description: None,
+            relationship: Default::default(),
+        }
+    }
+
+    /// The XML of a ZUGFeRD/Factur-X e-invoice as `factur-x.xml`. Combine with
+    /// `PdfExportOptions::factur_x` for the required XMP metadata.
+    pub fn factur_x<V>(xml: V) -> Self
+    where
+        V: Into<Vec<u8>>,
+    {
+        Self {
+            description: Some("Factur-X/ZUGFeRD invoice".to_owned()),
+            relationship: AttachmentRelationship::Alternative,
+            ..Self::new("factur-x.xml", xml, "text/xml")
+        }
+    }
+
+    pub fn with_description<S>(self, description: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            description: Some(description.into()),
+            ..self
+        }
+    }
+
+    pub fn with_relationship(self, relationship: AttachmentRelationship) -> Self {
+        Self {
+            relationship,
+            ..self
+        }
+    }
+}
+
+/// XMP metadata, that identifies the PDF as ZUGFeRD/Factur-X e-invoice. The invoice itself is
+/// attached with `PdfAttachment::factur_x()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FacturX {
+    /// Name of the attached XML.
+    pub document_file_name: String,
+    /// `INVOICE` for invoices.
+    pub document_type: String,
+    pub version: String,
+    /// Profile of the invoice, e.g. `EN 16931`, `BASIC` or `EXTENDED`.
+    pub conformance_level: String,
+}
+
+impl Default for FacturX {
+    fn default() -> Self {
+        Self {
+            document_file_name: "factur-x.xml".to_owned(),
+            document_type: "INVOICE".to_owned(),
+            version: "1.0".to_owned(),
+            conformance_level: "EN 16931".to_owned(),
+        }
+    }
+}
+
+const FACTUR_X_NAMESPACE: &str = "urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#";
+
+impl FacturX {
+    /// `rdf:Description` elements with the properties and the PDF/A extension schema, that
+    /// declares them.
+    fn to_xmp(&self) -> String {
+        let Self {
+            document_file_name,
+            document_type,
+            version,
+            conformance_level,
+        } = self;
+        let properties = [
+            ("DocumentFileName", "name of the embedded XML invoice file"),
+            ("DocumentType", "type of the hybrid document"),
+            ("Version", "version of the Factur-X XML schema"),
+            ("ConformanceLevel", "conformance level of the invoice"),
+        ]
+        .map(|(name, description)| {
+            format!(
+                "<rdf:li rdf:parseType=\"Resource\">\
+                 <pdfaProperty:name>{name}</pdfaProperty:name>\
+                 <pdfaProperty:valueType>Text</pdfaProperty:valueType>\
+                 <pdfaProperty:category>external</pdfaProperty:category>\
+                 <pdfaProperty:description>{description}</pdfaProperty:description>\
+                 </rdf:li>"
+            )
+        })
+        .concat();
+        format!(
+            "<rdf:Description rdf:about=\"\" xmlns:fx=\"{FACTUR_X_NAMESPACE}\">\
+             <fx:DocumentType>{}</fx:DocumentType>\
+             <fx:DocumentFileName>{}</fx:DocumentFileName>\
+             <fx:Version>{}</fx:Version>\
+             <fx:ConformanceLevel>{}</fx:ConformanceLevel>\
+             </rdf:Description>\
+             <rdf:Description rdf:about=\"\" \
+             xmlns:pdfaExtension=\"http://www.aiim.org/pdfa/ns/extension/\" \
+             xmlns:pdfaSchema=\"http://www.aiim.org/pdfa/ns/schema#\" \
+             xmlns:pdfaProperty=\"http://www.aiim.org/pdfa/ns/property#\">\
+             <pdfaExtension:schemas><rdf:Bag><rdf:li rdf:parseType=\"Resource\">\
+             <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>\
+             <pdfaSchema:namespaceURI>{FACTUR_X_NAMESPACE}</pdfaSchema:namespaceURI>\
+             <pdfaSchema:prefix>fx</pdfaSchema:prefix>\
+             <pdfaSchema:property><rdf:Seq>{properties}</rdf:Seq></pdfaSchema:property>\
+             </rdf:li></rdf:Bag></pdfaExtension:schemas>\
+             </rdf:Description>",
+            xml_escape(document_type),
+            xml_escape(document_file_name),
+            xml_escape(version),
+            xml_escape(conformance_level),
+        )
+    }
+}
+
+/// Embed `attachments` and the Factur-X metadata into the PDF.
+pub(crate) fn embed_attachments(
+    update: &mut PdfUpdate,
+    attachments: &[PdfAttachment],
+    factur_x: Option<&FacturX>,
+    mod_date: Option<DateTime<Utc>>,
+) -> Result<(), TypstAsLibError> {
+    if let Some(factur_x) = factur_x {
+        update.extend_xmp(&factur_x.to_xmp())?;
+    }
+    if !attachments.is_empty() {
+        attach(update, attachments, mod_date)?;
+    }
+    Ok(())
+}
+
+fn attach(
+    update: &mut PdfUpdate,
+    attachments: &[PdfAttachment],
+    mod_date: Option<DateTime<Utc>>,
+) -> Result<(), TypstAsLibError> {
+    let mod_date = mod_date
+        .map(|mod_date| format!(" /ModDate {}", date(mod_date)))
+        .unwrap_or_default();
+    let mut file_specs = Vec::new();
+    for attachment in attachments {
+        let PdfAttachment {
+            name: file_name,
+            data,
+            mime_type,
+            description,
+            relationship,
+        } = attachment;
+        let file_id = update.alloc();
+        update.write(
+            file_id,
+            stream(
+                &format!(
+                    "/Type /EmbeddedFile /Subtype {} /Params << /Size {}{mod_date} >> \
+                     /Length {}",
+                    name(mime_type),
+                    data.len(),
+                    data.len()
+                ),
+                data,
+            ),
+        );
+        let description = description
+            .as_deref()
+            .map(|description| format!(" /Desc {}", text_string(description)))
+            .unwrap_or_default();
+        let file_spec_id = update.alloc();
+        update.write(
+            file_spec_id,
+            format!(
+                "<< /Type /Filespec /F {0} /UF {0}{description} /AFRelationship {1} \
+                 /EF << /F {file_id} 0 R /UF {file_id} 0 R >> >>",
+                text_string(file_name),
+                relationship.as_name()
+            )
+            .into_bytes(),
+        );
+        file_specs.push((file_name.as_str(), file_spec_id));
+    }
+
+    // Keys of name trees must be sorted.
+    file_specs.sort_by_key(|(file_name, _)| *file_name);
+    let names = file_specs
+        .iter()
+        .map(|(file_name, id)| format!("{} {id} 0 R", text_string(file_name)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let embedded_files_id = update.alloc();
+    update.write(
+        embedded_files_id,
+        format!("<< /Names [{names}] >>").into_bytes(),
+    );
+
+    let catalog_id = update.catalog_id();
+    let mut catalog = update.object(catalog_id)?;
+    let embedded_files = format!("/EmbeddedFiles {embedded_files_id} 0 R");
+    if let Some(names_id) = reference(&catalog, "/Names") {
+        let names = update.object(names_id)?;
+        update.write(names_id, insert_entries(&names, &embedded_files)?);
+    } else if entry(&catalog, "/Names").is_some() {
+        catalog = insert_into_entry(&catalog, "/Names", &embedded_files)?;
+    } else {
+        catalog = insert_entries(&catalog, &format!("/Names << {embedded_files} >>"))?;
+    }
+    if entry(&catalog, "/AF").is_some() {
+        return Err(TypstAsLibError::Export(
+            "could not update PDF: document already has associated files".to_owned(),
+        ));
+    }
+    let associated_files = file_specs
+        .iter()
+        .map(|(_, id)| format!("{id} 0 R"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    catalog = insert_entries(&catalog, &format!("/AF [{associated_files}]"))?;
+    update.write(catalog_id, catalog);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use typst::foundations::{Bytes, Dict};
+    use typst::text::Font;
+
+    use super::*;
+    use crate::pdf::PdfExportOptions;
+    use crate::pdf_update::{entry, reference};
+    use crate::TypstTemplate;
+
+    static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
+
+    fn export(options: &PdfExportOptions) -> Vec<u8> {
+        let font = Font::new(Bytes::from_static(FONT), 0).unwrap();
+        let template = TypstTemplate::new(vec![font], "Invoice");
+        template
+            .compile_to_pdf_with_options(Dict::new(), options)
+            .output
+            .unwrap()
+    }
+
+    #[test]
+    fn new_with_different_types() {
+        let attachment = PdfAttachment::new("data.json", b"{}".to_vec(), String::from("text/json"));
+        assert_eq!(attachment.name, "data.json");
+        assert_eq!(attachment.mime_type, "text/json");
+    }
+
+    #[test]
+    fn round_trip() {
+        let xml = b"<rsm:CrossIndustryInvoice/>";
+        let options = PdfExportOptions {
+            attachments: vec![
+                PdfAttachment::new("z.txt", b"last".as_slice(), "text/plain"),
+                PdfAttachment::factur_x(xml.as_slice()),
+            ],
+            factur_x: Some(FacturX::default()),
+            ..Default::default()
+        };
+        let pdf = export(&options);
+        let update = PdfUpdate::new(&pdf).unwrap();
+        let catalog = update.object(update.catalog_id()).unwrap();
+
+        let associated_files = entry(&catalog, "/AF").unwrap();
+        assert_eq!(associated_files.matches(" R").count(), 2);
+
+        let names = match reference(&catalog, "/Names") {
+            Some(id) => update.object(id).unwrap(),
+            None => entry(&catalog, "/Names").unwrap().as_bytes().to_vec(),
+        };
+        let embedded_files = update
+            .object(reference(&names, "/EmbeddedFiles").unwrap())
+            .unwrap();
+        let embedded_files = String::from_utf8(embedded_files).unwrap();
+        // Keys of the name tree are sorted: `factur-x.xml` before `z.txt`.
+        let factur_x_name = text_string("factur-x.xml");
+        let z_name = text_string("z.txt");
+        assert!(embedded_files.find(&factur_x_name) < embedded_files.find(&z_name));
+
+        let file_spec_id: usize = embedded_files
+            [embedded_files.find(&factur_x_name).unwrap() + factur_x_name.len()..]
+            .split_whitespace()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let file_spec = update.object(file_spec_id).unwrap();
+        assert_eq!(
+            entry(&file_spec, "/AFRelationship").unwrap().trim(),
+            "/Alternative"
+        );
+        let ef = entry(&file_spec, "/EF").unwrap().as_bytes().to_vec();
+        let file = update.object(reference(&ef, "/F").unwrap()).unwrap();
+        assert_eq!(entry(&file, "/Subtype").unwrap().trim(), name("text/xml"));
+        assert!(file.ends_with(b"<rsm:CrossIndustryInvoice/>\nendstream"));
+
+        let metadata = update
+            .object(reference(&catalog, "/Metadata").unwrap())
+            .unwrap();
+        let metadata = String::from_utf8_lossy(&metadata);
+        assert!(metadata.contains("<fx:DocumentFileName>factur-x.xml</fx:DocumentFileName>"));
+        assert!(metadata.contains("</rdf:RDF>"));
+    }
+
+    #[test]
+    fn without_attachments_pdf_is_unchanged() {
+        let options = PdfExportOptions::reproducible();
+        let pdf = export(&options);
+        assert_eq!(
+            pdf.windows(b"startxref".len())
+                .filter(|w| w == b"startxref")
+                .count(),
+            1
+        );
+    }
+}
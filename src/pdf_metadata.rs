@@ -0,0 +1,183 @@
+use typst::model::Document;
+
+use crate::pdf_update::{text_string, xml_escape, PdfUpdate};
+use crate::TypstAsLibError;
+
+/// Metadata of the exported PDF, that overrides the metadata, which the template sets with
+/// `#set document(..)`. Fields, that are `None`, keep the values of the template.
+/// See `PdfExportOptions::metadata`.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::pdf::PdfExportOptions;
+/// # use typst_as_lib::pdf_metadata::{PdfMetadata, XmpProperty};
+/// # struct Customer {
+/// #     company: String,
+/// # }
+/// # let customer = Customer { company: "ACME Corp.".to_owned() };
+/// # let invoice_number = 42;
+/// # let document_id = "f81d4fae-7dec-11d0-a765-00a0c91e6bf6";
+/// let options = PdfExportOptions {
+///     metadata: PdfMetadata {
+///         title: Some(format!("Invoice {invoice_number}")),
+///         author: Some(vec![customer.company.clone()]),
+///         creator: Some("ACME Billing".to_owned()),
+///         ..Default::default()
+///     }
+///     .with_xmp_property(XmpProperty::new(
+///         "https://acme.example/ns/billing/",
+///         "acme",
+///         "DocumentId",
+///         document_id,
+///     )?),
+///     ..Default::default()
+/// };
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<Vec<String>>,
+    /// Description of the document.
+    pub subject: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    /// Application, that created the document. typst-pdf sets it to `Typst <version>`.
+    pub creator: Option<String>,
+    /// Additional properties in the XMP metadata. PDF/A requires an extension schema for
+    /// properties, that are not predefined.
+    pub xmp_properties: Vec<XmpProperty>,
+}
+
+/// A custom property in the XMP metadata, e.g. a document id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmpProperty {
+    /// Namespace URI of the property.
+    pub namespace: String,
+    /// Must be an XML name without colon (NCName), that doesn't start with `xml` and isn't
+    /// `rdf`.
+    pub prefix: String,
+    /// Must be an XML name without colon (NCName).
+    pub name: String,
+    pub value: String,
+}
+
+impl XmpProperty {
+    /// Fails with `TypstAsLibError::InvalidExportOptions`, if `prefix` or `name` are invalid.
+    pub fn new<S>(namespace: S, prefix: S, name: S, value: S) -> Result<Self, TypstAsLibError>
+    where
+        S: Into<String>,
+    {
+        let property = Self {
+            namespace: namespace.into(),
+            prefix: prefix.into(),
+            name: name.into(),
+            value: value.into(),
+        };
+        property.validate()?;
+        Ok(property)
+    }
+
+    /// `prefix` and `name` are written into the XMP packet unescaped.
+    fn validate(&self) -> Result<(), TypstAsLibError> {
+        let Self { prefix, name, .. } = self;
+        let invalid = |what: &str, value: &str| {
+            TypstAsLibError::InvalidExportOptions(format!("invalid XMP property {what}: {value:?}"))
+        };
+        if !is_nc_name(prefix) || prefix.to_ascii_lowercase().starts_with("xml") || prefix == "rdf"
+        {
+            return Err(invalid("prefix", prefix));
+        }
+        if !is_nc_name(name) {
+            return Err(invalid("name", name));
+        }
+        Ok(())
+    }
+}
+
+/// Whether `name` is an XML name without colon, e.g. `DocumentId`.
+fn is_nc_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|char| char.is_alphanumeric() || matches!(char, '_' | '-' | '.'))
+}
+
+impl PdfMetadata {
+    pub fn with_xmp_property(mut self, property: XmpProperty) -> Self {
+        self.xmp_properties.push(property);
+        self
+    }
+
+    /// Override the metadata of the document, which typst-pdf writes into the document
+    /// information dictionary and the XMP metadata. The subject is written by
+    /// `PdfMetadata::apply_to_pdf()`.
+    pub(crate) fn apply_to_document(&self, document: &mut Document) {
+        let Self {
+            title,
+            author,
+            keywords,
+            ..
+        } = self;
+        let info = &mut document.info;
+        if let Some(title) = title {
+            info.title = Some(title.as_str().into());
+        }
+        if let Some(author) = author {
+            info.author = author.iter().map(|author| author.as_str().into()).collect();
+        }
+        if let Some(keywords) = keywords {
+            info.keywords = keywords
+                .iter()
+                .map(|keyword| keyword.as_str().into())
+                .collect();
+        }
+    }
+
+    /// Whether `PdfMetadata::apply_to_pdf()` changes the exported PDF.
+    pub(crate) fn updates_pdf(&self) -> bool {
+        self.subject.is_some() || self.creator.is_some() || !self.xmp_properties.is_empty()
+    }
+
+    /// Write the metadata, that typst-pdf doesn't take from the document.
+    pub(crate) fn apply_to_pdf(&self, update: &mut PdfUpdate) -> Result<(), TypstAsLibError> {
+        let Self {
+            subject,
+            creator,
+            xmp_properties,
+            ..
+        } = self;
+        if let Some(subject) = subject {
+            // typst-pdf 0.12 doesn't write a description.
+            update.set_info(&[("/Subject", text_string(subject))])?;
+            update.extend_xmp(&format!(
+                "<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\
+                 <dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>\
+                 </dc:description></rdf:Description>",
+                xml_escape(subject)
+            ))?;
+        }
+        for property in xmp_properties {
+            property.validate()?;
+        }
+        if let Some(creator) = creator {
+            update.set_info(&[("/Creator", text_string(creator))])?;
+            update.set_xmp_text("xmp:CreatorTool", "http://ns.adobe.com/xap/1.0/", creator)?;
+        }
+        for XmpProperty {
+            namespace,
+            prefix,
+            name,
+            value,
+        } in xmp_properties
+        {
+            update.extend_xmp(&format!(
+                "<rdf:Description rdf:about=\"\" xmlns:{prefix}=\"{}\">\
+                 <{prefix}:{name}>{}</{prefix}:{name}></rdf:Description>",
+                xml_escape(namespace),
+                xml_escape(value)
+            ))?;
+        }
+        Ok(())
+    }
+}
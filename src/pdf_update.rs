@@ -0,0 +1,556 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::TypstAsLibError;
+
+/// Appends an incremental update to a PDF, that was written by typst-pdf: new objects and new
+/// versions of existing objects are written after the original file, followed by a new
+/// cross-reference section. The original bytes stay untouched.
+///
+/// Only classic cross-reference tables and uncompressed dictionaries are supported, which is
+/// what typst-pdf writes.
+pub(crate) struct PdfUpdate<'a> {
+    pdf: &'a [u8],
+    offsets: HashMap<usize, usize>,
+    startxref: usize,
+    root: usize,
+    info: Option<usize>,
+    file_id: Option<String>,
+    next_id: usize,
+    objects: Vec<(usize, Vec<u8>)>,
+}
+
+impl<'a> PdfUpdate<'a> {
+    pub(crate) fn new(pdf: &'a [u8]) -> Result<Self, TypstAsLibError> {
+        let startxref = rfind(pdf, b"startxref").ok_or_else(|| invalid("startxref"))?;
+        let startxref: usize = String::from_utf8_lossy(&pdf[startxref + b"startxref".len()..])
+            .split_whitespace()
+            .next()
+            .and_then(|offset| offset.parse().ok())
+            .ok_or_else(|| invalid("startxref"))?;
+        let mut offsets = HashMap::new();
+        let trailer = read_xref(pdf, startxref, &mut offsets)?;
+        // Earlier updates, e.g. of the PDF, that typst-pdf wrote.
+        let mut prev = prev_xref(trailer);
+        let mut visited = vec![startxref];
+        while let Some(offset) = prev.filter(|offset| !visited.contains(offset)) {
+            visited.push(offset);
+            prev = prev_xref(read_xref(pdf, offset, &mut offsets)?);
+        }
+        let root = reference(trailer, "/Root").ok_or_else(|| invalid("/Root"))?;
+        let size = entry(trailer, "/Size")
+            .and_then(|size| size.trim().parse().ok())
+            .ok_or_else(|| invalid("/Size"))?;
+        Ok(Self {
+            pdf,
+            offsets,
+            startxref,
+            root,
+            info: reference(trailer, "/Info"),
+            file_id: entry(trailer, "/ID").map(|id| id.trim().to_owned()),
+            next_id: size,
+            objects: Vec::new(),
+        })
+    }
+
+    pub(crate) fn catalog_id(&self) -> usize {
+        self.root
+    }
+
+    /// Content of an object (between `obj` and `endobj`), in its latest version.
+    pub(crate) fn object(&self, id: usize) -> Result<Vec<u8>, TypstAsLibError> {
+        if let Some((_, content)) = self.objects.iter().rev().find(|(other, _)| *other == id) {
+            return Ok(content.clone());
+        }
+        let invalid_object = || invalid(&format!("object {id}"));
+        let offset = *self.offsets.get(&id).ok_or_else(invalid_object)?;
+        let object = self.pdf.get(offset..).ok_or_else(invalid_object)?;
+        let start = find(object, b"obj").ok_or_else(invalid_object)? + b"obj".len();
+        let end = find(object, b"endobj").ok_or_else(invalid_object)?;
+        Ok(object[start..end].trim_ascii().to_vec())
+    }
+
+    /// Id for a new object, that is written with `PdfUpdate::write()`.
+    pub(crate) fn alloc(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    /// Write a new object or a new version of an existing one.
+    pub(crate) fn write(&mut self, id: usize, content: Vec<u8>) {
+        self.objects.retain(|(other, _)| *other != id);
+        self.objects.push((id, content));
+    }
+
+    /// Insert `description` (one or more `rdf:Description` elements) into the XMP metadata of
+    /// the document.
+    pub(crate) fn extend_xmp(&mut self, description: &str) -> Result<(), TypstAsLibError> {
+        self.edit_xmp(|xmp| {
+            let position = find(xmp, b"</rdf:RDF>").ok_or_else(|| invalid("XMP metadata"))?;
+            let mut out = xmp[..position].to_vec();
+            out.extend_from_slice(description.as_bytes());
+            out.extend_from_slice(&xmp[position..]);
+            Ok(out)
+        })
+    }
+
+    /// Replace the text of the XMP element `element` (e.g. `xmp:CreatorTool`). Inserts a new
+    /// `rdf:Description` with the element, if it doesn't exist.
+    pub(crate) fn set_xmp_text(
+        &mut self,
+        element: &str,
+        namespace: &str,
+        text: &str,
+    ) -> Result<(), TypstAsLibError> {
+        let (prefix, _) = element.split_once(':').ok_or_else(|| invalid(element))?;
+        let open = format!("<{element}>");
+        let close = format!("</{element}>");
+        let mut missing = false;
+        self.edit_xmp(|xmp| {
+            let Some(start) = find(xmp, open.as_bytes()) else {
+                missing = true;
+                return Ok(xmp.to_vec());
+            };
+            let start = start + open.len();
+            let end =
+                start + find(&xmp[start..], close.as_bytes()).ok_or_else(|| invalid(element))?;
+            let mut out = xmp[..start].to_vec();
+            out.extend_from_slice(xml_escape(text).as_bytes());
+            out.extend_from_slice(&xmp[end..]);
+            Ok(out)
+        })?;
+        if missing {
+            self.extend_xmp(&format!(
+                "<rdf:Description rdf:about=\"\" xmlns:{prefix}=\"{namespace}\">\
+                 {open}{}{close}</rdf:Description>",
+                xml_escape(text)
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn edit_xmp<F>(&mut self, edit: F) -> Result<(), TypstAsLibError>
+    where
+        F: FnOnce(&[u8]) -> Result<Vec<u8>, TypstAsLibError>,
+    {
+        let catalog = self.object(self.root)?;
+        let id = reference(&catalog, "/Metadata").ok_or_else(|| invalid("/Metadata"))?;
+        let object = self.object(id)?;
+        let dict_end = find(&object, b"stream").ok_or_else(|| invalid("metadata stream"))?;
+        if entry(&object[..dict_end], "/Filter").is_some() {
+            return Err(invalid("compressed metadata stream"));
+        }
+        let data = stream_data(&object).ok_or_else(|| invalid("metadata stream"))?;
+        let xmp = edit(data)?;
+        self.write(
+            id,
+            stream(
+                &format!("/Type /Metadata /Subtype /XML /Length {}", xmp.len()),
+                &xmp,
+            ),
+        );
+        Ok(())
+    }
+
+    /// Set entries of the document information dictionary (`/Info`), e.g. `/Creator`. Values
+    /// must be PDF objects, e.g. `text_string()`.
+    pub(crate) fn set_info(&mut self, entries: &[(&str, String)]) -> Result<(), TypstAsLibError> {
+        let mut info = match self.info {
+            Some(id) => self.object(id)?,
+            None => b"<< >>".to_vec(),
+        };
+        for (key, value) in entries {
+            info = remove_entry(&info, key);
+            info = insert_entries(&info, &format!("{key} {value}"))?;
+        }
+        let id = match self.info {
+            Some(id) => id,
+            None => self.alloc(),
+        };
+        self.info = Some(id);
+        self.write(id, info);
+        Ok(())
+    }
+
+    /// The original PDF followed by the update.
+    pub(crate) fn finish(self) -> Vec<u8> {
+        let Self {
+            pdf,
+            startxref,
+            root,
+            info,
+            file_id,
+            next_id,
+            mut objects,
+            ..
+        } = self;
+        let mut out = pdf.to_vec();
+        if !out.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+        objects.sort_by_key(|(id, _)| *id);
+        let mut offsets = Vec::new();
+        for (id, content) in &objects {
+            offsets.push((*id, out.len()));
+            out.extend_from_slice(format!("{id} 0 obj\n").as_bytes());
+            out.extend_from_slice(content);
+            out.extend_from_slice(b"\nendobj\n\n");
+        }
+
+        let xref = out.len();
+        out.extend_from_slice(b"xref\n");
+        let mut sections: Vec<Vec<(usize, usize)>> = Vec::new();
+        for (id, offset) in offsets {
+            match sections.last_mut() {
+                Some(section) if section.last().is_some_and(|(last, _)| last + 1 == id) => {
+                    section.push((id, offset))
+                }
+                _ => sections.push(vec![(id, offset)]),
+            }
+        }
+        for section in sections {
+            out.extend_from_slice(format!("{} {}\n", section[0].0, section.len()).as_bytes());
+            for (_, offset) in section {
+                out.extend_from_slice(format!("{offset:010} 00000 n\r\n").as_bytes());
+            }
+        }
+
+        let mut trailer = format!("trailer\n<< /Size {next_id} /Root {root} 0 R");
+        if let Some(info) = info {
+            trailer.push_str(&format!(" /Info {info} 0 R"));
+        }
+        if let Some(file_id) = file_id {
+            trailer.push_str(&format!(" /ID {file_id}"));
+        }
+        trailer.push_str(&format!(
+            " /Prev {startxref} >>\nstartxref\n{xref}\n%%EOF\n"
+        ));
+        out.extend_from_slice(trailer.as_bytes());
+        out
+    }
+}
+
+/// Read the cross-reference section at `xref` into `offsets` and return its trailer. Entries,
+/// that are already in `offsets`, are from a later update and are kept.
+fn read_xref<'b>(
+    pdf: &'b [u8],
+    xref: usize,
+    offsets: &mut HashMap<usize, usize>,
+) -> Result<&'b [u8], TypstAsLibError> {
+    let xref = pdf.get(xref..).ok_or_else(|| invalid("xref"))?;
+    let trailer_start = find(xref, b"trailer").ok_or_else(|| invalid("trailer"))?;
+    let table = String::from_utf8_lossy(&xref[..trailer_start]);
+    let mut tokens = table.split_whitespace();
+    if tokens.next() != Some("xref") {
+        return Err(invalid("xref"));
+    }
+    let number = |token: Option<&str>| -> Result<usize, TypstAsLibError> {
+        token
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| invalid("xref"))
+    };
+    // Subsections of `<first id> <count>`, followed by `<offset> <generation> <n|f>` entries.
+    while let Some(start) = tokens.next() {
+        let start = number(Some(start))?;
+        let count = number(tokens.next())?;
+        for id in start..start + count {
+            let offset = number(tokens.next())?;
+            let _generation = number(tokens.next())?;
+            match tokens.next() {
+                Some("n") => {
+                    offsets.entry(id).or_insert(offset);
+                }
+                Some("f") => {}
+                _ => return Err(invalid("xref")),
+            }
+        }
+    }
+    let trailer = &xref[trailer_start..];
+    Ok(&trailer[..find(trailer, b"startxref").unwrap_or(trailer.len())])
+}
+
+/// Offset of the previous cross-reference section (`/Prev`) of a trailer.
+fn prev_xref(trailer: &[u8]) -> Option<usize> {
+    entry(trailer, "/Prev")?.trim().parse().ok()
+}
+
+/// Stream object with the entries `dict` (without `<<`/`>>`).
+pub(crate) fn stream(dict: &str, data: &[u8]) -> Vec<u8> {
+    let mut object = format!("<< {dict} >>\nstream\n").into_bytes();
+    object.extend_from_slice(data);
+    object.extend_from_slice(b"\nendstream");
+    object
+}
+
+/// Insert `entries` at the end of the dictionary `dict`.
+pub(crate) fn insert_entries(dict: &[u8], entries: &str) -> Result<Vec<u8>, TypstAsLibError> {
+    let end = rfind(dict, b">>").ok_or_else(|| invalid("dictionary"))?;
+    let mut out = dict[..end].to_vec();
+    out.extend_from_slice(format!(" {entries} ").as_bytes());
+    out.extend_from_slice(&dict[end..]);
+    Ok(out)
+}
+
+/// Remove the entry `key` from the dictionary `dict`, e.g. before overriding it.
+fn remove_entry(dict: &[u8], key: &str) -> Vec<u8> {
+    let Some(start) = find_key(dict, key) else {
+        return dict.to_vec();
+    };
+    let end = start + key.len() + value_len(&dict[start + key.len()..]);
+    let mut out = dict[..start].to_vec();
+    out.extend_from_slice(&dict[end..]);
+    out
+}
+
+/// Insert `entries` into the dictionary, that is the value of the entry `key` of `dict`.
+pub(crate) fn insert_into_entry(
+    dict: &[u8],
+    key: &str,
+    entries: &str,
+) -> Result<Vec<u8>, TypstAsLibError> {
+    let start = find_key(dict, key).ok_or_else(|| invalid(key))? + key.len();
+    let end = start + value_len(&dict[start..]);
+    let mut out = dict[..start].to_vec();
+    out.push(b' ');
+    out.extend_from_slice(&insert_entries(&dict[start..end], entries)?);
+    out.extend_from_slice(&dict[end..]);
+    Ok(out)
+}
+
+/// Value of the entry `key` in a dictionary.
+pub(crate) fn entry<'b>(dict: &'b [u8], key: &str) -> Option<&'b str> {
+    let start = find_key(dict, key)? + key.len();
+    let value = &dict[start..];
+    std::str::from_utf8(&value[..value_len(value)]).ok()
+}
+
+/// Object id of the entry `key`, if it is an indirect reference (`12 0 R`).
+pub(crate) fn reference(dict: &[u8], key: &str) -> Option<usize> {
+    let value = entry(dict, key)?;
+    let mut parts = value.split_whitespace();
+    let id = parts.next()?.parse().ok()?;
+    (parts.next()? == "0" && parts.next()? == "R").then_some(id)
+}
+
+/// Length of the value at the start of `value`, up to the next key or the end of the
+/// dictionary. Nested dictionaries, arrays and strings are skipped.
+fn value_len(value: &[u8]) -> usize {
+    let mut depth = 0usize;
+    let mut index = 0;
+    // Skip the leading whitespace and the first character, which may be a `/` of a name.
+    while index < value.len() && value[index].is_ascii_whitespace() {
+        index += 1;
+    }
+    let mut first = true;
+    while index < value.len() {
+        let rest = &value[index..];
+        if rest.starts_with(b"<<") || rest.starts_with(b"[") || rest.starts_with(b"(") {
+            depth += 1;
+        } else if rest.starts_with(b">>") || rest.starts_with(b"]") || rest.starts_with(b")") {
+            if depth == 0 {
+                return index;
+            }
+            depth -= 1;
+        } else if rest.starts_with(b"/") && depth == 0 && !first {
+            return index;
+        }
+        first = false;
+        index += if rest.starts_with(b"<<") || rest.starts_with(b">>") {
+            2
+        } else {
+            1
+        };
+    }
+    index
+}
+
+fn find_key(dict: &[u8], key: &str) -> Option<usize> {
+    let key = key.as_bytes();
+    let mut start = 0;
+    while let Some(position) = find(&dict[start..], key) {
+        let position = start + position;
+        let next = dict.get(position + key.len());
+        // `/Names` must not match `/NamesX`.
+        if next.is_none_or(|next| !next.is_ascii_alphanumeric()) {
+            return Some(position);
+        }
+        start = position + key.len();
+    }
+    None
+}
+
+fn stream_data(object: &[u8]) -> Option<&[u8]> {
+    let start = find(object, b"stream")? + b"stream".len();
+    let data = &object[start..];
+    let data = data
+        .strip_prefix(b"\r\n")
+        .or_else(|| data.strip_prefix(b"\n"))
+        .unwrap_or(data);
+    let end = rfind(data, b"endstream")?;
+    let data = &data[..end];
+    Some(
+        data.strip_suffix(b"\r\n")
+            .or_else(|| data.strip_suffix(b"\n"))
+            .unwrap_or(data),
+    )
+}
+
+/// PDF text string in UTF-16BE, e.g. for file names and metadata.
+pub(crate) fn text_string(text: &str) -> String {
+    let mut out = "<FEFF".to_owned();
+    for unit in text.encode_utf16() {
+        out.push_str(&format!("{unit:04X}"));
+    }
+    out.push('>');
+    out
+}
+
+/// PDF name, e.g. `/text#2Fxml` for `text/xml`.
+pub(crate) fn name(name: &str) -> String {
+    let mut out = "/".to_owned();
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.+".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("#{byte:02X}"));
+        }
+    }
+    out
+}
+
+/// PDF date, e.g. `(D:20241019120000Z)`.
+pub(crate) fn date(date: DateTime<Utc>) -> String {
+    format!("(D:{}Z)", date.format("%Y%m%d%H%M%S"))
+}
+
+/// Escape text for XML, e.g. in XMP metadata.
+pub(crate) fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+fn invalid(what: &str) -> TypstAsLibError {
+    TypstAsLibError::Export(format!(
+        "could not update PDF: unsupported or missing {what}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use typst::foundations::Bytes;
+    use typst::text::Font;
+    use typst_pdf::PdfOptions;
+
+    use super::*;
+    use crate::TypstTemplate;
+
+    static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
+
+    fn typst_pdf() -> Vec<u8> {
+        let font = Font::new(Bytes::from_static(FONT), 0).unwrap();
+        let template = TypstTemplate::new(vec![font], "#set document(title: \"Test\")\nHello");
+        let document = template.compile().output.unwrap();
+        typst_pdf::pdf(&document, &PdfOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn reads_typst_pdf_output() {
+        let pdf = typst_pdf();
+        let update = PdfUpdate::new(&pdf).unwrap();
+        let catalog = update.object(update.catalog_id()).unwrap();
+        assert!(entry(&catalog, "/Type").is_some_and(|value| value.trim() == "/Catalog"));
+        let pages = reference(&catalog, "/Pages").unwrap();
+        assert!(update.object(pages).is_ok());
+        assert!(update.info.is_some());
+    }
+
+    #[test]
+    fn round_trip() {
+        let pdf = typst_pdf();
+        let mut update = PdfUpdate::new(&pdf).unwrap();
+        let id = update.alloc();
+        update.write(id, stream("/Length 4", b"data"));
+        update
+            .set_info(&[("/Creator", text_string("Round trip"))])
+            .unwrap();
+        update
+            .extend_xmp("<rdf:Description rdf:about=\"\"/>")
+            .unwrap();
+        let catalog_id = update.catalog_id();
+        let updated = update.finish();
+        assert!(updated.starts_with(&pdf));
+
+        let update = PdfUpdate::new(&updated).unwrap();
+        assert_eq!(update.catalog_id(), catalog_id);
+        assert_eq!(update.object(id).unwrap(), stream("/Length 4", b"data"));
+        let info = update.object(update.info.unwrap()).unwrap();
+        assert_eq!(
+            entry(&info, "/Creator").unwrap().trim(),
+            text_string("Round trip")
+        );
+        // Objects, that weren't updated, are found through `/Prev`.
+        let catalog = update.object(catalog_id).unwrap();
+        let pages = reference(&catalog, "/Pages").unwrap();
+        assert!(update.object(pages).unwrap().starts_with(b"<<"));
+        let metadata = update
+            .object(reference(&catalog, "/Metadata").unwrap())
+            .unwrap();
+        let xmp = stream_data(&metadata).unwrap();
+        assert!(find(xmp, b"<rdf:Description rdf:about=\"\"/></rdf:RDF>").is_some());
+    }
+
+    #[test]
+    fn chained_updates() {
+        let pdf = typst_pdf();
+        let mut update = PdfUpdate::new(&pdf).unwrap();
+        let first = update.alloc();
+        update.write(first, b"(first)".to_vec());
+        let updated = update.finish();
+
+        let mut update = PdfUpdate::new(&updated).unwrap();
+        let second = update.alloc();
+        assert_eq!(second, first + 1);
+        update.write(second, b"(second)".to_vec());
+        update.write(first, b"(first again)".to_vec());
+        let updated = update.finish();
+
+        let update = PdfUpdate::new(&updated).unwrap();
+        assert_eq!(update.object(first).unwrap(), b"(first again)");
+        assert_eq!(update.object(second).unwrap(), b"(second)");
+        assert!(update.object(update.catalog_id()).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_pdf() {
+        assert!(PdfUpdate::new(b"%PDF-1.7\n").is_err());
+    }
+
+    #[test]
+    fn dictionary_entries() {
+        let dict = b"<< /Type /Catalog /Names << /Dests 3 0 R >> /NamesX 4 0 R >>";
+        assert_eq!(reference(dict, "/NamesX"), Some(4));
+        assert_eq!(entry(dict, "/Names").unwrap().trim(), "<< /Dests 3 0 R >>");
+        let dict = insert_into_entry(dict, "/Names", "/EmbeddedFiles 5 0 R").unwrap();
+        let names = entry(&dict, "/Names").unwrap().as_bytes().to_vec();
+        assert_eq!(reference(&names, "/EmbeddedFiles"), Some(5));
+        assert_eq!(reference(&names, "/Dests"), Some(3));
+        let dict = remove_entry(&dict, "/Type");
+        assert!(entry(&dict, "/Type").is_none());
+        assert_eq!(reference(&dict, "/NamesX"), Some(4));
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt::Display;
+
+use typst_pdf::PdfStandard;
+
+/// A violation of a PDF/A standard, that was found by `validate_pdf_a()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PdfViolation {
+    /// PDF/A requires an output intent with an ICC profile.
+    MissingOutputIntent,
+    /// PDF/A requires XMP metadata in the document catalog.
+    MissingXmpMetadata,
+    /// The XMP metadata doesn't identify the document as PDF/A of this part.
+    MissingIdentification {
+        part: u8,
+    },
+    Encrypted,
+    JavaScript,
+    /// Fonts, that are not embedded. PDF/A requires all fonts to be embedded.
+    FontsNotEmbedded {
+        count: usize,
+    },
+}
+
+impl Display for PdfViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PdfViolation::MissingOutputIntent => write!(f, "no output intent"),
+            PdfViolation::MissingXmpMetadata => write!(f, "no XMP metadata"),
+            PdfViolation::MissingIdentification { part } => {
+                write!(
+                    f,
+                    "XMP metadata doesn't identify the document as PDF/A-{part}"
+                )
+            }
+            PdfViolation::Encrypted => write!(f, "document is encrypted"),
+            PdfViolation::JavaScript => write!(f, "document contains JavaScript"),
+            PdfViolation::FontsNotEmbedded { count } => write!(f, "{count} font(s) not embedded"),
+        }
+    }
+}
+
+/// Structural checks of a PDF, that was exported with a PDF/A standard. They don't replace a
+/// full validator like veraPDF, but catch the most common reasons for rejected archival files.
+/// Returns no violations, if none of `standards` is a PDF/A standard.
+///
+/// typst-pdf itself reports violations, that it can detect while exporting, as errors of the
+/// export.
+///
+/// Only the objects outside of streams are checked (except for the XMP metadata), so the
+/// compressed data of content, font and image streams can't cause violations.
+pub fn validate_pdf_a(pdf: &[u8], standards: &[PdfStandard]) -> Vec<PdfViolation> {
+    let Some(part) = standards.iter().find_map(pdf_a_part) else {
+        return Vec::new();
+    };
+    let objects = without_stream_data(pdf);
+    let mut violations = Vec::new();
+    if count_names(&objects, b"/OutputIntents") == 0 {
+        violations.push(PdfViolation::MissingOutputIntent);
+    }
+    // The identification is in the stream of the XMP metadata, which is not compressed.
+    if !contains(&objects, b"/Type /Metadata") {
+        violations.push(PdfViolation::MissingXmpMetadata);
+    } else if !contains(pdf, format!("pdfaid:part>{part}<").as_bytes())
+        && !contains(pdf, format!("pdfaid:part=\"{part}\"").as_bytes())
+    {
+        violations.push(PdfViolation::MissingIdentification { part });
+    }
+    if count_names(&objects, b"/Encrypt") > 0 {
+        violations.push(PdfViolation::Encrypted);
+    }
+    if count_names(&objects, b"/JavaScript") > 0 || count_names(&objects, b"/JS") > 0 {
+        violations.push(PdfViolation::JavaScript);
+    }
+    let font_descriptors = count_names(&objects, b"/Type /FontDescriptor");
+    let font_files = count_names(&objects, b"/FontFile")
+        + count_names(&objects, b"/FontFile2")
+        + count_names(&objects, b"/FontFile3");
+    if font_files < font_descriptors {
+        violations.push(PdfViolation::FontsNotEmbedded {
+            count: font_descriptors - font_files,
+        });
+    }
+    violations
+}
+
+fn pdf_a_part(standard: &PdfStandard) -> Option<u8> {
+    match standard {
+        PdfStandard::A_2b => Some(2),
+        _ => None,
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+/// Occurrences of the name `name` (e.g. `/JS`), that are not the prefix of a longer name.
+fn count_names(haystack: &[u8], name: &[u8]) -> usize {
+    haystack
+        .windows(name.len())
+        .enumerate()
+        .filter(|(start, window)| {
+            *window == name
+                && haystack
+                    .get(start + name.len())
+                    .is_none_or(|next| is_delimiter(*next))
+        })
+        .count()
+}
+
+/// Whitespace and delimiters, that end a name.
+fn is_delimiter(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || b"()<>[]{}/%".contains(&byte)
+}
+
+/// The PDF without the data between `stream` and `endstream`.
+fn without_stream_data(pdf: &[u8]) -> Vec<u8> {
+    let mut objects = Vec::with_capacity(pdf.len());
+    let mut rest = pdf;
+    while let Some(start) = find(rest, b"stream") {
+        let (before, after) = rest.split_at(start);
+        objects.extend_from_slice(before);
+        // `stream` starts the data only after the dictionary of the stream.
+        if !before.trim_ascii_end().ends_with(b">>") {
+            objects.extend_from_slice(b"stream");
+            rest = &after[b"stream".len()..];
+            continue;
+        }
+        let Some(end) = find(after, b"endstream") else {
+            return objects;
+        };
+        rest = &after[end + b"endstream".len()..];
+    }
+    objects.extend_from_slice(rest);
+    objects
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,83 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use typst::diag::FileResult;
+use typst::foundations::{Bytes, Plugin};
+use typst::syntax::{FileId, Source};
+
+use crate::file_resolver::FileResolver;
+use crate::util::not_found;
+use crate::{FileIdNewType, TypstAsLibError};
+
+/// Serves WASM plugins for typst's `plugin()`.
+///
+/// Typst memoizes compiled plugins by their bytes. This resolver always returns the same
+/// `Bytes`, so their hash is computed only once, and `PluginResolver::preload()` compiles the
+/// plugins before the first compilation. Compiled plugins are dropped, when the cache of typst
+/// is evicted (see `EvictionStrategy`), and then compiled again on their next use.
+///
+/// Example:
+/// ```rust,no_run
+/// # use typst_as_lib::plugins::PluginResolver;
+/// # use typst_as_lib::TypstTemplate;
+/// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+/// # static DIAGRAM_WASM: &[u8] = &[];
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// let plugins = PluginResolver::new().with_plugin("/plugins/diagram.wasm", DIAGRAM_WASM);
+/// plugins.preload()?;
+/// let template = TypstTemplate::new(fonts, TEMPLATE).add_file_resolver(plugins);
+/// // In typst: #let diagram = plugin("/plugins/diagram.wasm")
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PluginResolver {
+    plugins: HashMap<FileId, Bytes>,
+}
+
+impl PluginResolver {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_plugin<F, B>(mut self, path: F, wasm: B) -> Self
+    where
+        F: Into<FileIdNewType>,
+        B: Into<Bytes>,
+    {
+        let FileIdNewType(id) = path.into();
+        self.plugins.insert(id, wasm.into());
+        self
+    }
+
+    /// Compile all plugins. Fails, if one of them is not a valid plugin.
+    pub fn preload(&self) -> Result<(), TypstAsLibError> {
+        for (id, wasm) in &self.plugins {
+            Plugin::new(wasm.clone()).map_err(|err| {
+                TypstAsLibError::InvalidConfig(format!("invalid plugin {id:?}: {err}"))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl FileResolver for PluginResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        self.plugins
+            .get(&id)
+            .map(Cow::Borrowed)
+            .ok_or_else(|| not_found(id))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        Err(not_found(id))
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.plugins.values().map(|wasm| wasm.len()).sum()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.plugins.keys().copied().collect()
+    }
+}
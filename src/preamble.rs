@@ -0,0 +1,69 @@
+use typst::syntax::{FileId, Source};
+
+use crate::{TypstTemplate, TypstTemplateCollection};
+
+/// File name of the generated main file, next to the actual main file.
+const PREAMBLE_FILE_NAME: &str = "__preamble__.typ";
+
+impl TypstTemplateCollection {
+    /// Apply `preamble` to each compiled template, e.g. to import a shared style and apply its
+    /// `#show` rules. The preamble is not concatenated with the template: a main file is
+    /// generated next to the template, which contains the preamble followed by
+    /// `#include "<template>"`. So diagnostics keep pointing into the template.
+    ///
+    /// Set and show rules of the preamble apply to the template. Imports of the preamble are
+    /// not visible in the template. Relative paths in the preamble are resolved from the
+    /// directory of the template.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use typst_as_lib::TypstTemplateCollection;
+    /// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+    /// # let fonts = vec![font];
+    /// let template_collection = TypstTemplateCollection::new(fonts)
+    ///     .with_preamble(r#"
+    ///         #import "@preview/acme-style:0.1.0": style
+    ///         #show: style.with(accent: blue)
+    ///     "#);
+    /// ```
+    pub fn with_preamble<S>(mut self, preamble: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.with_preamble_mut(preamble);
+        self
+    }
+
+    /// Apply `preamble` to each compiled template.
+    /// See `TypstTemplateCollection::with_preamble()`.
+    pub fn with_preamble_mut<S>(&mut self, preamble: S)
+    where
+        S: Into<String>,
+    {
+        self.preamble = Some(preamble.into());
+    }
+
+    /// The generated main file, that includes `main_source_id` after the preamble.
+    pub(crate) fn preamble_source(&self, main_source_id: FileId) -> Option<Source> {
+        let preamble = self.preamble.as_ref()?;
+        let vpath = main_source_id.vpath();
+        let file_name = vpath.as_rootless_path().file_name()?.to_str()?;
+        let id = main_source_id.join(PREAMBLE_FILE_NAME);
+        let file_name = file_name.replace('\\', "\\\\").replace('"', "\\\"");
+        Some(Source::new(
+            id,
+            format!("{preamble}\n#include \"{file_name}\"\n"),
+        ))
+    }
+}
+
+impl TypstTemplate {
+    /// Apply `preamble` to the template. See `TypstTemplateCollection::with_preamble()`.
+    pub fn with_preamble<S>(mut self, preamble: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.collection.with_preamble_mut(preamble);
+        self
+    }
+}
@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use typst::diag::FileResult;
+use typst::syntax::package::PackageSpec;
+
+use crate::package_resolver::{PackageResolver, PackageResolverCache};
+use crate::vendor::scan_dir;
+
+/// Keeps the cache of a `PackageResolver` warm on a background thread, so packages are not
+/// downloaded while compiling. Missing packages are downloaded immediately, all packages are
+/// refreshed every `interval` (see `PackageResolver::revalidate()`). Template directories are
+/// scanned for package imports in every round. Stops, when dropped.
+///
+/// Example:
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// # use typst_as_lib::package_resolver::PackageResolverBuilder;
+/// # use typst_as_lib::prefetch::PackagePrefetcher;
+/// # use typst_as_lib::TypstTemplate;
+/// # static source: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// let resolver = Arc::new(PackageResolverBuilder::new().with_file_system_cache().build());
+/// let prefetcher = PackagePrefetcher::new()
+///     .with_template_dir("./templates")
+///     .with_package("@preview/cetz:0.3.1".parse().unwrap())
+///     .spawn(Arc::clone(&resolver), Duration::from_secs(60 * 60));
+/// let template = TypstTemplate::new(fonts, source).add_file_resolver(resolver);
+/// // e.g. in a health check:
+/// let status = prefetcher.status();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PackagePrefetcher {
+    packages: Vec<PackageSpec>,
+    template_dirs: Vec<PathBuf>,
+}
+
+impl PackagePrefetcher {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_package(mut self, package: PackageSpec) -> Self {
+        self.packages.push(package);
+        self
+    }
+
+    /// Prefetch the packages, that the `.typ` files in `dir` (recursively) import.
+    pub fn with_template_dir<P>(mut self, dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.template_dirs.push(dir.into());
+        self
+    }
+
+    /// Start the background thread. The first round starts immediately.
+    pub fn spawn<R>(self, resolver: Arc<R>, interval: Duration) -> PrefetchHandle
+    where
+        R: CachePackages + Send + Sync + 'static,
+    {
+        let status = Arc::new(Mutex::new(PrefetchStatus::default()));
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn({
+            let status = Arc::clone(&status);
+            move || loop {
+                self.run_round(resolver.as_ref(), &status);
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    _ => break,
+                }
+            }
+        });
+        PrefetchHandle {
+            status,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    fn run_round<R>(&self, resolver: &R, status: &Mutex<PrefetchStatus>)
+    where
+        R: CachePackages,
+    {
+        let Self {
+            packages,
+            template_dirs,
+        } = self;
+        let mut errors = Vec::new();
+        let mut round_packages = packages.clone();
+        for dir in template_dirs {
+            if let Err(err) = scan_dir(dir, &mut round_packages) {
+                errors.push(format!("{}: {err}", dir.display()));
+            }
+        }
+        round_packages.sort_by_key(ToString::to_string);
+        round_packages.dedup();
+
+        for package in round_packages {
+            let cached = lock(status).packages.iter().any(|prefetched| {
+                prefetched.package == package && prefetched.last_refresh.is_some()
+            });
+            let result = if cached {
+                resolver.refresh_package(&package)
+            } else {
+                resolver.cache_package(&package)
+            };
+            let now = Utc::now();
+            let mut status = lock(status);
+            let index = match status
+                .packages
+                .iter()
+                .position(|prefetched| prefetched.package == package)
+            {
+                Some(index) => index,
+                None => {
+                    status.packages.push(PrefetchedPackage {
+                        package: package.clone(),
+                        last_refresh: None,
+                        last_error: None,
+                    });
+                    status.packages.len() - 1
+                }
+            };
+            let prefetched = &mut status.packages[index];
+            match result {
+                Ok(_) => {
+                    prefetched.last_refresh = Some(now);
+                    prefetched.last_error = None;
+                }
+                Err(err) => prefetched.last_error = Some(err.to_string()),
+            }
+        }
+
+        let mut status = lock(status);
+        status.rounds += 1;
+        status.last_round = Some(Utc::now());
+        status.errors = errors;
+    }
+}
+
+/// Package resolvers with a cache, that `PackagePrefetcher` can fill.
+pub trait CachePackages {
+    /// Download the package, if it is not cached. Returns `true`, if it was downloaded.
+    fn cache_package(&self, package: &PackageSpec) -> FileResult<bool>;
+
+    /// Download the package again, if it changed. Returns `true`, if it was downloaded.
+    fn refresh_package(&self, package: &PackageSpec) -> FileResult<bool>;
+}
+
+impl<C> CachePackages for PackageResolver<C>
+where
+    C: PackageResolverCache,
+{
+    fn cache_package(&self, package: &PackageSpec) -> FileResult<bool> {
+        self.prefetch_package(package)
+    }
+
+    fn refresh_package(&self, package: &PackageSpec) -> FileResult<bool> {
+        self.revalidate_cached(package)
+    }
+}
+
+/// The running `PackagePrefetcher`. Stops the background thread, when dropped.
+pub struct PrefetchHandle {
+    status: Arc<Mutex<PrefetchStatus>>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PrefetchHandle {
+    pub fn status(&self) -> PrefetchStatus {
+        lock(&self.status).clone()
+    }
+
+    /// Stop the background thread and wait for the current round to finish.
+    pub fn stop(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        // Dropping the sender wakes up the thread.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for PrefetchHandle {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+/// Progress of a `PackagePrefetcher`.
+#[derive(Debug, Clone, Default)]
+pub struct PrefetchStatus {
+    /// Number of finished rounds.
+    pub rounds: u64,
+    pub last_round: Option<DateTime<Utc>>,
+    pub packages: Vec<PrefetchedPackage>,
+    /// Template directories, that could not be scanned in the last round.
+    pub errors: Vec<String>,
+}
+
+impl PrefetchStatus {
+    /// Whether all packages are cached and the last refresh of each package succeeded.
+    pub fn is_healthy(&self) -> bool {
+        self.rounds > 0
+            && self.errors.is_empty()
+            && self
+                .packages
+                .iter()
+                .all(|prefetched| prefetched.last_error.is_none())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrefetchedPackage {
+    pub package: PackageSpec,
+    /// Last time, the package was cached or refreshed successfully.
+    pub last_refresh: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+fn lock(status: &Mutex<PrefetchStatus>) -> std::sync::MutexGuard<'_, PrefetchStatus> {
+    status
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
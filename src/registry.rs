@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use typst::foundations::Dict;
+use typst::syntax::FileId;
+
+use crate::schema::InputValidationError;
+use crate::FileIdNewType;
+
+/// Validates the inputs of a template, before it gets compiled. Implemented by `InputSchema`
+/// and closures.
+pub trait InputValidator {
+    fn validate(&self, inputs: &Dict) -> Result<(), InputValidationError>;
+}
+
+impl<F> InputValidator for F
+where
+    F: Fn(&Dict) -> Result<(), InputValidationError>,
+{
+    fn validate(&self, inputs: &Dict) -> Result<(), InputValidationError> {
+        self(inputs)
+    }
+}
+
+/// Templates of a `TypstTemplateCollection`, registered by a logical name.
+/// See `TypstTemplateCollection::register_template()`.
+#[derive(Default)]
+pub struct TemplateRegistry {
+    templates: HashMap<String, RegisteredTemplate>,
+}
+
+pub struct RegisteredTemplate {
+    main_source_id: FileId,
+    input_validator: Option<Box<dyn InputValidator + Send + Sync + 'static>>,
+}
+
+impl RegisteredTemplate {
+    pub fn new<F>(main_source_id: F) -> Self
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(main_source_id) = main_source_id.into();
+        Self {
+            main_source_id,
+            input_validator: None,
+        }
+    }
+
+    /// Validate the inputs before compiling, e.g. with an `InputSchema`.
+    pub fn with_input_validator<V>(mut self, input_validator: V) -> Self
+    where
+        V: InputValidator + Send + Sync + 'static,
+    {
+        self.input_validator = Some(Box::new(input_validator));
+        self
+    }
+
+    pub fn main_source_id(&self) -> FileId {
+        self.main_source_id
+    }
+
+    pub(crate) fn input_validator(&self) -> Option<&(dyn InputValidator + Send + Sync)> {
+        self.input_validator.as_deref()
+    }
+}
+
+impl TemplateRegistry {
+    /// Registers the template under the name. A template, that was registered with the same name
+    /// before, gets replaced.
+    pub fn register<S>(&mut self, name: S, template: RegisteredTemplate)
+    where
+        S: Into<String>,
+    {
+        self.templates.insert(name.into(), template);
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Option<RegisteredTemplate> {
+        self.templates.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegisteredTemplate> {
+        self.templates.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(String::as_str)
+    }
+}
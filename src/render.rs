@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use typst::diag::Warned;
+use typst::foundations::{Dict, Smart};
+use typst::layout::Page;
+use typst::visualize::{Color, Paint};
+
+use crate::pages::PageSelection;
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Options for rendering pages as PNG. See
+/// `TypstTemplateCollection::compile_to_png_with_options()`.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::pages::PageSelection;
+/// # use typst_as_lib::render::{RasterBackground, RasterOptions};
+/// // Thumbnail of the first page, that fits into 200x200 pixels.
+/// let thumbnail = RasterOptions {
+///     pages: PageSelection::First,
+///     max_width: Some(200),
+///     max_height: Some(200),
+///     ..Default::default()
+/// };
+/// // All pages in 300 DPI with transparent background.
+/// let print = RasterOptions {
+///     pixel_per_pt: 300.0 / 72.0,
+///     background: RasterBackground::Transparent,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct RasterOptions {
+    /// Pages to render. Default: all pages.
+    pub pages: PageSelection,
+    /// Resolution, e.g. `2.0` for 144 DPI. Default: `1.0` (72 DPI).
+    pub pixel_per_pt: f32,
+    /// Resolution of single pages by page number (1-based), overriding `pixel_per_pt`.
+    pub page_pixel_per_pt: HashMap<usize, f32>,
+    pub background: RasterBackground,
+    /// Pages, that would be wider or higher, are downscaled to fit.
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+}
+
+impl Default for RasterOptions {
+    fn default() -> Self {
+        Self {
+            pages: Default::default(),
+            pixel_per_pt: 1.0,
+            page_pixel_per_pt: Default::default(),
+            background: Default::default(),
+            max_width: None,
+            max_height: None,
+        }
+    }
+}
+
+/// Background of rendered pages.
+#[derive(Debug, Clone, Default)]
+pub enum RasterBackground {
+    /// The fill of the page, which is white, unless the document sets another one.
+    #[default]
+    Page,
+    Transparent,
+    Color(Color),
+}
+
+impl RasterOptions {
+    /// Render the page with number `page_number` (1-based) as PNG.
+    fn render(&self, page: &Page, page_number: usize) -> Result<Vec<u8>, TypstAsLibError> {
+        let Self {
+            pixel_per_pt,
+            page_pixel_per_pt,
+            background,
+            max_width,
+            max_height,
+            ..
+        } = self;
+        let mut pixel_per_pt = page_pixel_per_pt
+            .get(&page_number)
+            .copied()
+            .unwrap_or(*pixel_per_pt);
+        let size = page.frame.size();
+        for (max, length) in [(max_width, size.x.to_pt()), (max_height, size.y.to_pt())] {
+            if let Some(max) = max {
+                if length > 0.0 {
+                    pixel_per_pt = pixel_per_pt.min((f64::from(*max) / length) as f32);
+                }
+            }
+        }
+        let fill = match background {
+            RasterBackground::Page => None,
+            RasterBackground::Transparent => Some(Smart::Custom(None)),
+            RasterBackground::Color(color) => Some(Smart::Custom(Some(Paint::Solid(*color)))),
+        };
+        let pixmap = if let Some(fill) = fill {
+            let mut page = page.clone();
+            page.fill = fill;
+            typst_render::render(&page, pixel_per_pt)
+        } else {
+            typst_render::render(page, pixel_per_pt)
+        };
+        pixmap
+            .encode_png()
+            .map_err(|err| TypstAsLibError::Export(err.to_string()))
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Compile with a `Dict` as input and render the selected pages as PNG with
+    /// `typst_render::render()`. Returns one PNG per page. `pixel_per_pt` sets the
+    /// resolution (e.g. `2.0` for 144 DPI).
+    pub fn compile_to_png<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        pages: &PageSelection,
+        pixel_per_pt: f32,
+    ) -> Warned<Result<Vec<Vec<u8>>, TypstAsLibError>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let options = RasterOptions {
+            pages: pages.clone(),
+            pixel_per_pt,
+            ..Default::default()
+        };
+        self.compile_to_png_with_options(main_source_id, inputs, &options)
+    }
+
+    /// Compile with a `Dict` as input and render the selected pages as PNG. Returns one PNG
+    /// per page. See `RasterOptions`. With feature `rayon`, the pages are rendered in
+    /// parallel.
+    pub fn compile_to_png_with_options<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        options: &RasterOptions,
+    ) -> Warned<Result<Vec<Vec<u8>>, TypstAsLibError>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let Warned { output, warnings } = self.compile_with_input(main_source_id, inputs);
+        let output = output.and_then(|document| {
+            options
+                .pages
+                .map_pages(&document, |index, page| options.render(page, index + 1))
+                .into_iter()
+                .collect()
+        });
+        Warned { output, warnings }
+    }
+}
+
+impl TypstTemplate {
+    /// Compile with a `Dict` as input and render the selected pages as PNG.
+    /// See `TypstTemplateCollection::compile_to_png()`.
+    pub fn compile_to_png<D>(
+        &self,
+        inputs: D,
+        pages: &PageSelection,
+        pixel_per_pt: f32,
+    ) -> Warned<Result<Vec<Vec<u8>>, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        let options = RasterOptions {
+            pages: pages.clone(),
+            pixel_per_pt,
+            ..Default::default()
+        };
+        self.compile_to_png_with_options(inputs, &options)
+    }
+
+    /// Compile with a `Dict` as input and render the selected pages as PNG.
+    /// See `RasterOptions`.
+    pub fn compile_to_png_with_options<D>(
+        &self,
+        inputs: D,
+        options: &RasterOptions,
+    ) -> Warned<Result<Vec<Vec<u8>>, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        let inputs = match self.validated_input(inputs) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                return Warned {
+                    output: Err(err),
+                    warnings: Default::default(),
+                }
+            }
+        };
+        self.collection
+            .compile_to_png_with_options(self.source_id, inputs, options)
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Duration;
+
+use ecow::eco_format;
+use typst::diag::{FileError, SourceDiagnostic};
+use typst::layout::{Frame, FrameItem};
+use typst::model::Document;
+use typst::syntax::{package::PackageSpec, FileId, Source, Span};
+
+use crate::file_resolver::FileResolverStats;
+#[cfg(feature = "pdf")]
+use crate::pages::PageSelection;
+use crate::resolver_middleware::ResolveKind;
+
+/// Output of a compilation together with a `CompilationReport`.
+#[derive(Debug, Clone)]
+pub struct Reported<T> {
+    pub output: T,
+    pub report: CompilationReport,
+}
+
+/// Additional information, that was collected during a compilation.
+#[derive(Debug, Clone, Default)]
+pub struct CompilationReport {
+    /// Font lookups, that failed or fell back to a font, that could not display the text.
+    pub missing_fonts: Vec<MissingFont>,
+    /// Binaries, that could not be resolved and were served by the fallback file resolver.
+    pub substituted_files: Vec<SubstitutedFile>,
+    /// Sources, that were not valid UTF-8 and were decoded by a `DecodingResolver`. The
+    /// compilation also warns about each of them.
+    pub decoded_sources: Vec<DecodedSource>,
+    pub timings: CompileTimings,
+    /// Number of distinct files, that each file resolver (by `FileResolver::name()`) served.
+    pub files_resolved_per_resolver: HashMap<String, usize>,
+    /// Which file resolver served each file and how long the file resolvers took, slowest
+    /// first.
+    pub resolved_files: Vec<ResolvedFile>,
+    /// The following counters are the difference of `FileResolver::stats()` before and after
+    /// the compilation. When file resolvers are shared between concurrent compilations, they
+    /// contain the counts of all of them.
+    pub packages_downloaded: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Size of the largest source file in bytes.
+    pub largest_source_bytes: usize,
+    /// Packages, from which files were read, sorted by name and version.
+    pub packages: Vec<UsedPackage>,
+    /// Fonts, that were embedded into the exported PDF. Only set by
+    /// `compile_to_pdf_with_report()` (feature `pdf`).
+    pub embedded_fonts: Vec<EmbeddedFont>,
+    /// Accessibility problems of the exported pages, e.g. to block releases of documents with
+    /// images without alternative text. Only set by `compile_to_pdf_with_report()` (feature
+    /// `pdf`). typst-pdf doesn't tag the content of the PDF (PDF/UA), so screen readers can't
+    /// tell headings, tables and reading order apart, even if there are no issues.
+    pub accessibility_issues: Vec<AccessibilityIssue>,
+    /// The document was taken from the `ResultCache` without compiling. All other fields
+    /// are empty then.
+    pub from_result_cache: bool,
+}
+
+/// Wall-clock time of the phases of a compilation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompileTimings {
+    pub library_injection: Duration,
+    /// Time spent in file resolvers. Is part of `compile`.
+    pub resolve: Duration,
+    /// Time of `typst::compile()`.
+    pub compile: Duration,
+    pub eviction: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubstitutedFile {
+    pub id: FileId,
+    /// Error of the file resolvers, that failed to resolve the file.
+    pub error: FileError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedSource {
+    pub id: FileId,
+    /// E.g. `UTF-16` or `lossy UTF-8`. See `SourceDecoding`.
+    pub encoding: &'static str,
+}
+
+/// Resolutions of a file during a compilation. See `CompilationReport::resolved_files`.
+#[derive(Debug, Clone)]
+pub struct ResolvedFile {
+    pub id: FileId,
+    pub kind: ResolveKind,
+    /// Name of the file resolver, that served the file, or `None`, if no file resolver could.
+    pub resolver: Option<String>,
+    /// Time of all file resolvers, that were asked for the file, including those, that
+    /// failed.
+    pub duration: Duration,
+    /// Number of file resolvers, that were asked for the file.
+    pub attempts: usize,
+}
+
+/// A package, that was used by a compilation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsedPackage {
+    pub package: PackageSpec,
+    /// Imported by a file of the template, not only by other packages.
+    pub direct: bool,
+}
+
+/// A font, that is embedded into a PDF. typst-pdf embeds only the used glyphs (subsetting).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedFont {
+    pub family: String,
+    /// Style, weight and stretch.
+    pub variant: String,
+    /// Number of distinct glyphs, that are used in the document.
+    pub glyphs: usize,
+    /// Size of the whole font file in bytes.
+    pub font_bytes: usize,
+    /// Size of the embedded subset in bytes, before the PDF stream is compressed.
+    pub embedded_bytes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MissingFont {
+    /// A font family was requested, but is not contained in the font book.
+    UnknownFamily(String),
+    /// The font with this index in the font book could not be loaded.
+    FailedToLoad(usize),
+    /// Text was set in a font, that has no glyphs for it.
+    MissingGlyphs { family: String, text: String },
+}
+
+/// An accessibility problem of an exported document.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AccessibilityIssue {
+    /// An image has no alternative text (`image(.., alt: "..")`). Page numbers are 1-based.
+    MissingAltText { page: usize },
+    /// The document has no title (`set document(title: ..)`), which screen readers announce.
+    MissingTitle,
+}
+
+impl CompilationReport {
+    /// Share of cache lookups, that were hits. `None`, if there were no lookups.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    pub(crate) fn record_file_resolver_stats(&mut self, stats: FileResolverStats) {
+        let FileResolverStats {
+            cache_hits,
+            cache_misses,
+            packages_downloaded,
+        } = stats;
+        self.cache_hits = cache_hits;
+        self.cache_misses = cache_misses;
+        self.packages_downloaded = packages_downloaded;
+    }
+
+    pub(crate) fn record_missing_font(&mut self, missing_font: MissingFont) {
+        if !self.missing_fonts.contains(&missing_font) {
+            self.missing_fonts.push(missing_font);
+        }
+    }
+
+    pub(crate) fn record_substituted_file(&mut self, substituted_file: SubstitutedFile) {
+        let id = substituted_file.id;
+        if !self.substituted_files.iter().any(|file| file.id == id) {
+            self.substituted_files.push(substituted_file);
+        }
+    }
+
+    pub(crate) fn record_decoded_source(&mut self, decoded_source: DecodedSource) {
+        if !self.decoded_sources.contains(&decoded_source) {
+            self.decoded_sources.push(decoded_source);
+        }
+    }
+
+    /// Warnings about the decoded sources, that are added to the warnings of typst.
+    pub(crate) fn decoded_source_warnings(&self) -> impl Iterator<Item = SourceDiagnostic> + '_ {
+        self.decoded_sources
+            .iter()
+            .map(|DecodedSource { id, encoding }| {
+                SourceDiagnostic::warning(
+                    Span::detached(),
+                    eco_format!(
+                        "{} is not valid UTF-8 and was decoded as {encoding}",
+                        id.vpath().as_rootless_path().display()
+                    ),
+                )
+                .with_hint("save the file as UTF-8")
+            })
+    }
+
+    /// Typst warns about font families, that are not in the font book.
+    pub(crate) fn collect_unknown_font_families(&mut self, warnings: &[SourceDiagnostic]) {
+        for warning in warnings {
+            if let Some(family) = warning.message.strip_prefix("unknown font family: ") {
+                self.record_missing_font(MissingFont::UnknownFamily(family.to_owned()));
+            }
+        }
+    }
+
+    /// Glyph id 0 is the `.notdef` glyph, which gets rendered, when no font could
+    /// display a character.
+    pub(crate) fn collect_missing_glyphs(&mut self, document: &Document) {
+        for page in &document.pages {
+            self.collect_missing_glyphs_in_frame(&page.frame);
+        }
+    }
+
+    /// Issues of the selected `pages`.
+    #[cfg(feature = "pdf")]
+    pub(crate) fn collect_accessibility_issues(
+        &mut self,
+        document: &Document,
+        pages: &PageSelection,
+    ) {
+        if document.info.title.is_none() {
+            self.accessibility_issues
+                .push(AccessibilityIssue::MissingTitle);
+        }
+        for index in pages.indices(document.pages.len()) {
+            let mut missing_alt_texts = 0;
+            visit_frame(&document.pages[index].frame, &mut |item| {
+                if let FrameItem::Image(image, ..) = item {
+                    if image.alt().is_none() {
+                        missing_alt_texts += 1;
+                    }
+                }
+            });
+            self.accessibility_issues.extend(
+                (0..missing_alt_texts)
+                    .map(|_| AccessibilityIssue::MissingAltText { page: index + 1 }),
+            );
+        }
+    }
+
+    fn collect_missing_glyphs_in_frame(&mut self, frame: &Frame) {
+        for (_, item) in frame.items() {
+            match item {
+                FrameItem::Group(group) => self.collect_missing_glyphs_in_frame(&group.frame),
+                FrameItem::Text(text) => {
+                    let missing: String = text
+                        .glyphs
+                        .iter()
+                        .filter(|glyph| glyph.id == 0)
+                        .filter_map(|glyph| text.text.get(glyph.range()))
+                        .collect();
+                    if !missing.is_empty() {
+                        self.record_missing_font(MissingFont::MissingGlyphs {
+                            family: text.font.info().family.clone(),
+                            text: missing,
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// One call of a file resolver during resolution of a file.
+pub(crate) struct ResolveAttempt<'a> {
+    pub(crate) resolver: &'a str,
+    pub(crate) id: FileId,
+    pub(crate) kind: ResolveKind,
+    pub(crate) duration: Duration,
+    pub(crate) result: Result<(), &'a FileError>,
+}
+
+/// Which file resolvers can serve a file, e.g. to find out, why an old version of a file is
+/// used. See `TypstTemplateCollection::explain_resolution()`.
+#[derive(Debug, Clone)]
+pub struct ResolutionExplanation {
+    pub id: FileId,
+    /// All file resolvers in the order, in which they are consulted.
+    pub candidates: Vec<ResolutionCandidate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolutionCandidate {
+    /// `FileResolver::name()`
+    pub resolver: String,
+    pub source: Result<(), FileError>,
+    pub binary: Result<(), FileError>,
+}
+
+impl ResolutionExplanation {
+    /// The file resolver, that serves the file as source (e.g. `.typ` files).
+    pub fn source_winner(&self) -> Option<&ResolutionCandidate> {
+        self.candidates
+            .iter()
+            .find(|candidate| candidate.source.is_ok())
+    }
+
+    /// The file resolver, that serves the file as binary (e.g. images).
+    pub fn binary_winner(&self) -> Option<&ResolutionCandidate> {
+        self.candidates
+            .iter()
+            .find(|candidate| candidate.binary.is_ok())
+    }
+
+    /// File resolvers, that could serve the file as source, but are shadowed by the winner.
+    pub fn shadowed_sources(&self) -> Vec<&ResolutionCandidate> {
+        self.candidates
+            .iter()
+            .filter(|candidate| candidate.source.is_ok())
+            .skip(1)
+            .collect()
+    }
+
+    /// File resolvers, that could serve the file as binary, but are shadowed by the winner.
+    pub fn shadowed_binaries(&self) -> Vec<&ResolutionCandidate> {
+        self.candidates
+            .iter()
+            .filter(|candidate| candidate.binary.is_ok())
+            .skip(1)
+            .collect()
+    }
+}
+
+impl std::fmt::Display for ResolutionExplanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:?}", self.id)?;
+        // By index, several file resolvers can have the same name.
+        let source_winner = self
+            .candidates
+            .iter()
+            .position(|candidate| candidate.source.is_ok());
+        let binary_winner = self
+            .candidates
+            .iter()
+            .position(|candidate| candidate.binary.is_ok());
+        for (index, candidate) in self.candidates.iter().enumerate() {
+            let ResolutionCandidate {
+                resolver,
+                source,
+                binary,
+            } = candidate;
+            let describe = |result: &Result<(), FileError>, winner: Option<usize>| match result {
+                Ok(()) if winner == Some(index) => "used".to_owned(),
+                Ok(()) => "shadowed".to_owned(),
+                Err(error) => format!("not resolved ({error})"),
+            };
+            writeln!(
+                f,
+                "  {index}: {resolver}: source {}, binary {}",
+                describe(source, source_winner),
+                describe(binary, binary_winner),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the `CompilationReport` during a compilation.
+#[derive(Debug, Default)]
+pub(crate) struct ReportRecorder {
+    pub(crate) report: CompilationReport,
+    resolved: HashSet<(FileId, ResolveKind)>,
+    resolved_files: HashMap<(FileId, ResolveKind), ResolvedFile>,
+    used_packages: HashSet<PackageSpec>,
+    /// Packages, that files of the template (not of packages) import.
+    direct_imports: HashSet<PackageSpec>,
+}
+
+impl ReportRecorder {
+    pub(crate) fn finish(self) -> CompilationReport {
+        let Self {
+            mut report,
+            used_packages,
+            direct_imports,
+            resolved_files,
+            ..
+        } = self;
+        report.resolved_files = resolved_files.into_values().collect();
+        report
+            .resolved_files
+            .sort_by_key(|file| std::cmp::Reverse(file.duration));
+        let packages: BTreeMap<_, _> = used_packages
+            .into_iter()
+            .map(|package| {
+                let key = (
+                    package.namespace.clone(),
+                    package.name.clone(),
+                    package.version,
+                );
+                let direct = direct_imports.contains(&package);
+                (key, UsedPackage { package, direct })
+            })
+            .collect();
+        report.packages = packages.into_values().collect();
+        report
+    }
+
+    /// Record the package of a file, that was read.
+    pub(crate) fn record_file(&mut self, id: FileId) {
+        if let Some(package) = id.package() {
+            self.used_packages.insert(package.clone());
+        }
+    }
+
+    /// Record the package of a source and, for files of the template, the packages, that it
+    /// imports. Package specs are found without parsing, so they also count, if they are only
+    /// mentioned in a string.
+    pub(crate) fn record_source(&mut self, source: &Source) {
+        self.record_file(source.id());
+        if source.id().package().is_some() {
+            return;
+        }
+        let text = source.text();
+        for (start, _) in text.match_indices("\"@") {
+            let string = &text[start + 1..];
+            let Some(end) = string.find('"') else {
+                continue;
+            };
+            if let Ok(package) = PackageSpec::from_str(&string[..end]) {
+                self.direct_imports.insert(package);
+            }
+        }
+    }
+
+    pub(crate) fn record_resolve_attempt(&mut self, attempt: &ResolveAttempt) {
+        let ResolveAttempt {
+            resolver,
+            id,
+            kind,
+            duration,
+            result,
+        } = attempt;
+        self.report.timings.resolve += *duration;
+        let resolved_file =
+            self.resolved_files
+                .entry((*id, *kind))
+                .or_insert_with(|| ResolvedFile {
+                    id: *id,
+                    kind: *kind,
+                    resolver: None,
+                    duration: Duration::ZERO,
+                    attempts: 0,
+                });
+        resolved_file.duration += *duration;
+        resolved_file.attempts += 1;
+        if result.is_ok() {
+            resolved_file.resolver = Some(resolver.to_string());
+        }
+        if result.is_ok() && self.resolved.insert((*id, *kind)) {
+            *self
+                .report
+                .files_resolved_per_resolver
+                .entry(resolver.to_string())
+                .or_default() += 1;
+        }
+    }
+
+    pub(crate) fn record_source_bytes(&mut self, bytes: usize) {
+        let largest_source_bytes = &mut self.report.largest_source_bytes;
+        *largest_source_bytes = (*largest_source_bytes).max(bytes);
+    }
+}
+
+#[cfg(feature = "pdf")]
+fn visit_frame<F>(frame: &Frame, f: &mut F)
+where
+    F: FnMut(&FrameItem),
+{
+    for (_, item) in frame.items() {
+        if let FrameItem::Group(group) = item {
+            visit_frame(&group.frame, f);
+        } else {
+            f(item);
+        }
+    }
+}
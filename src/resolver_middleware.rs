@@ -0,0 +1,825 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use ecow::eco_format;
+use typst::{
+    diag::{FileError, FileResult, PackageError},
+    foundations::Bytes,
+    syntax::{FileId, Source},
+};
+
+use crate::cached_file_resolver::lock_cache;
+use crate::cancellation::CancellationToken;
+use crate::file_resolver::{FileResolver, FileResolverStats};
+use crate::instrument;
+
+static DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
+const DEFAULT_MAX_RUNNING_RESOLVES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResolveKind {
+    Binary,
+    Source,
+}
+
+/// Passed to the logger of a `LoggingResolver` after each resolution.
+#[derive(Debug, Clone)]
+pub struct ResolveEvent<'a> {
+    pub id: FileId,
+    pub kind: ResolveKind,
+    pub result: Result<(), &'a FileError>,
+    pub duration: Duration,
+}
+
+impl fmt::Display for ResolveEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            id,
+            kind,
+            result,
+            duration,
+        } = self;
+        let package = id
+            .package()
+            .map(|package| format!("{package}"))
+            .unwrap_or_default();
+        let path = id.vpath().as_rootless_path().display();
+        match result {
+            Ok(()) => write!(f, "resolved {kind:?} {package}/{path} in {duration:?}"),
+            Err(error) => write!(
+                f,
+                "failed to resolve {kind:?} {package}/{path} in {duration:?}: {error}"
+            ),
+        }
+    }
+}
+
+type Logger = Arc<dyn Fn(&ResolveEvent) + Send + Sync>;
+
+/// Calls a logger after each resolution of the wrapped file resolver.
+pub struct LoggingResolver<T> {
+    pub file_resolver: T,
+    logger: Logger,
+}
+
+impl<T> LoggingResolver<T> {
+    /// Logs with `log::debug!()` (feature `log`). Without the feature, nothing is logged; pass
+    /// a logger to `LoggingResolver::new_with_logger()` instead.
+    pub fn new(file_resolver: T) -> Self {
+        #[cfg(feature = "log")]
+        let logger = |event: &ResolveEvent| log::debug!("{event}");
+        #[cfg(not(feature = "log"))]
+        let logger = |_: &ResolveEvent| ();
+        Self::new_with_logger(file_resolver, logger)
+    }
+
+    pub fn new_with_logger<L>(file_resolver: T, logger: L) -> Self
+    where
+        L: Fn(&ResolveEvent) + Send + Sync + 'static,
+    {
+        Self {
+            file_resolver,
+            logger: Arc::new(logger),
+        }
+    }
+
+    fn log<R>(&self, id: FileId, kind: ResolveKind, start: Instant, result: &FileResult<R>) {
+        let event = ResolveEvent {
+            id,
+            kind,
+            result: result.as_ref().map(|_| ()),
+            duration: start.elapsed(),
+        };
+        (self.logger)(&event);
+    }
+}
+
+impl<T> FileResolver for LoggingResolver<T>
+where
+    T: FileResolver,
+{
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let start = Instant::now();
+        let result = self.file_resolver.resolve_binary(id);
+        self.log(id, ResolveKind::Binary, start, &result);
+        result
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let start = Instant::now();
+        let result = self.file_resolver.resolve_source(id);
+        self.log(id, ResolveKind::Source, start, &result);
+        result
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.file_resolver.stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.file_resolver.memory_footprint()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.file_resolver.file_ids()
+    }
+
+    fn uses_network(&self) -> bool {
+        self.file_resolver.uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.file_resolver.roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        self.file_resolver.source_encoding(id)
+    }
+
+    fn invalidate(&self, id: FileId) {
+        self.file_resolver.invalidate(id);
+    }
+}
+
+/// Retries the wrapped file resolver, when it fails with a transient error.
+pub struct RetryingResolver<T> {
+    pub file_resolver: T,
+    retries: u32,
+    delay: Duration,
+    is_transient: fn(&FileError) -> bool,
+}
+
+impl<T> RetryingResolver<T> {
+    pub fn new(file_resolver: T, retries: u32) -> Self {
+        Self {
+            file_resolver,
+            retries,
+            delay: DEFAULT_RETRY_DELAY,
+            is_transient: is_transient_error,
+        }
+    }
+
+    /// Time to wait between attempts.
+    pub fn with_delay(self, delay: Duration) -> Self {
+        Self { delay, ..self }
+    }
+
+    /// Decides, which errors are worth a retry. By default, `FileError::Other` and
+    /// network failures of packages are retried.
+    pub fn with_is_transient(self, is_transient: fn(&FileError) -> bool) -> Self {
+        Self {
+            is_transient,
+            ..self
+        }
+    }
+
+    fn retry<R, F>(&self, resolve: F) -> FileResult<R>
+    where
+        F: Fn() -> FileResult<R>,
+    {
+        let Self {
+            retries,
+            delay,
+            is_transient,
+            ..
+        } = self;
+        let mut result = resolve();
+        for _ in 0..*retries {
+            match &result {
+                Err(error) if is_transient(error) => {
+                    thread::sleep(*delay);
+                    result = resolve();
+                }
+                _ => break,
+            }
+        }
+        result
+    }
+}
+
+fn is_transient_error(error: &FileError) -> bool {
+    matches!(
+        error,
+        FileError::Other(_) | FileError::Package(PackageError::NetworkFailed(_))
+    )
+}
+
+impl<T> FileResolver for RetryingResolver<T>
+where
+    T: FileResolver,
+{
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        self.retry(|| self.file_resolver.resolve_binary(id))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        self.retry(|| self.file_resolver.resolve_source(id))
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.file_resolver.stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.file_resolver.memory_footprint()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.file_resolver.file_ids()
+    }
+
+    fn uses_network(&self) -> bool {
+        self.file_resolver.uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.file_resolver.roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        self.file_resolver.source_encoding(id)
+    }
+
+    fn invalidate(&self, id: FileId) {
+        self.file_resolver.invalidate(id);
+    }
+}
+
+/// Fails with `FileError::Other`, when the wrapped file resolver takes longer than the
+/// timeout. The resolution runs on its own thread. After a timeout, its `CancellationToken`
+/// is cancelled, which aborts package downloads. Other work keeps running in the
+/// background, until the wrapped file resolver returns, and the thread is not joined.
+///
+/// So that threads of resolutions, that hang, can't pile up, at most
+/// `with_max_running()` resolutions (including the timed out ones, that are still running)
+/// run at a time. Further resolutions fail immediately with `FileError::Other`.
+pub struct TimeoutResolver<T> {
+    pub file_resolver: Arc<T>,
+    timeout: Duration,
+    max_running: usize,
+    running: Arc<AtomicUsize>,
+}
+
+impl<T> TimeoutResolver<T> {
+    pub fn new(file_resolver: T, timeout: Duration) -> Self {
+        Self {
+            file_resolver: Arc::new(file_resolver),
+            timeout,
+            max_running: DEFAULT_MAX_RUNNING_RESOLVES,
+            running: Default::default(),
+        }
+    }
+
+    /// Maximum number of threads, that resolve files at a time. Defaults to 64.
+    pub fn with_max_running(self, max_running: usize) -> Self {
+        Self {
+            max_running,
+            ..self
+        }
+    }
+}
+
+/// Decrements the number of running resolutions, when the thread of a resolution ends.
+struct RunningGuard(Arc<AtomicUsize>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl<T> TimeoutResolver<T>
+where
+    T: FileResolver + Send + Sync + 'static,
+{
+    fn with_timeout<R, F>(&self, id: FileId, resolve: F) -> FileResult<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&T) -> FileResult<R> + Send + 'static,
+    {
+        let Self {
+            file_resolver,
+            timeout,
+            max_running,
+            running,
+        } = self;
+        if running.fetch_add(1, Ordering::AcqRel) >= *max_running {
+            running.fetch_sub(1, Ordering::AcqRel);
+            return Err(FileError::Other(Some(eco_format!(
+                "Cannot resolve {:?}: {max_running} resolutions are still running",
+                id.vpath()
+            ))));
+        }
+        let running = RunningGuard(running.clone());
+        let file_resolver = file_resolver.clone();
+        let token = CancellationToken::new();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn({
+            let token = token.clone();
+            move || {
+                let _running = running;
+                // The receiver is gone after a timeout.
+                let _ = sender.send(token.run(|| resolve(&file_resolver)));
+            }
+        });
+        receiver.recv_timeout(*timeout).unwrap_or_else(|_| {
+            token.cancel();
+            Err(FileError::Other(Some(eco_format!(
+                "Resolving {:?} timed out after {timeout:?}",
+                id.vpath()
+            ))))
+        })
+    }
+}
+
+impl<T> FileResolver for TimeoutResolver<T>
+where
+    T: FileResolver + Send + Sync + 'static,
+{
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let bytes = self.with_timeout(id, move |file_resolver| {
+            file_resolver.resolve_binary(id).map(Cow::into_owned)
+        })?;
+        Ok(Cow::Owned(bytes))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let source = self.with_timeout(id, move |file_resolver| {
+            file_resolver.resolve_source(id).map(Cow::into_owned)
+        })?;
+        Ok(Cow::Owned(source))
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.file_resolver.stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.file_resolver.memory_footprint()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.file_resolver.file_ids()
+    }
+
+    fn uses_network(&self) -> bool {
+        self.file_resolver.uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.file_resolver.roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        self.file_resolver.source_encoding(id)
+    }
+
+    fn invalidate(&self, id: FileId) {
+        self.file_resolver.invalidate(id);
+    }
+}
+
+type Transcoder = Arc<dyn Fn(&[u8]) -> Option<String> + Send + Sync>;
+
+/// How `DecodingResolver` decodes sources, that are not valid UTF-8. The decoders are tried
+/// in this order: UTF-16, the custom transcoder, lossy UTF-8. Without any, invalid sources
+/// fail with `FileError::InvalidUtf8`, as usual.
+#[derive(Clone, Default)]
+pub struct SourceDecoding {
+    utf16: bool,
+    transcoder: Option<Transcoder>,
+    lossy: bool,
+}
+
+impl SourceDecoding {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Decode UTF-16 with a byte order mark (e.g. saved by Windows editors as "Unicode").
+    /// UTF-16 without a byte order mark is not detected, as its endianness can't be told
+    /// reliably; decode it with `with_transcoder()`.
+    pub fn with_utf16(mut self) -> Self {
+        self.utf16 = true;
+        self
+    }
+
+    /// Decode with `transcoder`, e.g. from Windows-1252 with the `encoding_rs` crate.
+    /// `None` means, that the transcoder can't decode the bytes.
+    pub fn with_transcoder<F>(mut self, transcoder: F) -> Self
+    where
+        F: Fn(&[u8]) -> Option<String> + Send + Sync + 'static,
+    {
+        self.transcoder = Some(Arc::new(transcoder));
+        self
+    }
+
+    /// Replace invalid sequences with `U+FFFD`, as last resort.
+    pub fn with_lossy(mut self) -> Self {
+        self.lossy = true;
+        self
+    }
+
+    /// The text and the name of the encoding, that was used.
+    fn decode(&self, bytes: &[u8]) -> Option<(String, &'static str)> {
+        let Self {
+            utf16,
+            transcoder,
+            lossy,
+        } = self;
+        if *utf16 {
+            if let Some(text) = decode_utf16(bytes) {
+                return Some((text, "UTF-16"));
+            }
+        }
+        if let Some(text) = transcoder.as_ref().and_then(|transcoder| transcoder(bytes)) {
+            return Some((text, "custom transcoder"));
+        }
+        lossy.then(|| (String::from_utf8_lossy(bytes).into_owned(), "lossy UTF-8"))
+    }
+}
+
+impl fmt::Debug for SourceDecoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourceDecoding")
+            .field("utf16", &self.utf16)
+            .field("transcoder", &self.transcoder.is_some())
+            .field("lossy", &self.lossy)
+            .finish()
+    }
+}
+
+fn decode_utf16(bytes: &[u8]) -> Option<String> {
+    let (bytes, from_bytes): (_, fn([u8; 2]) -> u16) = match bytes {
+        [0xFF, 0xFE, rest @ ..] => (rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => (rest, u16::from_be_bytes),
+        _ => return None,
+    };
+    let units = bytes
+        .chunks_exact(2)
+        .map(|unit| from_bytes([unit[0], unit[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+/// Decodes sources of the wrapped file resolver, that are not valid UTF-8, as configured by
+/// `SourceDecoding`, e.g. templates of customers, that were saved in a Windows encoding.
+/// Compilations, that use a decoded source, return a warning for it and list it in
+/// `CompilationReport::decoded_sources`. A warning is also logged (features `log` and
+/// `tracing`).
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::file_resolver::FileSystemResolver;
+/// # use typst_as_lib::resolver_middleware::{IntoResolverMiddleware, SourceDecoding};
+/// let file_resolver = FileSystemResolver::new("./uploads".into())
+///     .into_decoding(SourceDecoding::new().with_utf16().with_lossy());
+/// ```
+pub struct DecodingResolver<T> {
+    pub file_resolver: T,
+    decoding: SourceDecoding,
+    /// Encodings of the sources, that were decoded.
+    decoded: Mutex<HashMap<FileId, &'static str>>,
+}
+
+impl<T> DecodingResolver<T> {
+    pub fn new(file_resolver: T, decoding: SourceDecoding) -> Self {
+        Self {
+            file_resolver,
+            decoding,
+            decoded: Default::default(),
+        }
+    }
+}
+
+impl<T> FileResolver for DecodingResolver<T>
+where
+    T: FileResolver,
+{
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        self.file_resolver.resolve_binary(id)
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        match self.file_resolver.resolve_source(id) {
+            Err(FileError::InvalidUtf8) => {
+                let bytes = self.file_resolver.resolve_binary(id)?;
+                let (text, encoding) =
+                    self.decoding.decode(&bytes).ok_or(FileError::InvalidUtf8)?;
+                instrument::source_decoded(id, encoding);
+                lock_cache(&self.decoded).insert(id, encoding);
+                Ok(Cow::Owned(Source::new(id, text)))
+            }
+            result => {
+                lock_cache(&self.decoded).remove(&id);
+                result
+            }
+        }
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.file_resolver.stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.file_resolver.memory_footprint()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.file_resolver.file_ids()
+    }
+
+    fn uses_network(&self) -> bool {
+        self.file_resolver.uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.file_resolver.roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        lock_cache(&self.decoded).get(&id).copied()
+    }
+
+    fn invalidate(&self, id: FileId) {
+        lock_cache(&self.decoded).remove(&id);
+        self.file_resolver.invalidate(id);
+    }
+}
+
+type Transformer = Arc<dyn Fn(FileId, &[u8]) -> FileResult<Bytes> + Send + Sync>;
+
+/// Transformers of `TransformingResolver`, keyed by the (case-insensitive) file extension.
+/// Transformers of the same extension run in the order, in which they were added.
+#[derive(Clone, Default)]
+pub struct AssetTransformers {
+    transformers: HashMap<String, Vec<Transformer>>,
+}
+
+impl AssetTransformers {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Transform binaries with the extension `extension` (without the dot, e.g. `"heic"`).
+    pub fn with_transformer<F>(mut self, extension: &str, transformer: F) -> Self
+    where
+        F: Fn(FileId, &[u8]) -> FileResult<Bytes> + Send + Sync + 'static,
+    {
+        self.with_transformer_mut(extension, transformer);
+        self
+    }
+
+    pub fn with_transformer_mut<F>(&mut self, extension: &str, transformer: F)
+    where
+        F: Fn(FileId, &[u8]) -> FileResult<Bytes> + Send + Sync + 'static,
+    {
+        self.transformers
+            .entry(extension.to_ascii_lowercase())
+            .or_default()
+            .push(Arc::new(transformer));
+    }
+
+    fn get(&self, id: FileId) -> Option<&[Transformer]> {
+        let extension = id.vpath().as_rooted_path().extension()?.to_str()?;
+        self.transformers
+            .get(&extension.to_ascii_lowercase())
+            .map(Vec::as_slice)
+    }
+}
+
+impl fmt::Debug for AssetTransformers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut extensions: Vec<_> = self.transformers.keys().collect();
+        extensions.sort();
+        f.debug_struct("AssetTransformers")
+            .field("extensions", &extensions)
+            .finish()
+    }
+}
+
+/// Transforms binaries (`read()`, `image()`, ..) of the wrapped file resolver before they
+/// reach typst, e.g. to convert HEIC to PNG, downscale oversized images or strip EXIF data,
+/// so hosts don't need to keep preprocessed copies of their assets. Sources are not
+/// transformed. The file keeps its path, so typst may need the format, e.g.
+/// `image("photo.heic", format: "png")`.
+///
+/// Transformers run on each resolution. Wrap the resolver with `into_cached()`, if they are
+/// expensive.
+///
+/// Example:
+/// ```rust
+/// # use typst::diag::FileResult;
+/// # use typst::foundations::Bytes;
+/// # use typst_as_lib::cached_file_resolver::CachedFileResolver;
+/// # use typst_as_lib::file_resolver::FileSystemResolver;
+/// # use typst_as_lib::resolver_middleware::{AssetTransformers, IntoResolverMiddleware};
+/// # fn heic_to_png(bytes: &[u8]) -> FileResult<Bytes> {
+/// #     Ok(Bytes::from(bytes))
+/// # }
+/// # fn strip_exif(bytes: &[u8]) -> FileResult<Bytes> {
+/// #     Ok(Bytes::from(bytes))
+/// # }
+/// let file_resolver = CachedFileResolver::new(
+///     FileSystemResolver::new("./assets".into()).into_transforming(
+///         AssetTransformers::new()
+///             .with_transformer("heic", |_id, bytes| heic_to_png(bytes))
+///             .with_transformer("jpg", |_id, bytes| strip_exif(bytes)),
+///     ),
+/// )
+/// .with_in_memory_binary_cache();
+/// ```
+pub struct TransformingResolver<T> {
+    pub file_resolver: T,
+    transformers: AssetTransformers,
+}
+
+impl<T> TransformingResolver<T> {
+    pub fn new(file_resolver: T, transformers: AssetTransformers) -> Self {
+        Self {
+            file_resolver,
+            transformers,
+        }
+    }
+}
+
+impl<T> FileResolver for TransformingResolver<T>
+where
+    T: FileResolver,
+{
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let bytes = self.file_resolver.resolve_binary(id)?;
+        let Some(transformers) = self.transformers.get(id) else {
+            return Ok(bytes);
+        };
+        let mut bytes = bytes.into_owned();
+        for transformer in transformers {
+            bytes = transformer(id, &bytes)?;
+        }
+        Ok(Cow::Owned(bytes))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        self.file_resolver.resolve_source(id)
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.file_resolver.stats()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.file_resolver.memory_footprint()
+    }
+
+    fn file_ids(&self) -> Vec<FileId> {
+        self.file_resolver.file_ids()
+    }
+
+    fn uses_network(&self) -> bool {
+        self.file_resolver.uses_network()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.file_resolver.roots()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        self.file_resolver.source_encoding(id)
+    }
+
+    fn invalidate(&self, id: FileId) {
+        self.file_resolver.invalidate(id);
+    }
+}
+
+/// Wraps file resolvers in middleware.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::file_resolver::FileSystemResolver;
+/// # use typst_as_lib::resolver_middleware::IntoResolverMiddleware;
+/// # use typst_as_lib::TypstTemplate;
+/// # static TEMPLATE_FILE: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// // E.g. templates on a network share.
+/// let template = TypstTemplate::new(vec![font], TEMPLATE_FILE)
+///     .add_file_resolver(
+///         FileSystemResolver::new("/mnt/templates".into())
+///             .into_retrying(3)
+///             .into_logged()
+///     );
+/// ```
+pub trait IntoResolverMiddleware: Sized {
+    fn into_logged(self) -> LoggingResolver<Self> {
+        LoggingResolver::new(self)
+    }
+
+    fn into_retrying(self, retries: u32) -> RetryingResolver<Self> {
+        RetryingResolver::new(self, retries)
+    }
+
+    fn into_timeout(self, timeout: Duration) -> TimeoutResolver<Self> {
+        TimeoutResolver::new(self, timeout)
+    }
+
+    fn into_decoding(self, decoding: SourceDecoding) -> DecodingResolver<Self> {
+        DecodingResolver::new(self, decoding)
+    }
+
+    fn into_transforming(self, transformers: AssetTransformers) -> TransformingResolver<Self> {
+        TransformingResolver::new(self, transformers)
+    }
+}
+
+impl<T> IntoResolverMiddleware for T where T: FileResolver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_resolver::FileSystemResolver;
+    use crate::test_util::{file_id, font, temp_dir};
+    use crate::util::not_found;
+    use crate::TypstTemplateCollection;
+
+    fn utf16_le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    }
+
+    /// Serves `main.typ` with `bytes` from a temporary directory.
+    fn collection(name: &str, bytes: &[u8]) -> TypstTemplateCollection {
+        let dir = temp_dir(name);
+        std::fs::write(dir.join("main.typ"), bytes).unwrap();
+        TypstTemplateCollection::new(vec![font()]).add_file_resolver(
+            FileSystemResolver::new(dir).into_decoding(SourceDecoding::new().with_utf16()),
+        )
+    }
+
+    #[test]
+    fn decoded_source_is_reported() {
+        let collection = collection("decoded-source", &utf16_le("= Hello"));
+        let reported = collection.compile_with_report("/main.typ");
+        assert!(reported.output.output.is_ok());
+        assert_eq!(reported.report.decoded_sources.len(), 1);
+        assert_eq!(reported.report.decoded_sources[0].encoding, "UTF-16");
+        assert!(reported
+            .output
+            .warnings
+            .iter()
+            .any(|warning| warning.message.contains("decoded as UTF-16")));
+    }
+
+    #[test]
+    fn utf16_without_byte_order_mark_is_not_decoded() {
+        let bytes = utf16_le("= Grüße").split_off(2);
+        let collection = collection("utf16-without-bom", &bytes);
+        assert!(collection.compile("/main.typ").output.is_err());
+    }
+
+    /// Resolves each file, once a message was sent.
+    struct BlockingResolver(Mutex<mpsc::Receiver<()>>);
+
+    impl FileResolver for BlockingResolver {
+        fn resolve_binary(&self, _id: FileId) -> FileResult<Cow<'_, Bytes>> {
+            let _ = self.0.lock().unwrap().recv();
+            Ok(Cow::Owned(Bytes::from_static(b"")))
+        }
+
+        fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+            Err(not_found(id))
+        }
+    }
+
+    #[test]
+    fn timed_out_resolutions_limit_running_resolutions() {
+        let (release, receiver) = mpsc::channel();
+        let resolver = BlockingResolver(Mutex::new(receiver))
+            .into_timeout(Duration::from_millis(10))
+            .with_max_running(1);
+        let id = file_id("/image.png");
+
+        let error = resolver.resolve_binary(id).unwrap_err();
+        assert!(error.to_string().contains("timed out"));
+        let error = resolver.resolve_binary(id).unwrap_err();
+        assert!(error.to_string().contains("still running"));
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        while resolver.running.load(Ordering::Acquire) > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(resolver.resolve_binary(id).is_ok());
+    }
+}
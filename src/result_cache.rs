@@ -0,0 +1,223 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use ecow::EcoVec;
+use typst::diag::SourceDiagnostic;
+use typst::foundations::Datetime;
+use typst::model::Document;
+use typst::syntax::FileId;
+use typst::utils::hash128;
+
+use crate::cached_file_resolver::lock_cache;
+use crate::{today, TypstTemplateCollection};
+
+/// Caches compiled documents by main source file, inputs and the hashes of all files, that
+/// were read during the compilation. When the same template is compiled with the same inputs
+/// again and none of its files changed, the cached document is returned.
+///
+/// See `TypstTemplateCollection::with_result_cache()`.
+#[derive(Debug)]
+pub struct ResultCache {
+    capacity: usize,
+    entries: Mutex<ResultCacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct ResultCacheEntries {
+    results: HashMap<u128, CachedResult>,
+    /// Keys in order of insertion. The oldest entry is removed first.
+    order: VecDeque<u128>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CachedResult {
+    pub(crate) dependencies: Vec<Dependency>,
+    pub(crate) document: Document,
+    pub(crate) warnings: EcoVec<SourceDiagnostic>,
+}
+
+/// Something a compilation depended on, besides the main source file id and the inputs.
+/// The hashes only depend on the content, so they are the same in other processes.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Dependency {
+    Source(FileId, u128),
+    File(FileId, u128),
+    Today(Option<i64>, Option<Datetime>),
+}
+
+impl ResultCache {
+    /// Cache at most `capacity` documents.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().results.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.lock();
+        entries.results.clear();
+        entries.order.clear();
+    }
+
+    pub(crate) fn get(&self, key: u128) -> Option<CachedResult> {
+        self.lock().results.get(&key).cloned()
+    }
+
+    pub(crate) fn insert(&self, key: u128, result: CachedResult) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.lock();
+        let ResultCacheEntries { results, order } = &mut *entries;
+        if results.insert(key, result).is_none() {
+            order.push_back(key);
+        }
+        while results.len() > self.capacity {
+            let Some(oldest) = order.pop_front() else {
+                break;
+            };
+            results.remove(&oldest);
+        }
+    }
+
+    pub(crate) fn remove(&self, key: u128) {
+        let mut entries = self.lock();
+        entries.results.remove(&key);
+        entries.order.retain(|k| *k != key);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ResultCacheEntries> {
+        lock_cache(&self.entries)
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Returns the cached result, if none of its dependencies changed. Outdated results are
+    /// removed.
+    pub(crate) fn cached_result(&self, key: u128, now: DateTime<Utc>) -> Option<CachedResult> {
+        let result_cache = self.result_cache.as_ref()?;
+        let cached = result_cache.get(key)?;
+        let unchanged = cached
+            .dependencies
+            .iter()
+            .all(|dependency| self.is_unchanged(dependency, now));
+        if !unchanged {
+            result_cache.remove(key);
+            return None;
+        }
+        Some(cached)
+    }
+
+    pub(crate) fn is_unchanged(&self, dependency: &Dependency, now: DateTime<Utc>) -> bool {
+        match dependency {
+            Dependency::Source(id, hash) => self
+                .resolve_source(*id, |_| ())
+                .is_ok_and(|source| hash128(source.text()) == *hash),
+            Dependency::File(id, hash) => self
+                .resolve_file(*id, |_| ())
+                .ok()
+                .or_else(|| {
+                    let fallback_file_resolver = self.fallback_file_resolver.as_ref()?;
+                    fallback_file_resolver.resolve_binary(*id).ok()
+                })
+                .is_some_and(|bytes| hash128(bytes.as_ref()) == *hash),
+            Dependency::Today(offset, date) => today(now, *offset) == *date,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use typst::foundations::{Dict, IntoValue};
+
+    use super::*;
+    use crate::test_util::{font, MemoryResolver};
+
+    fn collection(resolver: &Arc<MemoryResolver>) -> TypstTemplateCollection {
+        TypstTemplateCollection::new(vec![font()])
+            .add_file_resolver(Arc::clone(resolver))
+            .with_result_cache(2)
+    }
+
+    /// Compiles `/main.typ` and returns, whether the result was cached.
+    fn from_result_cache(collection: &TypstTemplateCollection, inputs: Dict) -> bool {
+        let reported = collection.compile_with_input_and_report("/main.typ", inputs);
+        assert!(reported.output.output.is_ok());
+        reported.report.from_result_cache
+    }
+
+    fn resolver() -> Arc<MemoryResolver> {
+        let resolver = MemoryResolver::default()
+            .with_source("/main.typ", "#include \"body.typ\"\n#read(\"data.txt\")")
+            .with_source("/body.typ", "Hello")
+            .with_source("/data.txt", "World");
+        Arc::new(resolver)
+    }
+
+    #[test]
+    fn unchanged_compilation_hits() {
+        let collection = collection(&resolver());
+        assert!(!from_result_cache(&collection, Dict::new()));
+        assert!(from_result_cache(&collection, Dict::new()));
+        assert_eq!(collection.result_cache().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn changed_inputs_miss() {
+        let collection = collection(&resolver());
+        let mut inputs = Dict::new();
+        assert!(!from_result_cache(&collection, inputs.clone()));
+        inputs.insert("name".into(), "World".into_value());
+        assert!(!from_result_cache(&collection, inputs.clone()));
+        assert!(from_result_cache(&collection, inputs));
+    }
+
+    #[test]
+    fn changed_source_misses() {
+        let resolver = resolver();
+        let collection = collection(&resolver);
+        assert!(!from_result_cache(&collection, Dict::new()));
+        resolver.set_source("/body.typ", "Hello again");
+        assert!(!from_result_cache(&collection, Dict::new()));
+        assert!(from_result_cache(&collection, Dict::new()));
+    }
+
+    #[test]
+    fn changed_file_misses() {
+        let resolver = resolver();
+        let collection = collection(&resolver);
+        assert!(!from_result_cache(&collection, Dict::new()));
+        resolver.set_source("/data.txt", "Typst");
+        assert!(!from_result_cache(&collection, Dict::new()));
+    }
+
+    #[test]
+    fn oldest_result_is_removed() {
+        let collection = collection(&resolver());
+        for name in ["a", "b", "c"] {
+            let mut inputs = Dict::new();
+            inputs.insert("name".into(), name.into_value());
+            from_result_cache(&collection, inputs);
+        }
+        assert_eq!(collection.result_cache().unwrap().len(), 2);
+        let mut inputs = Dict::new();
+        inputs.insert("name".into(), "a".into_value());
+        assert!(!from_result_cache(&collection, inputs));
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt;
+
+use typst::foundations::{Dict, IntoValue, Value};
+
+use crate::registry::InputValidator;
+
+/// Expected structure of the inputs of a template. Inputs are checked before compiling, so
+/// wrong inputs fail with an `InputValidationError`, instead of an error deep inside of the
+/// template.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::schema::{DictSchema, InputSchema};
+/// # use typst_as_lib::TypstTemplate;
+/// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// let schema = InputSchema::Dict(
+///     DictSchema::new()
+///         .required("customer", InputSchema::Str)
+///         .required("items", InputSchema::array(InputSchema::Dict(
+///             DictSchema::new()
+///                 .required("name", InputSchema::Str)
+///                 .required("price", InputSchema::Float),
+///         )))
+///         .optional("note", InputSchema::Str),
+/// );
+/// let template = TypstTemplate::new(vec![font], TEMPLATE).with_input_schema(schema);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputSchema {
+    Any,
+    Bool,
+    Int,
+    /// Floats and integers.
+    Float,
+    Str,
+    Array(Box<InputSchema>),
+    Dict(DictSchema),
+    /// The value or `none`.
+    Optional(Box<InputSchema>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DictSchema {
+    fields: Vec<FieldSchema>,
+    allow_extra_keys: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FieldSchema {
+    name: String,
+    schema: InputSchema,
+    required: bool,
+}
+
+impl InputSchema {
+    pub fn array(items: InputSchema) -> Self {
+        InputSchema::Array(Box::new(items))
+    }
+
+    pub fn optional(schema: InputSchema) -> Self {
+        InputSchema::Optional(Box::new(schema))
+    }
+
+    /// Derive the schema from an example of the inputs, e.g. `T::default()` of the struct,
+    /// that is used as input. All keys of dictionaries are required. Arrays are expected to
+    /// contain values like their first element. `none` matches anything.
+    pub fn from_example<V>(example: V) -> Self
+    where
+        V: IntoValue,
+    {
+        Self::infer(&example.into_value())
+    }
+
+    fn infer(value: &Value) -> Self {
+        match value {
+            Value::Bool(_) => InputSchema::Bool,
+            Value::Int(_) => InputSchema::Int,
+            Value::Float(_) => InputSchema::Float,
+            Value::Str(_) => InputSchema::Str,
+            Value::Array(array) => {
+                let items = array.iter().next().map_or(InputSchema::Any, Self::infer);
+                InputSchema::array(items)
+            }
+            Value::Dict(dict) => InputSchema::Dict(DictSchema {
+                fields: dict
+                    .iter()
+                    .map(|(name, value)| FieldSchema {
+                        name: name.to_string(),
+                        schema: Self::infer(value),
+                        required: true,
+                    })
+                    .collect(),
+                allow_extra_keys: false,
+            }),
+            _ => InputSchema::Any,
+        }
+    }
+
+    /// Check the inputs and return all problems at once.
+    pub fn validate(&self, inputs: &Dict) -> Result<(), InputValidationError> {
+        let mut issues = Vec::new();
+        self.check(&Value::Dict(inputs.clone()), "inputs", &mut issues);
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(InputValidationError { issues })
+        }
+    }
+
+    fn check(&self, value: &Value, path: &str, issues: &mut Vec<InputValidationIssue>) {
+        let wrong_type = |expected: &str| InputValidationIssue::WrongType {
+            path: path.to_owned(),
+            expected: expected.to_owned(),
+            found: value.ty().short_name().to_owned(),
+        };
+        match (self, value) {
+            (InputSchema::Any, _)
+            | (InputSchema::Bool, Value::Bool(_))
+            | (InputSchema::Int, Value::Int(_))
+            | (InputSchema::Float, Value::Float(_) | Value::Int(_))
+            | (InputSchema::Str, Value::Str(_))
+            | (InputSchema::Optional(_), Value::None) => (),
+            (InputSchema::Optional(schema), value) => schema.check(value, path, issues),
+            (InputSchema::Array(items), Value::Array(array)) => {
+                for (i, item) in array.iter().enumerate() {
+                    items.check(item, &format!("{path}[{i}]"), issues);
+                }
+            }
+            (InputSchema::Dict(schema), Value::Dict(dict)) => schema.check(dict, path, issues),
+            (schema, _) => issues.push(wrong_type(schema.name())),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            InputSchema::Any => "any",
+            InputSchema::Bool => "bool",
+            InputSchema::Int => "int",
+            InputSchema::Float => "float",
+            InputSchema::Str => "str",
+            InputSchema::Array(_) => "array",
+            InputSchema::Dict(_) => "dictionary",
+            InputSchema::Optional(schema) => schema.name(),
+        }
+    }
+}
+
+impl DictSchema {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn required<S>(mut self, name: S, schema: InputSchema) -> Self
+    where
+        S: Into<String>,
+    {
+        self.fields.push(FieldSchema {
+            name: name.into(),
+            schema,
+            required: true,
+        });
+        self
+    }
+
+    pub fn optional<S>(mut self, name: S, schema: InputSchema) -> Self
+    where
+        S: Into<String>,
+    {
+        self.fields.push(FieldSchema {
+            name: name.into(),
+            schema,
+            required: false,
+        });
+        self
+    }
+
+    /// Don't report keys, that are not in the schema. By default they are reported.
+    pub fn allow_extra_keys(mut self) -> Self {
+        self.allow_extra_keys = true;
+        self
+    }
+
+    fn check(&self, dict: &Dict, path: &str, issues: &mut Vec<InputValidationIssue>) {
+        let Self {
+            fields,
+            allow_extra_keys,
+        } = self;
+        for FieldSchema {
+            name,
+            schema,
+            required,
+        } in fields
+        {
+            let field_path = format!("{path}.{name}");
+            match dict.get(name) {
+                Ok(value) => schema.check(value, &field_path, issues),
+                Err(_) if *required => {
+                    issues.push(InputValidationIssue::Missing { path: field_path })
+                }
+                Err(_) => (),
+            }
+        }
+        if *allow_extra_keys {
+            return;
+        }
+        for (key, _) in dict.iter() {
+            if !fields.iter().any(|field| field.name == key.as_str()) {
+                issues.push(InputValidationIssue::Extra {
+                    path: format!("{path}.{key}"),
+                });
+            }
+        }
+    }
+}
+
+impl InputValidator for InputSchema {
+    fn validate(&self, inputs: &Dict) -> Result<(), InputValidationError> {
+        InputSchema::validate(self, inputs)
+    }
+}
+
+/// Inputs did not match the `InputSchema` or were rejected by an `InputValidator`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputValidationError {
+    pub issues: Vec<InputValidationIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputValidationIssue {
+    Missing {
+        path: String,
+    },
+    Extra {
+        path: String,
+    },
+    WrongType {
+        path: String,
+        expected: String,
+        found: String,
+    },
+    /// Returned by a custom `InputValidator`.
+    Other(String),
+}
+
+impl InputValidationError {
+    /// Error with a single message, e.g. for custom `InputValidator`s.
+    pub fn other<S>(message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            issues: vec![InputValidationIssue::Other(message.into())],
+        }
+    }
+}
+
+impl fmt::Display for InputValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut issues = self.issues.iter();
+        if let Some(issue) = issues.next() {
+            write!(f, "{issue}")?;
+        }
+        for issue in issues {
+            write!(f, "; {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for InputValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputValidationIssue::Missing { path } => write!(f, "missing key {path}"),
+            InputValidationIssue::Extra { path } => write!(f, "unexpected key {path}"),
+            InputValidationIssue::WrongType {
+                path,
+                expected,
+                found,
+            } => write!(f, "{path} should be {expected}, but is {found}"),
+            InputValidationIssue::Other(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for InputValidationError {}
@@ -0,0 +1,167 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use typst::diag::Warned;
+use typst::foundations::Dict;
+use typst::model::Document;
+use typst::syntax::{FileId, Source};
+
+use crate::report::Reported;
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Holds edited sources, that shadow the file resolvers of a template. Sources are edited
+/// in place, so unchanged parts of the syntax tree are reused on the next compilation,
+/// which makes repeated compilations after small changes (e.g. in an editor preview) fast.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::TypstTemplate;
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// static TEMPLATE_FILE: &str = "= Greeting\nWorld";
+/// // ...
+/// let template = TypstTemplate::new(vec![font], TEMPLATE_FILE);
+/// let mut session = template.session();
+/// let doc = session.compile().output.expect("Typst error!");
+/// session.edit_source(template.main_source_id(), 11..16, "Hello")?;
+/// let doc = session.compile().output.expect("Typst error!");
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub struct TypstSession<'a> {
+    collection: &'a TypstTemplateCollection,
+    main_source_id: FileId,
+    sources: HashMap<FileId, Source>,
+}
+
+impl<'a> TypstSession<'a> {
+    pub(crate) fn new(collection: &'a TypstTemplateCollection, main_source_id: FileId) -> Self {
+        Self {
+            collection,
+            main_source_id,
+            sources: Default::default(),
+        }
+    }
+
+    /// Replace the whole text of a source. Only the changed part gets reparsed.
+    pub fn update_source<F>(&mut self, file_id: F, text: &str) -> Result<(), TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(file_id) = file_id.into();
+        self.source_mut(file_id)?.replace(text);
+        Ok(())
+    }
+
+    /// Replace the byte `range` of a source with `with`. Only the changed part gets reparsed.
+    pub fn edit_source<F>(
+        &mut self,
+        file_id: F,
+        range: Range<usize>,
+        with: &str,
+    ) -> Result<(), TypstAsLibError>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(file_id) = file_id.into();
+        let source = self.source_mut(file_id)?;
+        let text = source.text();
+        let valid = range.start <= range.end
+            && range.end <= text.len()
+            && text.is_char_boundary(range.start)
+            && text.is_char_boundary(range.end);
+        if !valid {
+            return Err(TypstAsLibError::InvalidEdit(file_id, range));
+        }
+        source.edit(range, with);
+        Ok(())
+    }
+
+    /// The edited source, if it was edited in this session.
+    pub fn source<F>(&self, file_id: F) -> Option<&Source>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(file_id) = file_id.into();
+        self.sources.get(&file_id)
+    }
+
+    /// Discard the edits of a source, so it is resolved by the file resolvers again.
+    pub fn reset_source<F>(&mut self, file_id: F)
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(file_id) = file_id.into();
+        self.sources.remove(&file_id);
+    }
+
+    /// Call `typst::compile()` with the edited sources.
+    pub fn compile(&self) -> Warned<Result<Document, TypstAsLibError>> {
+        self.compile_with_report().output
+    }
+
+    /// Call `typst::compile()` with the edited sources and a `Dict` as input.
+    pub fn compile_with_input<D>(&self, input: D) -> Warned<Result<Document, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        self.compile_with_input_and_report(input).output
+    }
+
+    /// Like `TypstSession::compile()`, but also returns a `CompilationReport`.
+    pub fn compile_with_report(&self) -> Reported<Warned<Result<Document, TypstAsLibError>>> {
+        let Self {
+            collection,
+            main_source_id,
+            sources,
+        } = self;
+        collection.compile_helper::<_, Dict>(*main_source_id, None, Some(sources))
+    }
+
+    /// Like `TypstSession::compile_with_input()`, but also returns a `CompilationReport`.
+    pub fn compile_with_input_and_report<D>(
+        &self,
+        input: D,
+    ) -> Reported<Warned<Result<Document, TypstAsLibError>>>
+    where
+        D: Into<Dict>,
+    {
+        let Self {
+            collection,
+            main_source_id,
+            sources,
+        } = self;
+        collection.compile_helper(*main_source_id, Some(input), Some(sources))
+    }
+
+    fn source_mut(&mut self, file_id: FileId) -> Result<&mut Source, TypstAsLibError> {
+        let Self {
+            collection,
+            sources,
+            ..
+        } = self;
+        Ok(match sources.entry(file_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(collection.resolve_source(file_id, |_| ())?.into_owned())
+            }
+        })
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Start a `TypstSession` for editing sources, with `main_source_id` as main source file.
+    pub fn session<F>(&self, main_source_id: F) -> TypstSession<'_>
+    where
+        F: Into<FileIdNewType>,
+    {
+        let FileIdNewType(main_source_id) = main_source_id.into();
+        TypstSession::new(self, main_source_id)
+    }
+}
+
+impl TypstTemplate {
+    /// Start a `TypstSession` for editing sources.
+    pub fn session(&self) -> TypstSession<'_> {
+        TypstSession::new(&self.collection, self.source_id)
+    }
+}
@@ -0,0 +1,51 @@
+use typst::diag::Warned;
+use typst::foundations::Dict;
+
+use crate::pages::PageSelection;
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+impl TypstTemplateCollection {
+    /// Compile with a `Dict` as input and export the selected pages as SVG with
+    /// `typst_svg::svg()`. Returns one SVG per page. With feature `rayon`, the pages are
+    /// exported in parallel.
+    pub fn compile_to_svg<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        pages: &PageSelection,
+    ) -> Warned<Result<Vec<String>, TypstAsLibError>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let Warned { output, warnings } = self.compile_with_input(main_source_id, inputs);
+        let output =
+            output.map(|document| pages.map_pages(&document, |_, page| typst_svg::svg(page)));
+        Warned { output, warnings }
+    }
+}
+
+impl TypstTemplate {
+    /// Compile with a `Dict` as input and export the selected pages as SVG.
+    /// See `TypstTemplateCollection::compile_to_svg()`.
+    pub fn compile_to_svg<D>(
+        &self,
+        inputs: D,
+        pages: &PageSelection,
+    ) -> Warned<Result<Vec<String>, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        let inputs = match self.validated_input(inputs) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                return Warned {
+                    output: Err(err),
+                    warnings: Default::default(),
+                }
+            }
+        };
+        self.collection
+            .compile_to_svg(self.source_id, inputs, pages)
+    }
+}
@@ -0,0 +1,298 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use typst::diag::{FileResult, Warned};
+use typst::foundations::{Bytes, Dict};
+use typst::model::Document;
+use typst::syntax::{FileId, Source, VirtualPath};
+
+use crate::file_resolver::{FileResolver, FileResolverStats};
+use crate::util::not_found;
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Serves the files of multiple tenants from one `TypstTemplateCollection`. Use it with
+/// `TypstTemplateCollection::compile_for_tenant()`, which resolves every non-package file
+/// `/<path>` as `/<tenant>/<path>`. So a compilation for tenant A can never read the files of
+/// tenant B, while fonts, packages and caches are shared.
+///
+/// `/<tenant>/<path>` is resolved as `/<path>` by the file resolver of the tenant.
+///
+/// Example:
+/// ```rust,no_run
+/// # use typst::foundations::Dict;
+/// # use typst_as_lib::file_resolver::FileSystemResolver;
+/// # use typst_as_lib::tenant::TenantScopedResolver;
+/// # use typst_as_lib::TypstTemplateCollection;
+/// # static INVOICE: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// # let inputs = Dict::new();
+/// let collection = TypstTemplateCollection::new(fonts)
+///     .with_static_source_file_resolver([("/invoice.typ", INVOICE)])
+///     .add_file_resolver(
+///         TenantScopedResolver::new()
+///             .with_tenant("acme", FileSystemResolver::new("/srv/tenants/acme".into()))
+///             .with_tenant("globex", FileSystemResolver::new("/srv/tenants/globex".into())),
+///     );
+/// // `#image("logo.png")` reads `/srv/tenants/acme/logo.png`.
+/// let doc = collection.compile_for_tenant("acme", "/invoice.typ", inputs);
+/// ```
+#[derive(Default)]
+pub struct TenantScopedResolver {
+    tenants: HashMap<String, Box<dyn FileResolver + Send + Sync + 'static>>,
+}
+
+impl TenantScopedResolver {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_tenant<S, F>(mut self, tenant: S, file_resolver: F) -> Self
+    where
+        S: Into<String>,
+        F: FileResolver + Send + Sync + 'static,
+    {
+        self.tenants.insert(tenant.into(), Box::new(file_resolver));
+        self
+    }
+
+    /// Resolver of the tenant and the id relative to the directory of the tenant.
+    fn tenant_resolver(
+        &self,
+        id: FileId,
+    ) -> FileResult<(&(dyn FileResolver + Send + Sync), FileId)> {
+        if id.package().is_some() {
+            return Err(not_found(id));
+        }
+        let path = id.vpath().as_rootless_path();
+        let mut components = path.components();
+        let Some(Component::Normal(tenant)) = components.next() else {
+            return Err(not_found(id));
+        };
+        let file_resolver = tenant
+            .to_str()
+            .and_then(|tenant| self.tenants.get(tenant))
+            .ok_or_else(|| not_found(id))?;
+        let id = FileId::new(None, VirtualPath::new(components.as_path()));
+        Ok((file_resolver.as_ref(), id))
+    }
+}
+
+impl FileResolver for TenantScopedResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let (file_resolver, tenant_id) = self.tenant_resolver(id)?;
+        file_resolver.resolve_binary(tenant_id)
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let (file_resolver, tenant_id) = self.tenant_resolver(id)?;
+        let source = file_resolver.resolve_source(tenant_id)?;
+        Ok(Cow::Owned(Source::new(id, source.text().to_owned())))
+    }
+
+    fn stats(&self) -> FileResolverStats {
+        self.tenants
+            .values()
+            .map(|file_resolver| file_resolver.stats())
+            .fold(Default::default(), |a, b| a + b)
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.tenants
+            .values()
+            .map(|file_resolver| file_resolver.memory_footprint())
+            .sum()
+    }
+
+    fn invalidate(&self, id: FileId) {
+        if let Ok((file_resolver, tenant_id)) = self.tenant_resolver(id) {
+            file_resolver.invalidate(tenant_id);
+        }
+    }
+
+    fn uses_network(&self) -> bool {
+        self.tenants
+            .values()
+            .any(|file_resolver| file_resolver.uses_network())
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.tenants
+            .values()
+            .flat_map(|file_resolver| file_resolver.roots())
+            .collect()
+    }
+
+    fn source_encoding(&self, id: FileId) -> Option<&'static str> {
+        let (file_resolver, tenant_id) = self.tenant_resolver(id).ok()?;
+        file_resolver.source_encoding(tenant_id)
+    }
+}
+
+/// `/<path>` -> `/<tenant>/<path>`. Package files and files, that are already under the prefix
+/// of the tenant, are not changed.
+pub(crate) fn scope_file_id(tenant: &str, id: FileId) -> FileId {
+    if id.package().is_some() {
+        return id;
+    }
+    // `VirtualPath` keeps leading `..` (e.g. of `/../<other tenant>/<path>`), which would
+    // leave the directory of the tenant after the join. Like `/..` on a file system, they
+    // stay at the root.
+    let path: PathBuf = id
+        .vpath()
+        .as_rootless_path()
+        .components()
+        .skip_while(|component| matches!(component, Component::ParentDir))
+        .collect();
+    if path.starts_with(tenant) {
+        return FileId::new(None, VirtualPath::new(path));
+    }
+    FileId::new(None, VirtualPath::new(Path::new(tenant).join(path)))
+}
+
+/// A tenant must be a single, normal path component.
+fn validate_tenant(tenant: &str) -> Result<(), TypstAsLibError> {
+    let mut components = Path::new(tenant).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(component)), None) if component == tenant => Ok(()),
+        _ => Err(TypstAsLibError::InvalidTenant(tenant.to_owned())),
+    }
+}
+
+impl TypstTemplateCollection {
+    /// Compile for the tenant with a `Dict` as input. Every non-package file `/<path>` is
+    /// resolved as `/<tenant>/<path>`, except the main source file, so one template can be
+    /// shared by all tenants. Use `/<tenant>/main.typ` for templates of a tenant.
+    /// See `TenantScopedResolver`.
+    pub fn compile_for_tenant<F, D>(
+        &self,
+        tenant: &str,
+        main_source_id: F,
+        inputs: D,
+    ) -> Warned<Result<Document, TypstAsLibError>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        if let Err(err) = validate_tenant(tenant) {
+            return Warned {
+                output: Err(err),
+                warnings: Default::default(),
+            };
+        }
+        self.compile_tracked(
+            main_source_id,
+            Some(inputs),
+            None,
+            Some(tenant),
+            false,
+            self.now(),
+        )
+        .0
+        .output
+    }
+}
+
+impl TypstTemplate {
+    /// Compile the template for the tenant with a `Dict` as input.
+    /// See `TypstTemplateCollection::compile_for_tenant()`.
+    pub fn compile_for_tenant<D>(
+        &self,
+        tenant: &str,
+        inputs: D,
+    ) -> Warned<Result<Document, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        let inputs = match self.validated_input(inputs) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                return Warned {
+                    output: Err(err),
+                    warnings: Default::default(),
+                }
+            }
+        };
+        self.collection
+            .compile_for_tenant(tenant, self.source_id, inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use typst::foundations::IntoValue;
+
+    use super::*;
+    use crate::test_util::{font, MemoryResolver};
+
+    /// `/main.typ` includes `/body.typ` of the tenant, which has one page for `acme` and two
+    /// pages for `globex`.
+    fn collection() -> TypstTemplateCollection {
+        let acme = MemoryResolver::default()
+            .with_source("/body.typ", "Acme")
+            .with_source("/secret.txt", "Acme");
+        let globex = MemoryResolver::default()
+            .with_source("/body.typ", "Globex #pagebreak() Globex")
+            .with_source("/secret.txt", "Globex");
+        TypstTemplateCollection::new(vec![font()])
+            .with_static_source_file_resolver([
+                ("/main.typ", "#include \"body.typ\""),
+                ("/read.typ", "#read(sys.inputs.path)"),
+            ])
+            .add_file_resolver(
+                TenantScopedResolver::new()
+                    .with_tenant("acme", Arc::new(acme))
+                    .with_tenant("globex", Arc::new(globex)),
+            )
+    }
+
+    fn page_count(collection: &TypstTemplateCollection, tenant: &str) -> usize {
+        let doc = collection.compile_for_tenant(tenant, "/main.typ", Dict::new());
+        doc.output.unwrap().pages.len()
+    }
+
+    fn read(collection: &TypstTemplateCollection, tenant: &str, path: &str) -> bool {
+        let mut inputs = Dict::new();
+        inputs.insert("path".into(), path.into_value());
+        let doc = collection.compile_for_tenant(tenant, "/read.typ", inputs);
+        doc.output.is_ok()
+    }
+
+    #[test]
+    fn tenants_read_their_own_files() {
+        let collection = collection();
+        assert_eq!(page_count(&collection, "acme"), 1);
+        assert_eq!(page_count(&collection, "globex"), 2);
+        assert!(read(&collection, "acme", "/secret.txt"));
+        assert!(read(&collection, "acme", "/acme/secret.txt"));
+    }
+
+    #[test]
+    fn tenants_cannot_read_files_of_other_tenants() {
+        let collection = collection();
+        assert!(!read(&collection, "acme", "/globex/secret.txt"));
+        assert!(!read(&collection, "acme", "/../globex/secret.txt"));
+        assert!(!read(&collection, "acme", "/acme/../globex/secret.txt"));
+    }
+
+    #[test]
+    fn results_are_cached_per_tenant() {
+        let collection = collection().with_result_cache(8);
+        assert_eq!(page_count(&collection, "acme"), 1);
+        assert_eq!(page_count(&collection, "globex"), 2);
+        assert_eq!(page_count(&collection, "acme"), 1);
+        assert_eq!(collection.result_cache().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn invalid_tenants_are_rejected() {
+        let collection = collection();
+        for tenant in ["", "..", "acme/../globex", "/globex"] {
+            let doc = collection.compile_for_tenant(tenant, "/main.typ", Dict::new());
+            assert!(matches!(doc.output, Err(TypstAsLibError::InvalidTenant(_))));
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Helpers for the unit tests.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use typst::diag::FileResult;
+use typst::foundations::Bytes;
+use typst::syntax::{FileId, Source, VirtualPath};
+use typst::text::Font;
+
+use crate::file_resolver::FileResolver;
+use crate::util::not_found;
+
+static FONT: &[u8] = include_bytes!("../examples/fonts/texgyrecursor-regular.otf");
+
+pub(crate) fn font() -> Font {
+    Font::new(Bytes::from_static(FONT), 0).expect("Could not parse font!")
+}
+
+pub(crate) fn file_id(path: &str) -> FileId {
+    FileId::new(None, VirtualPath::new(path))
+}
+
+/// Empty directory in the temporary directory, unique per process and `name`.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("typst-as-lib-test-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Could not create temporary directory!");
+    dir
+}
+
+/// Sources, that can be changed after the file resolver was added to a collection (e.g. as
+/// `Arc<MemoryResolver>`).
+#[derive(Debug, Default)]
+pub(crate) struct MemoryResolver {
+    sources: Mutex<HashMap<FileId, String>>,
+}
+
+impl MemoryResolver {
+    pub(crate) fn with_source(self, path: &str, text: &str) -> Self {
+        self.set_source(path, text);
+        self
+    }
+
+    pub(crate) fn set_source(&self, path: &str, text: &str) {
+        let mut sources = self.sources.lock().unwrap();
+        sources.insert(file_id(path), text.to_owned());
+    }
+}
+
+impl FileResolver for MemoryResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let sources = self.sources.lock().unwrap();
+        let text = sources.get(&id).ok_or_else(|| not_found(id))?;
+        Ok(Cow::Owned(Bytes::from(text.as_bytes())))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let sources = self.sources.lock().unwrap();
+        let text = sources.get(&id).ok_or_else(|| not_found(id))?;
+        Ok(Cow::Owned(Source::new(id, text.clone())))
+    }
+}
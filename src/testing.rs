@@ -0,0 +1,233 @@
+use std::path::{Path, PathBuf};
+
+use tiny_skia::Pixmap;
+use typst::model::Document;
+
+/// Environment variable, that makes `assert_matches_golden()` (re)write the golden files
+/// instead of comparing with them.
+pub const UPDATE_GOLDEN_ENV: &str = "TYPST_AS_LIB_UPDATE_GOLDEN";
+
+/// Resolution, in which pages are compared (144 DPI).
+const PIXEL_PER_PT: f32 = 2.0;
+
+/// How much a rendered page may differ from its golden file, e.g. because of different
+/// anti-aliasing on other platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GoldenTolerance {
+    /// Difference of a color channel (0-255), up to which pixels are considered equal.
+    pub max_channel_difference: u8,
+    /// Number of pixels per page, that may differ by more than `max_channel_difference`.
+    pub max_differing_pixels: usize,
+}
+
+/// A page, that doesn't match its golden file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenMismatch {
+    /// 1-based page number.
+    pub page: usize,
+    pub golden: PathBuf,
+    /// Why the page doesn't match, e.g. the number of differing pixels.
+    pub reason: String,
+    /// The rendered page and an image with the differing pixels in red, if they were written.
+    pub actual: Option<PathBuf>,
+    pub diff: Option<PathBuf>,
+}
+
+impl std::fmt::Display for GoldenMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            page,
+            golden,
+            reason,
+            actual,
+            diff,
+        } = self;
+        write!(
+            f,
+            "page {page} doesn't match {}: {reason}",
+            golden.display()
+        )?;
+        if let (Some(actual), Some(diff)) = (actual, diff) {
+            write!(
+                f,
+                " (actual: {}, diff: {})",
+                actual.display(),
+                diff.display()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Render the pages of `document` (as `TypstTemplate::compile_to_png()` would) and compare
+/// them with the golden files `<golden>-<page>.png`, e.g. `tests/golden/invoice-1.png` for
+/// `golden` = `tests/golden/invoice`. On mismatch, `<golden>-<page>.actual.png` and
+/// `<golden>-<page>.diff.png` are written next to the golden file.
+///
+/// The pages are compared, not the exported PDF, as PDFs can't be rasterized without an
+/// external renderer. Set the environment variable `TYPST_AS_LIB_UPDATE_GOLDEN` to write the
+/// golden files.
+///
+/// Example:
+/// ```rust,no_run
+/// # use typst_as_lib::testing::{assert_matches_golden, GoldenTolerance};
+/// # use typst::foundations::{dict, Array};
+/// # use typst_as_lib::TypstTemplate;
+/// # static TEMPLATE: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], TEMPLATE);
+/// # let inputs = dict! { "v" => Array::new() };
+/// let doc = template.compile_with_input(inputs).output?;
+/// assert_matches_golden(&doc, "tests/golden/invoice", &GoldenTolerance::default());
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub fn assert_matches_golden<P>(document: &Document, golden: P, tolerance: &GoldenTolerance)
+where
+    P: AsRef<Path>,
+{
+    if let Err(mismatches) = compare_with_golden(document, golden, tolerance) {
+        let mismatches: Vec<_> = mismatches.iter().map(ToString::to_string).collect();
+        panic!(
+            "document doesn't match golden files:\n{}",
+            mismatches.join("\n")
+        );
+    }
+}
+
+/// Like `assert_matches_golden()`, but returns the mismatches instead of panicking.
+pub fn compare_with_golden<P>(
+    document: &Document,
+    golden: P,
+    tolerance: &GoldenTolerance,
+) -> Result<(), Vec<GoldenMismatch>>
+where
+    P: AsRef<Path>,
+{
+    let golden = golden.as_ref();
+    let update = std::env::var_os(UPDATE_GOLDEN_ENV).is_some();
+    let mut mismatches = Vec::new();
+    for (index, page) in document.pages.iter().enumerate() {
+        let page_number = index + 1;
+        let path = with_suffix(golden, &format!("-{page_number}.png"));
+        let actual = typst_render::render(page, PIXEL_PER_PT);
+        let mismatch = |reason: String| GoldenMismatch {
+            page: page_number,
+            golden: path.clone(),
+            reason,
+            actual: None,
+            diff: None,
+        };
+        if update {
+            if let Err(err) = write_png(&actual, &path) {
+                mismatches.push(mismatch(err));
+            }
+            continue;
+        }
+        let expected = match std::fs::read(&path) {
+            Ok(expected) => expected,
+            Err(err) => {
+                mismatches.push(mismatch(format!(
+                    "{err}, set {UPDATE_GOLDEN_ENV} to create it"
+                )));
+                continue;
+            }
+        };
+        let expected = match Pixmap::decode_png(&expected) {
+            Ok(expected) => expected,
+            Err(err) => {
+                mismatches.push(mismatch(err.to_string()));
+                continue;
+            }
+        };
+        let Some(reason) = compare(&expected, &actual, tolerance) else {
+            continue;
+        };
+        let actual_path = with_suffix(golden, &format!("-{page_number}.actual.png"));
+        let diff_path = with_suffix(golden, &format!("-{page_number}.diff.png"));
+        let written = write_png(&actual, &actual_path).and_then(|()| {
+            diff_image(&expected, &actual, tolerance)
+                .map_or(Ok(()), |diff| write_png(&diff, &diff_path))
+        });
+        mismatches.push(GoldenMismatch {
+            actual: written.is_ok().then_some(actual_path),
+            diff: written.is_ok().then_some(diff_path),
+            ..mismatch(reason)
+        });
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+/// `None`, if the images match within the tolerance.
+fn compare(expected: &Pixmap, actual: &Pixmap, tolerance: &GoldenTolerance) -> Option<String> {
+    if (expected.width(), expected.height()) != (actual.width(), actual.height()) {
+        return Some(format!(
+            "size is {}x{}, expected {}x{}",
+            actual.width(),
+            actual.height(),
+            expected.width(),
+            expected.height()
+        ));
+    }
+    let differing = differing_pixels(expected, actual, tolerance.max_channel_difference).count();
+    (differing > tolerance.max_differing_pixels).then(|| {
+        format!(
+            "{differing} pixels differ, at most {} are allowed",
+            tolerance.max_differing_pixels
+        )
+    })
+}
+
+/// Indices of the pixels, that differ by more than `max_channel_difference`.
+fn differing_pixels<'a>(
+    expected: &'a Pixmap,
+    actual: &'a Pixmap,
+    max_channel_difference: u8,
+) -> impl Iterator<Item = usize> + 'a {
+    expected
+        .data()
+        .chunks_exact(4)
+        .zip(actual.data().chunks_exact(4))
+        .enumerate()
+        .filter(move |(_, (expected, actual))| {
+            expected
+                .iter()
+                .zip(actual.iter())
+                .any(|(expected, actual)| expected.abs_diff(*actual) > max_channel_difference)
+        })
+        .map(|(index, _)| index)
+}
+
+/// The actual image faded, with the differing pixels in red. `None`, if the sizes differ.
+fn diff_image(expected: &Pixmap, actual: &Pixmap, tolerance: &GoldenTolerance) -> Option<Pixmap> {
+    if (expected.width(), expected.height()) != (actual.width(), actual.height()) {
+        return None;
+    }
+    let mut diff = actual.clone();
+    for pixel in diff.data_mut().chunks_exact_mut(4) {
+        pixel[3] /= 4;
+        for channel in &mut pixel[..3] {
+            *channel /= 4;
+        }
+    }
+    for index in differing_pixels(expected, actual, tolerance.max_channel_difference) {
+        diff.data_mut()[index * 4..index * 4 + 4].copy_from_slice(&[255, 0, 0, 255]);
+    }
+    Some(diff)
+}
+
+fn write_png(pixmap: &Pixmap, path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    pixmap.save_png(path).map_err(|err| err.to_string())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
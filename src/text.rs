@@ -0,0 +1,225 @@
+use typst::diag::Warned;
+use typst::foundations::Dict;
+use typst::layout::{Abs, Frame, FrameItem, Point};
+use typst::model::Document;
+use typst::text::TextItem;
+
+use crate::pages::{PageSelection, Rect};
+use crate::{FileIdNewType, TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Options for the plain text rendering of a document.
+/// See `TypstTemplateCollection::compile_to_text()`.
+#[derive(Debug, Clone)]
+pub struct TextExportOptions {
+    /// Inserted between pages. Default: `"\n\n"`.
+    pub page_separator: String,
+    pub pages: PageSelection,
+}
+
+impl Default for TextExportOptions {
+    fn default() -> Self {
+        Self {
+            page_separator: "\n\n".to_owned(),
+            pages: Default::default(),
+        }
+    }
+}
+
+/// Plain text of the document, e.g. for search indexing or snapshot tests. Text items are
+/// written in the order, in which they were laid out. A line break is inserted, when the
+/// baseline changes.
+pub fn document_to_text(document: &Document, options: &TextExportOptions) -> String {
+    let TextExportOptions {
+        page_separator,
+        pages,
+    } = options;
+    pages
+        .select(document)
+        .into_iter()
+        .map(|page| {
+            let mut writer = TextWriter::default();
+            writer.write_frame(&page.frame, Point::zero());
+            writer.text
+        })
+        .collect::<Vec<_>>()
+        .join(page_separator)
+}
+
+#[derive(Default)]
+struct TextWriter {
+    text: String,
+    baseline: Option<Abs>,
+}
+
+impl TextWriter {
+    fn write_frame(&mut self, frame: &Frame, offset: Point) {
+        for (pos, item) in frame.items() {
+            let pos = offset + *pos;
+            match item {
+                FrameItem::Group(group) => self.write_frame(&group.frame, pos),
+                FrameItem::Text(text) => {
+                    if let Some(baseline) = self.baseline {
+                        if (pos.y - baseline).abs() > text.size / 2.0 {
+                            self.text.push('\n');
+                        }
+                    }
+                    self.text.push_str(&text.text);
+                    self.baseline = Some(pos.y);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// A match of `search_document()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    /// 1-based page number.
+    pub page: usize,
+    /// Areas of the match in points, one for each text run of the match (e.g. two, if the match
+    /// continues on the next line).
+    pub rects: Vec<Rect>,
+}
+
+/// Find all occurrences of `query` in the text of the document, e.g. to highlight search
+/// hits on a rendered preview. Matching ignores ASCII case. Lines are joined with a space,
+/// so queries can span lines.
+///
+/// Example:
+/// ```rust
+/// # use typst_as_lib::pages::Rect;
+/// # use typst_as_lib::text;
+/// # fn highlight(_page: usize, _rects: &[Rect]) {}
+/// # use typst_as_lib::TypstTemplate;
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], "Total: 12.50 EUR");
+/// let doc = template.compile().output?;
+/// for hit in text::search_document(&doc, "total") {
+///     highlight(hit.page, &hit.rects);
+/// }
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub fn search_document(document: &Document, query: &str) -> Vec<SearchMatch> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let query = query.to_ascii_lowercase();
+    let mut matches = Vec::new();
+    for (index, page) in document.pages.iter().enumerate() {
+        let mut runs = Vec::new();
+        collect_runs(&page.frame, Point::zero(), &mut runs);
+
+        // Text of the page and the start of each run in it.
+        let mut text = String::new();
+        let mut starts = Vec::with_capacity(runs.len());
+        let mut baseline = None;
+        for (pos, run) in &runs {
+            if baseline.is_some_and(|baseline| baseline != pos.y) {
+                text.push(' ');
+            }
+            baseline = Some(pos.y);
+            starts.push(text.len());
+            text.push_str(&run.text);
+        }
+
+        for (start, _) in text.to_ascii_lowercase().match_indices(&query) {
+            let end = start + query.len();
+            let rects = runs
+                .iter()
+                .zip(&starts)
+                .filter_map(|((pos, run), &run_start)| {
+                    let from = start.max(run_start) - run_start;
+                    let to = end.min(run_start + run.text.len()).checked_sub(run_start)?;
+                    run_rect(*pos, run, from..to)
+                })
+                .collect();
+            matches.push(SearchMatch {
+                page: index + 1,
+                rects,
+            });
+        }
+    }
+    matches
+}
+
+fn collect_runs<'a>(frame: &'a Frame, offset: Point, runs: &mut Vec<(Point, &'a TextItem)>) {
+    for (pos, item) in frame.items() {
+        let pos = offset + *pos;
+        match item {
+            FrameItem::Group(group) => collect_runs(&group.frame, pos, runs),
+            FrameItem::Text(text) => runs.push((pos, text)),
+            _ => (),
+        }
+    }
+}
+
+/// Area of the glyphs of `run`, that belong to `range` of its text.
+fn run_rect(pos: Point, run: &TextItem, range: std::ops::Range<usize>) -> Option<Rect> {
+    if range.is_empty() {
+        return None;
+    }
+    let mut x = pos.x;
+    let mut bounds: Option<(Abs, Abs)> = None;
+    for glyph in &run.glyphs {
+        let advance = glyph.x_advance.at(run.size);
+        let glyph_range = glyph.range();
+        if glyph_range.start < range.end && range.start < glyph_range.end {
+            bounds = Some(match bounds {
+                None => (x, x + advance),
+                Some((min, max)) => (min.min(x), max.max(x + advance)),
+            });
+        }
+        x += advance;
+    }
+    let (min_x, max_x) = bounds?;
+    let metrics = run.font.metrics();
+    Some(Rect::from_bounds(
+        Point::new(min_x, pos.y - metrics.ascender.at(run.size)),
+        Point::new(max_x, pos.y - metrics.descender.at(run.size)),
+    ))
+}
+
+impl TypstTemplateCollection {
+    /// Compile with a `Dict` as input and render the document as plain text.
+    /// See `document_to_text()`.
+    pub fn compile_to_text<F, D>(
+        &self,
+        main_source_id: F,
+        inputs: D,
+        options: &TextExportOptions,
+    ) -> Warned<Result<String, TypstAsLibError>>
+    where
+        F: Into<FileIdNewType>,
+        D: Into<Dict>,
+    {
+        let Warned { output, warnings } = self.compile_with_input(main_source_id, inputs);
+        let output = output.map(|document| document_to_text(&document, options));
+        Warned { output, warnings }
+    }
+}
+
+impl TypstTemplate {
+    /// Compile with a `Dict` as input and render the document as plain text.
+    /// See `document_to_text()`.
+    pub fn compile_to_text<D>(
+        &self,
+        inputs: D,
+        options: &TextExportOptions,
+    ) -> Warned<Result<String, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+    {
+        let inputs = match self.validated_input(inputs) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                return Warned {
+                    output: Err(err),
+                    warnings: Default::default(),
+                }
+            }
+        };
+        self.collection
+            .compile_to_text(self.source_id, inputs, options)
+    }
+}
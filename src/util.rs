@@ -1,5 +1,12 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
 use typst::{
     diag::{FileError, FileResult},
+    foundations::Datetime,
     syntax::{FileId, Source},
 };
 
@@ -13,3 +20,33 @@ pub(crate) fn bytes_to_source(id: FileId, bytes: &[u8]) -> FileResult<Source> {
     let contents = contents.trim_start_matches('\u{feff}');
     Ok(Source::new(id, contents.to_owned()))
 }
+
+pub(crate) fn datetime(now: DateTime<Utc>) -> Option<Datetime> {
+    Datetime::from_ymd_hms(
+        now.year(),
+        now.month() as u8,
+        now.day() as u8,
+        now.hour() as u8,
+        now.minute() as u8,
+        now.second() as u8,
+    )
+}
+
+/// Write `contents` to a temporary file next to `path` and rename it, so a crash can't leave
+/// a truncated file.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    // Unique per process and call, as multiple processes may share the directory.
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".tmp-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = PathBuf::from(tmp);
+    let result = fs::write(&tmp, contents).and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
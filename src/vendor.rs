@@ -0,0 +1,307 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use binstall_tar::Archive;
+use typst::diag::{FileError, FileResult};
+use typst::foundations::Bytes;
+use typst::syntax::{ast, package::PackageSpec, FileId, Source, SyntaxKind, SyntaxNode};
+
+use crate::file_resolver::FileResolver;
+use crate::package_resolver::{
+    compose_cache_file_path, download_package, matches_package_pattern, unpack_atomically,
+    PackageMirror, PackagePolicy, RegistryClient,
+};
+use crate::util::{bytes_to_source, not_found};
+use crate::TypstAsLibError;
+
+/// Download all packages, that the templates in `template_dir` import (also the packages,
+/// that those packages import), into `out_dir`. Serve them with `VendoredPackageResolver`,
+/// e.g. when the production environment has no network access.
+///
+/// Packages, that already exist in `out_dir`, are not downloaded again. Only packages of the
+/// `preview` namespace can be downloaded, all others are skipped.
+/// Returns the vendored packages in sorted order.
+///
+/// Example:
+/// ```rust,no_run
+/// # use typst_as_lib::vendor::{vendor_packages, VendoredPackageResolver};
+/// # use typst_as_lib::TypstTemplate;
+/// # static source: &str = include_str!("../examples/templates/template.typ");
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let fonts = vec![font];
+/// // In a build script or a CI step:
+/// vendor_packages("./templates", "./vendor/packages")?;
+/// // At runtime:
+/// let template = TypstTemplate::new(fonts, source)
+///     .add_file_resolver(VendoredPackageResolver::from_dir("./vendor/packages"));
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub fn vendor_packages<P, Q>(
+    template_dir: P,
+    out_dir: Q,
+) -> Result<Vec<PackageSpec>, TypstAsLibError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    vendor_packages_with_options(template_dir, out_dir, &Default::default())
+}
+
+/// Like `vendor_packages()`, but `options` control, which packages end up in `out_dir`.
+/// See `VendorOptions`.
+pub fn vendor_packages_with_options<P, Q>(
+    template_dir: P,
+    out_dir: Q,
+    options: &VendorOptions,
+) -> Result<Vec<PackageSpec>, TypstAsLibError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let VendorOptions {
+        include,
+        exclude,
+        pinned_versions,
+        local_packages,
+        policy,
+        client,
+    } = options;
+    let out_dir = out_dir.as_ref();
+    let mut pending = Vec::new();
+    scan_dir(template_dir.as_ref(), &mut pending)?;
+    pending.extend(local_packages.iter().map(|(package, _)| package.clone()));
+
+    let mut vendored = HashSet::new();
+    while let Some(package) = pending.pop() {
+        if vendored.contains(&package) {
+            continue;
+        }
+        let is_included = include.is_empty()
+            || include
+                .iter()
+                .any(|pattern| matches_package_pattern(pattern, &package));
+        if !is_included
+            || exclude
+                .iter()
+                .any(|pattern| matches_package_pattern(pattern, &package))
+        {
+            continue;
+        }
+        let local_package = local_packages
+            .iter()
+            .find(|(local_package, _)| *local_package == package);
+        let dir = compose_cache_file_path(out_dir, &package)?;
+        if let Some((_, path)) = local_package {
+            copy_dir(path, &dir)?;
+        } else if package.namespace != "preview" {
+            continue;
+        } else if !dir.exists() {
+            let download = pinned_versions
+                .iter()
+                .find(|pinned| pinned.namespace == package.namespace && pinned.name == package.name)
+                .unwrap_or(&package);
+            policy.check(download).map_err(FileError::Package)?;
+            let archive = download_package(client, download)?;
+            unpack_atomically(Archive::new(&archive[..]), &dir)?;
+        }
+        scan_dir(&dir, &mut pending)?;
+        vendored.insert(package);
+    }
+    let mut vendored: Vec<_> = vendored.into_iter().collect();
+    vendored.sort_by_key(ToString::to_string);
+    Ok(vendored)
+}
+
+/// Controls, which packages `vendor_packages_with_options()` writes into the output
+/// directory.
+///
+/// Include and exclude patterns are a namespace (`preview`), a namespace and a package name
+/// (`preview/cetz`) or a package with version (`preview/cetz:0.3.1`).
+///
+/// Example:
+/// ```rust,no_run
+/// # use std::str::FromStr;
+/// # use typst::syntax::package::PackageSpec;
+/// # use typst_as_lib::vendor::{vendor_packages_with_options, VendorOptions};
+/// let options = VendorOptions::new()
+///     .with_exclude("preview/lorem")
+///     .with_pinned_version(PackageSpec::from_str("@preview/cetz:0.3.1").unwrap())
+///     .with_local_package(PackageSpec::from_str("@local/letterhead:0.1.0").unwrap(), "./letterhead");
+/// vendor_packages_with_options("./templates", "./vendor/packages", &options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VendorOptions {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    pinned_versions: Vec<PackageSpec>,
+    local_packages: Vec<(PackageSpec, PathBuf)>,
+    policy: PackagePolicy,
+    client: RegistryClient,
+}
+
+impl VendorOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Only vendor packages, that match one of the include patterns. If no include pattern is
+    /// set, all packages are vendored.
+    pub fn with_include<S>(mut self, pattern: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Don't vendor packages, that match the pattern. Takes precedence over the include
+    /// patterns.
+    pub fn with_exclude<S>(mut self, pattern: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Download this version, whichever version of the package the templates import. It is
+    /// stored under the imported version, so the templates don't need to be changed.
+    pub fn with_pinned_version(mut self, package: PackageSpec) -> Self {
+        self.pinned_versions.push(package);
+        self
+    }
+
+    /// Copy the package from a local directory instead of downloading it, e.g. for in-house
+    /// packages of the `local` namespace. It is vendored, even if no template imports it.
+    pub fn with_local_package<P>(mut self, package: PackageSpec, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.local_packages.push((package, path.into()));
+        self
+    }
+
+    /// Fail, if a package, that would be downloaded, is not allowed by the policy. In contrast
+    /// to `VendorOptions::with_exclude()`, it is an error.
+    pub fn with_policy(mut self, policy: PackagePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Download from this package repository. See `PackageResolverBuilder::with_mirror()`.
+    pub fn with_mirror(mut self, mirror: PackageMirror) -> Self {
+        self.client = self.client.with_mirror(mirror);
+        self
+    }
+
+    /// `User-Agent` header of all requests. See `PackageResolverBuilder::user_agent()`.
+    pub fn with_user_agent<S>(mut self, user_agent: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.client = self.client.with_user_agent(user_agent);
+        self
+    }
+
+    /// Modify every request before it is sent. See `PackageResolverBuilder::request_hook()`.
+    pub fn with_request_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(ureq::Request) -> ureq::Request + Send + Sync + 'static,
+    {
+        self.client = self.client.with_request_hook(hook);
+        self
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), TypstAsLibError> {
+    std::fs::create_dir_all(to).map_err(|error| FileError::from_io(error, to))?;
+    let entries = std::fs::read_dir(from).map_err(|error| FileError::from_io(error, from))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|error| FileError::from_io(error, from))?
+            .path();
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        let target = to.join(file_name);
+        if path.is_dir() {
+            copy_dir(&path, &target)?;
+        } else {
+            std::fs::copy(&path, &target).map_err(|error| FileError::from_io(error, &path))?;
+        }
+    }
+    Ok(())
+}
+
+/// Collect the package imports of all `.typ` files in `dir` (recursively).
+pub(crate) fn scan_dir(dir: &Path, packages: &mut Vec<PackageSpec>) -> Result<(), TypstAsLibError> {
+    let entries = std::fs::read_dir(dir).map_err(|error| FileError::from_io(error, dir))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|error| FileError::from_io(error, dir))?
+            .path();
+        if path.is_dir() {
+            scan_dir(&path, packages)?;
+        } else if path.extension().is_some_and(|extension| extension == "typ") {
+            let text =
+                std::fs::read_to_string(&path).map_err(|error| FileError::from_io(error, &path))?;
+            collect_package_specs(&typst::syntax::parse(&text), packages);
+        }
+    }
+    Ok(())
+}
+
+/// All string literals, that are package specs, e.g. `"@preview/cetz:0.3.1"`.
+fn collect_package_specs(node: &SyntaxNode, packages: &mut Vec<PackageSpec>) {
+    if node.kind() == SyntaxKind::Str {
+        if let Some(package) = node
+            .cast::<ast::Str>()
+            .and_then(|string| PackageSpec::from_str(&string.get()).ok())
+        {
+            packages.push(package);
+        }
+    }
+    for child in node.children() {
+        collect_package_specs(child, packages);
+    }
+}
+
+/// Resolves package files from a directory, that was filled with `vendor_packages()`.
+/// Never downloads anything. Files, that are not part of a package, are not resolved.
+#[derive(Debug, Clone)]
+pub struct VendoredPackageResolver {
+    root: PathBuf,
+}
+
+impl VendoredPackageResolver {
+    pub fn from_dir<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self { root: path.into() }
+    }
+
+    fn resolve_bytes(&self, id: FileId) -> FileResult<Vec<u8>> {
+        let Some(package) = id.package() else {
+            return Err(not_found(id));
+        };
+        let dir = compose_cache_file_path(&self.root, package)?;
+        let path = id.vpath().resolve(&dir).ok_or_else(|| not_found(id))?;
+        std::fs::read(&path).map_err(|error| FileError::from_io(error, &path))
+    }
+}
+
+impl FileResolver for VendoredPackageResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let bytes = self.resolve_bytes(id)?;
+        Ok(Cow::Owned(bytes.into()))
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let bytes = self.resolve_bytes(id)?;
+        Ok(Cow::Owned(bytes_to_source(id, &bytes)?))
+    }
+}
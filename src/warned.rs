@@ -0,0 +1,69 @@
+use typst::diag::Warned;
+
+#[cfg(any(feature = "tracing", feature = "log"))]
+use crate::instrument;
+use crate::TypstAsLibError;
+
+/// Extension methods for the `Warned` output of the compile functions.
+///
+/// Example:
+/// ```rust
+/// # use typst::foundations::Dict;
+/// # use typst_as_lib::warned::WarnedExt;
+/// # use typst_as_lib::TypstTemplate;
+/// # let inputs = Dict::new();
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// # let template = TypstTemplate::new(vec![font], "Hello");
+/// # #[cfg(any(feature = "tracing", feature = "log"))]
+/// let pdf = template
+///     .compile_with_input(inputs)
+///     .log_warnings()
+///     .map_output(|doc| typst_pdf::pdf(&doc, &Default::default()))
+///     .into_result_strict()?;
+/// # Ok::<(), typst_as_lib::TypstAsLibError>(())
+/// ```
+pub trait WarnedExt<T> {
+    /// Treat warnings as errors. They are returned as `TypstAsLibError::TypstSource`.
+    fn into_result_strict(self) -> Result<T, TypstAsLibError>;
+
+    /// Log the warnings as `tracing` events (feature `tracing`) or with the `log` crate
+    /// (feature `log`).
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    fn log_warnings(self) -> Self;
+
+    /// Map the output, keeping the warnings. Errors of `f` are converted to
+    /// `TypstAsLibError`.
+    fn map_output<U, E, F>(self, f: F) -> Warned<Result<U, TypstAsLibError>>
+    where
+        F: FnOnce(T) -> Result<U, E>,
+        E: Into<TypstAsLibError>;
+}
+
+impl<T> WarnedExt<T> for Warned<Result<T, TypstAsLibError>> {
+    fn into_result_strict(self) -> Result<T, TypstAsLibError> {
+        let Warned { output, warnings } = self;
+        let output = output?;
+        if !warnings.is_empty() {
+            return Err(TypstAsLibError::TypstSource(warnings));
+        }
+        Ok(output)
+    }
+
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    fn log_warnings(self) -> Self {
+        for warning in &self.warnings {
+            instrument::compile_warning(warning);
+        }
+        self
+    }
+
+    fn map_output<U, E, F>(self, f: F) -> Warned<Result<U, TypstAsLibError>>
+    where
+        F: FnOnce(T) -> Result<U, E>,
+        E: Into<TypstAsLibError>,
+    {
+        let Warned { output, warnings } = self;
+        let output = output.and_then(|output| f(output).map_err(Into::into));
+        Warned { output, warnings }
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use typst::diag::{FileError, Warned};
+use typst::foundations::Dict;
+use typst::model::Document;
+use typst::syntax::{FileId, VirtualPath};
+
+use crate::{TypstAsLibError, TypstTemplate, TypstTemplateCollection};
+
+/// Watches file system roots and invalidates the cached files of a `TypstTemplate` or
+/// `TypstTemplateCollection`, when they change. Stops watching, when dropped.
+///
+/// Don't watch files, that are memory-mapped (see `FileSystemResolver::with_memory_map()`):
+/// editing them in place is undefined behavior.
+///
+/// Example:
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use typst_as_lib::watch::TemplateWatcher;
+/// # use typst_as_lib::TypstTemplate;
+/// # static TEMPLATE_FILE: &str = include_str!("../examples/templates/template.typ");
+/// # static ROOT: &str = "./templates";
+/// # fn write_preview(_: &typst::model::Document) {}
+/// # let font = typst::text::Font::new(typst::foundations::Bytes::from_static(include_bytes!("../examples/fonts/texgyrecursor-regular.otf")), 0).unwrap();
+/// let template = Arc::new(
+///     TypstTemplate::new(vec![font], TEMPLATE_FILE).with_file_system_resolver(ROOT),
+/// );
+/// let _watcher = TemplateWatcher::recompiling(template, [ROOT], None, |warned| {
+///     match warned.output {
+///         Ok(doc) => write_preview(&doc),
+///         Err(err) => eprintln!("{err}"),
+///     }
+/// })
+/// .expect("Could not watch templates!");
+/// ```
+pub struct TemplateWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl TemplateWatcher {
+    /// Watches `roots` recursively. After cache entries of changed files are invalidated,
+    /// `on_change` is called with the template and the `FileId`s of the changed files (relative
+    /// to the root, they were found in).
+    pub fn new<C, I, P, F>(
+        template: Arc<C>,
+        roots: I,
+        mut on_change: F,
+    ) -> Result<Self, TypstAsLibError>
+    where
+        C: AsRef<TypstTemplateCollection> + Send + Sync + 'static,
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+        F: FnMut(&C, &[FileId]) + Send + 'static,
+    {
+        let roots = roots
+            .into_iter()
+            .map(|root| {
+                let root = root.into();
+                root.canonicalize()
+                    .map_err(|error| FileError::from_io(error, &root))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let watched_roots = roots.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                if !(event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove()) {
+                    return;
+                }
+                let changed: Vec<FileId> = event
+                    .paths
+                    .iter()
+                    .filter_map(|path| {
+                        watched_roots
+                            .iter()
+                            .find_map(|root| path.strip_prefix(root).ok())
+                    })
+                    .map(|relative| FileId::new(None, VirtualPath::new(relative)))
+                    .collect();
+                if changed.is_empty() {
+                    return;
+                }
+                let collection: &TypstTemplateCollection = (*template).as_ref();
+                for id in &changed {
+                    collection.invalidate(*id);
+                }
+                on_change(&*template, &changed);
+            })
+            .map_err(|error| TypstAsLibError::Watch(format!("{error}")))?;
+        for root in &roots {
+            watcher
+                .watch(root, RecursiveMode::Recursive)
+                .map_err(|error| TypstAsLibError::Watch(format!("{error}")))?;
+        }
+        Ok(Self { _watcher: watcher })
+    }
+
+    /// Watches `roots` recursively and recompiles `template` (with `inputs`, if set), when
+    /// a file changed. `on_compiled` is called with the new document or the diagnostics.
+    pub fn recompiling<I, P, F>(
+        template: Arc<TypstTemplate>,
+        roots: I,
+        inputs: Option<Dict>,
+        mut on_compiled: F,
+    ) -> Result<Self, TypstAsLibError>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+        F: FnMut(Warned<Result<Document, TypstAsLibError>>) + Send + 'static,
+    {
+        Self::new(template, roots, move |template, _| {
+            let warned = match &inputs {
+                Some(inputs) => template.compile_with_input(inputs.clone()),
+                None => template.compile(),
+            };
+            on_compiled(warned);
+        })
+    }
+}